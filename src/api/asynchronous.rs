@@ -0,0 +1,138 @@
+//! A client of the API on `tokio`, for fetching many wikis concurrently.
+
+use super::{
+    fixture, parse_query, read_dump, read_file, read_local_settings, site, Endpoint,
+    EndpointNewError, Error, ErrorKind, Fetched,
+};
+use std::time;
+
+/// Fetches without blocking a thread per request, for async applications and for fetching many
+/// wikis concurrently.  Unlike `Client`, it neither logs in nor preflights, probes or looks up
+/// archived responses, and the corresponding options are ignored.
+pub struct AsyncClient {
+    client: reqwest::Client,
+    options: super::Options,
+}
+
+impl AsyncClient {
+    /// A client with these options.
+    pub fn new(options: super::Options) -> Result<Self, reqwest::Error> {
+        let mut builder = reqwest::Client::builder().user_agent(super::user_agent());
+        if let Some(timeout) = options.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(timeout) = options.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(proxy) = &options.proxy {
+            builder = builder.proxy(reqwest::Proxy::all(proxy.as_str())?);
+        }
+        for certificate in &options.root_certificates {
+            builder = builder.add_root_certificate(certificate.clone());
+        }
+        if options.insecure {
+            builder = builder.danger_accept_invalid_certs(true);
+        }
+        if let Some(identity) = &options.identity {
+            builder = builder.identity(identity.clone());
+        }
+        if let Some(cookies) = &options.cookies {
+            builder = builder.cookie_provider(cookies.clone());
+        }
+        let codings = options
+            .compression
+            .as_deref()
+            .unwrap_or(super::DEFAULT_COMPRESSION);
+        builder = builder
+            .gzip(codings.contains(&super::Compression::Gzip))
+            .deflate(codings.contains(&super::Compression::Deflate))
+            .brotli(codings.contains(&super::Compression::Brotli));
+        builder = builder
+            .default_headers(super::default_headers(&options))
+            .redirect(super::redirect_policy(options.max_redirects.unwrap_or(10)));
+        let client = builder.build()?;
+        Ok(Self { client, options })
+    }
+
+    /// The siteinfo of `domain`, like `Client::fetch_query`.
+    pub async fn fetch_async(&self, domain: &str) -> Result<Fetched, Error> {
+        let mut attempt = 0;
+        loop {
+            match self.fetch_once(domain).await {
+                Err(kind)
+                    if attempt < self.options.retries
+                        && kind.is_retryable(self.options.retry_all_errors) =>
+                {
+                    let delay = time::Duration::from_secs(1 << attempt.min(6));
+                    attempt += 1;
+                    log::warn!(
+                        "{}: {} (retry {}/{} in {:?})",
+                        domain,
+                        kind,
+                        attempt,
+                        self.options.retries,
+                        delay
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                result => return result.map_err(|kind| Error::new(domain, kind)),
+            }
+        }
+    }
+
+    async fn fetch_once(&self, domain: &str) -> Result<Fetched, ErrorKind> {
+        let site: site::Site = domain.parse().map_err(EndpointNewError::from)?;
+        let url = Endpoint::new_url(&site, &self.options, &Endpoint::categories(&self.options))
+            .map_err(EndpointNewError::from)?;
+        log::debug!("url = {}", url);
+        if site.is_insecure() && self.options.replay.is_none() {
+            log::warn!("fetching over plain HTTP, unencrypted: {}", url);
+        }
+        if let Some(name) = &self.options.fixture {
+            log::warn!(
+                "using bundled fixture {:?} instead of the API response",
+                name
+            );
+            let raw = fixture::get(name)
+                .ok_or_else(|| ErrorKind::UnknownFixture(name.clone()))?
+                .to_owned();
+            let query = parse_query(&raw)?;
+            return Ok(Fetched { query, raw, url });
+        }
+        if let Some(path) = &self.options.from_file {
+            return read_file(path, url);
+        }
+        if let Some(path) = &self.options.from_dump {
+            return read_dump(path, url);
+        }
+        if let Some(path) = &self.options.from_local_settings {
+            return read_local_settings(path, url);
+        }
+        if let Some(cassette) = &self.options.cassette {
+            let interaction = cassette
+                .play(&url)
+                .ok_or_else(|| ErrorKind::NotRecorded(url.to_string()))?;
+            let query = parse_query(&interaction.body)?;
+            return Ok(Fetched {
+                query,
+                raw: interaction.body.clone(),
+                url: interaction.url.clone(),
+            });
+        }
+        let request = if self.options.post {
+            let (url, form) = super::post_form(&url);
+            self.client.post(url).form(&form)
+        } else {
+            self.client.get(url)
+        };
+        let response = request.send().await?.error_for_status()?;
+        let url = response.url().clone();
+        let raw = if super::is_zstd(response.headers()) {
+            super::decode_zstd(&response.bytes().await?)?
+        } else {
+            response.text().await?
+        };
+        let query = parse_query(&raw)?;
+        Ok(Fetched { query, raw, url })
+    }
+}
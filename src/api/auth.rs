@@ -0,0 +1,304 @@
+//! Authentication of the requests to wikis: bot passwords, and headers such as OAuth 2 tokens.
+
+use std::{
+    collections, error, fmt,
+    io::{self, Write},
+    process, str, sync,
+};
+use thiserror::Error;
+
+/// Authentication against the API endpoint of a wiki.  `login` is called once per endpoint before
+/// its first request, with a client that keeps the cookies set by the wiki, and again if the
+/// session expires when `has_session` is true; `authorize` is applied to every request sent to the
+/// endpoint.
+pub trait AuthProvider: Send + Sync {
+    /// Whether `login` starts a session, which may expire.
+    fn has_session(&self) -> bool {
+        false
+    }
+
+    /// Log in to the endpoint at `_url`, with the client whose cookies keep the session.
+    fn login(&self, _client: &reqwest::blocking::Client, _url: &url::Url) -> Result<(), AuthError> {
+        Ok(())
+    }
+
+    /// Authorize a request to the endpoint, e.g. by a header.
+    fn authorize(
+        &self,
+        request: reqwest::blocking::RequestBuilder,
+    ) -> reqwest::blocking::RequestBuilder {
+        request
+    }
+}
+
+/// Requests are not authenticated.
+#[derive(Debug)]
+pub struct NoAuth;
+
+/// Login with a bot password (`Special:BotPasswords`), where `user` is `<user>@<bot name>`.  The
+/// password is obtained at the first login, and kept for the others.
+#[derive(Debug)]
+pub struct BotPassword {
+    /// `<user>@<bot name>`.
+    pub user: String,
+    /// How the password is obtained.
+    pub password: Password,
+    // By host, except those prompted for, which are of all hosts.
+    obtained: sync::Mutex<collections::BTreeMap<String, String>>,
+}
+
+/// How the password of a bot password is obtained.
+#[derive(Debug)]
+pub enum Password {
+    /// This password.
+    Given(String),
+    /// Run by the shell with the argument `get`, as a git credential helper: the protocol, host and
+    /// user name are written to its stdin, and the password is read from its stdout, as
+    /// `password=<password>`.
+    Command(String),
+    /// Read by this function, given the prompt, e.g. from the terminal without echo.
+    Prompt(fn(&str) -> Result<String, io::Error>),
+}
+
+/// A header sent with every request, parsed from `Name: value`.
+#[derive(Clone, Debug)]
+pub struct Header {
+    /// The name of the header.
+    pub name: reqwest::header::HeaderName,
+    /// The value of the header.
+    pub value: reqwest::header::HeaderValue,
+}
+
+/// Value of the `assert` API parameter, which makes the API fail if the request is not
+/// authenticated as a user, or as a user with the `bot` right.
+#[derive(Clone, Copy, Debug)]
+pub enum Assert {
+    /// `assert=user`.
+    User,
+    /// `assert=bot`.
+    Bot,
+}
+
+/// A failure to log in.
+#[derive(Debug, Error)]
+pub enum AuthError {
+    /// A request failed.
+    #[error("{0}")]
+    Reqwest(#[from] reqwest::Error),
+    /// The response to the request of a login token lacks it.
+    #[error("no login token in response")]
+    TokenNotFound,
+    /// The wiki rejected the login, for this reason.
+    #[error("login failed: {0}")]
+    Login(String),
+    /// The password cannot be obtained, for this reason.
+    #[error("cannot obtain the password: {0}")]
+    Password(String),
+    /// Of another `AuthProvider`.
+    #[error("{0}")]
+    Other(Box<dyn error::Error + Send + Sync>),
+}
+
+/// An invalid header.
+#[derive(Debug, Error)]
+pub enum HeaderParseError {
+    /// Not of the form `Name: value`.
+    #[error("expected `Name: value`")]
+    Separator,
+    /// The name is invalid.
+    #[error("invalid header name: {0}")]
+    Name(#[from] reqwest::header::InvalidHeaderName),
+    /// The value is invalid.
+    #[error("invalid header value: {0}")]
+    Value(#[from] reqwest::header::InvalidHeaderValue),
+}
+
+/// Neither `user` nor `bot`.
+#[derive(Debug, Error)]
+#[error("invalid value: {0:?}")]
+pub struct AssertParseError(String);
+
+impl AuthProvider for NoAuth {}
+
+impl BotPassword {
+    /// A bot password of `user`, whose password is not obtained yet.
+    pub fn new(user: String, password: Password) -> Self {
+        Self {
+            user,
+            password,
+            obtained: Default::default(),
+        }
+    }
+
+    fn password(&self, url: &url::Url) -> Result<String, AuthError> {
+        let host = match self.password {
+            Password::Given(ref password) => return Ok(password.clone()),
+            Password::Command(..) => url.host_str().unwrap_or_default(),
+            Password::Prompt(..) => "",
+        };
+        // Locked while obtaining, so that concurrent logins prompt once.
+        let mut obtained = self.obtained.lock().unwrap();
+        if let Some(password) = obtained.get(host) {
+            return Ok(password.clone());
+        }
+        let password = match &self.password {
+            Password::Command(command) => self.credential(command, url)?,
+            Password::Prompt(prompt) => prompt(&format!("Bot password for {}: ", self.user))
+                .map_err(|e| {
+                    AuthError::Password(format!(
+                        "cannot prompt on the terminal ({}), give it by `--login-password`, its \
+                            environment variable or `--credential-command`",
+                        e
+                    ))
+                })?,
+            Password::Given(password) => password.clone(),
+        };
+        obtained.insert(host.to_owned(), password.clone());
+        Ok(password)
+    }
+
+    fn credential(&self, command: &str, url: &url::Url) -> Result<String, AuthError> {
+        log::info!("obtain the password from {:?} ...", command);
+        let error = |e: io::Error| AuthError::Password(format!("{:?}: {}", command, e));
+        let mut child = process::Command::new("sh")
+            .arg("-c")
+            .arg(format!("{} get", command))
+            .stdin(process::Stdio::piped())
+            .stdout(process::Stdio::piped())
+            .spawn()
+            .map_err(error)?;
+        let input = format!(
+            "protocol={}\nhost={}\nusername={}\n\n",
+            url.scheme(),
+            url.host_str().unwrap_or_default(),
+            self.user
+        );
+        // Helpers may exit without reading their input.
+        if let Some(mut stdin) = child.stdin.take() {
+            let _ = stdin.write_all(input.as_bytes());
+        }
+        let output = child.wait_with_output().map_err(error)?;
+        if !output.status.success() {
+            return Err(AuthError::Password(format!(
+                "{:?} failed with {}",
+                command, output.status
+            )));
+        }
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .find_map(|line| line.strip_prefix("password="))
+            .map(str::to_owned)
+            .ok_or_else(|| AuthError::Password(format!("{:?} returned no password", command)))
+    }
+}
+
+impl AuthProvider for BotPassword {
+    fn has_session(&self) -> bool {
+        true
+    }
+    fn login(&self, client: &reqwest::blocking::Client, url: &url::Url) -> Result<(), AuthError> {
+        let password = self.password(url)?;
+        let mut tokens_url = url.clone();
+        tokens_url.query_pairs_mut().clear().extend_pairs([
+            ("action", "query"),
+            ("meta", "tokens"),
+            ("type", "login"),
+            ("format", "json"),
+            ("formatversion", "2"),
+        ]);
+        let tokens: serde_json::Value = client
+            .get(tokens_url.as_ref())
+            .send()?
+            .error_for_status()?
+            .json()?;
+        let token = tokens["query"]["tokens"]["logintoken"]
+            .as_str()
+            .ok_or(AuthError::TokenNotFound)?;
+
+        let mut login_url = url.clone();
+        login_url.set_query(None);
+        let response: serde_json::Value = client
+            .post(login_url.as_ref())
+            .form(&[
+                ("action", "login"),
+                ("lgname", &self.user),
+                ("lgpassword", &password),
+                ("lgtoken", token),
+                ("format", "json"),
+                ("formatversion", "2"),
+            ])
+            .send()?
+            .error_for_status()?
+            .json()?;
+        let login = &response["login"];
+        match login["result"].as_str() {
+            Some("Success") => {
+                log::info!("logged in as {}", login["lgusername"]);
+                Ok(())
+            }
+            result => Err(AuthError::Login(
+                login["reason"]
+                    .as_str()
+                    .or(result)
+                    .unwrap_or("unexpected response")
+                    .to_owned(),
+            )),
+        }
+    }
+}
+
+impl AuthProvider for Header {
+    fn authorize(
+        &self,
+        request: reqwest::blocking::RequestBuilder,
+    ) -> reqwest::blocking::RequestBuilder {
+        request.header(&self.name, &self.value)
+    }
+}
+
+impl Header {
+    /// `Authorization: Bearer <token>`, e.g. of an OAuth 2 owner-only consumer.
+    pub fn bearer(token: &str) -> Result<Self, HeaderParseError> {
+        let mut value: reqwest::header::HeaderValue = format!("Bearer {}", token.trim()).parse()?;
+        value.set_sensitive(true);
+        Ok(Self {
+            name: reqwest::header::AUTHORIZATION,
+            value,
+        })
+    }
+}
+
+impl str::FromStr for Header {
+    type Err = HeaderParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, value) = s.split_once(':').ok_or(HeaderParseError::Separator)?;
+        let mut value: reqwest::header::HeaderValue = value.trim().parse()?;
+        value.set_sensitive(true);
+        Ok(Self {
+            name: name.trim().parse()?,
+            value,
+        })
+    }
+}
+
+impl fmt::Display for Assert {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::User => "user",
+            Self::Bot => "bot",
+        })
+    }
+}
+
+impl str::FromStr for Assert {
+    type Err = AssertParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "user" => Ok(Self::User),
+            "bot" => Ok(Self::Bot),
+            _ => Err(AssertParseError(s.to_owned())),
+        }
+    }
+}
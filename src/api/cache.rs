@@ -0,0 +1,182 @@
+//! The cache of siteinfo responses.
+
+use super::site;
+use serde::{Deserialize, Serialize};
+use std::{fs, io, io::Read, io::Write, path, time};
+
+/// Raw siteinfo responses on disk, a gzip-compressed file per wiki, so that repeated runs with different output
+/// options do not fetch them again.  An entry is only used for the same request, and until it is
+/// older than `ttl`.
+#[derive(Clone, Debug)]
+pub struct Cache {
+    /// The directory of the entries, see `Cache::default_directory`.
+    pub directory: path::PathBuf,
+    /// The age up to which entries are used.
+    pub ttl: time::Duration,
+    /// Fetch again, and replace the entries.
+    pub refresh: bool,
+    /// Never fetch: use the entries however old, and fail for wikis not cached.
+    pub offline: bool,
+}
+
+/// A cached response.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Entry {
+    /// The canonical form of the wiki, see `site::key`.
+    pub domain: String,
+    /// The URL of the request, before any probe or redirect.
+    pub request: url::Url,
+    /// The URL of the response.
+    pub url: url::Url,
+    /// In seconds since the Unix epoch.
+    pub fetched_at: u64,
+    /// The response as received.
+    pub raw: String,
+}
+
+impl Cache {
+    /// `siteinfo` in the directory of the crate in the user cache directory, if there is one.
+    pub fn default_directory() -> Option<path::PathBuf> {
+        dirs::cache_dir().map(|dir| dir.join(env!("CARGO_PKG_NAME")).join("siteinfo"))
+    }
+
+    /// The file of the wiki with the canonical form `key`, see `site::key`.
+    pub fn path(&self, key: &str) -> path::PathBuf {
+        self.directory
+            .join(format!("{}.json.gz", site::file_name(key)))
+    }
+
+    /// The entry of the wiki with the canonical form `key`, if it is of `request`, and usable.
+    pub fn get(&self, key: &str, request: &url::Url) -> Option<Entry> {
+        if self.refresh {
+            return None;
+        }
+        let path = self.path(key);
+        let entry = match Entry::load(&path) {
+            Ok(entry) => entry,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return None,
+            Err(e) => {
+                log::warn!("cannot read cached siteinfo {:?}, ignored: {}", path, e);
+                return None;
+            }
+        };
+        if entry.request != *request {
+            log::debug!("cached siteinfo {:?} is of another request", path);
+            return None;
+        }
+        if entry.age() > self.ttl && !self.offline {
+            log::debug!("cached siteinfo {:?} expired", path);
+            return None;
+        }
+        Some(entry)
+    }
+
+    /// The file of each entry, by file name, see `Entry::load`.
+    pub fn paths(&self) -> Result<Vec<path::PathBuf>, io::Error> {
+        let dir = match fs::read_dir(&self.directory) {
+            Ok(dir) => dir,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let mut paths = Vec::new();
+        for entry in dir {
+            let path = entry?.path();
+            if path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.ends_with(".json.gz"))
+            {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+        Ok(paths)
+    }
+
+    /// Returns whether the wiki with the canonical form `key` was cached.
+    pub fn remove(&self, key: &str) -> Result<bool, io::Error> {
+        match fs::remove_file(self.path(key)) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Remove the directory, with any files left behind by interrupted runs.  Returns the number of
+    /// entries removed.
+    pub fn clear(&self) -> Result<usize, io::Error> {
+        let count = self.paths()?.len();
+        match fs::remove_dir_all(&self.directory) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(count),
+        }
+    }
+
+    /// Failures are only logged, the response having been fetched anyway.
+    pub fn put(&self, key: &str, entry: &Entry) {
+        let path = self.path(key);
+        log::debug!("cache siteinfo in {:?}", path);
+        if let Err(e) = entry.save(&path) {
+            log::warn!("cannot cache siteinfo in {:?}: {}", path, e);
+        }
+    }
+}
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+
+impl Entry {
+    /// An entry fetched now.
+    pub fn new(domain: String, request: url::Url, url: url::Url, raw: String) -> Self {
+        Self {
+            domain,
+            request,
+            url,
+            fetched_at: time::SystemTime::now()
+                .duration_since(time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            raw,
+        }
+    }
+
+    /// The entry in the file at `path`, compressed with gzip or not.
+    pub fn load(path: &path::Path) -> Result<Self, io::Error> {
+        Self::parse(&fs::read(path)?)
+    }
+
+    /// The entry in `contents`, compressed with gzip or not.
+    pub fn parse(contents: &[u8]) -> Result<Self, io::Error> {
+        let invalid = |e| io::Error::new(io::ErrorKind::InvalidData, e);
+        if !contents.starts_with(GZIP_MAGIC) {
+            return serde_json::from_slice(contents).map_err(invalid);
+        }
+        let mut decompressed = Vec::new();
+        flate2::read::GzDecoder::new(contents).read_to_end(&mut decompressed)?;
+        serde_json::from_slice(&decompressed).map_err(invalid)
+    }
+
+    /// Write the entry to the file at `path`, compressed with gzip.
+    pub fn save(&self, path: &path::Path) -> Result<(), io::Error> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let json =
+            serde_json::to_vec(self).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&json)?;
+        // Renamed into place, so that concurrent runs never read a partial entry.
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(format!(".{}.tmp", std::process::id()));
+        fs::write(&temporary, encoder.finish()?)?;
+        fs::rename(&temporary, path)
+    }
+
+    /// The time since the entry was fetched.
+    pub fn age(&self) -> time::Duration {
+        let fetched_at = time::UNIX_EPOCH + time::Duration::from_secs(self.fetched_at);
+        time::SystemTime::now()
+            .duration_since(fetched_at)
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,57 @@
+//! Recorded siteinfo responses, for reproducing runs.
+
+use serde::{Deserialize, Serialize};
+use std::{fs, io, path};
+
+/// The siteinfo responses fetched by a run, recorded by `--record` and replayed by
+/// `--replay-cassette`, so that a malformed response reported in a bug is captured once and
+/// reproduced deterministically.
+/// Only successful responses are recorded, by the URL of their request.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Cassette {
+    /// In the order recorded.
+    pub interactions: Vec<Interaction>,
+}
+
+/// A request, and its response.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Interaction {
+    /// The URL of the request.
+    pub request: url::Url,
+    /// The URL of the response, after any redirect.
+    pub url: url::Url,
+    /// The response as received.
+    pub body: String,
+}
+
+impl Cassette {
+    /// The cassette saved at `path`.
+    pub fn load(path: &path::Path) -> Result<Self, io::Error> {
+        let contents = fs::read_to_string(path)?;
+        serde_json::from_str(&contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Written again as a whole after each interaction, so that the responses fetched before a
+    /// crash or an interruption are kept.
+    pub fn save(&self, path: &path::Path) -> Result<(), io::Error> {
+        let contents = serde_json::to_string_pretty(self)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        fs::write(path, contents + "\n")
+    }
+
+    /// A request made again, e.g. after a session expired, replaces the earlier interaction.
+    pub fn record(&mut self, interaction: Interaction) {
+        self.interactions
+            .retain(|recorded| recorded.request != interaction.request);
+        self.interactions.push(interaction);
+    }
+
+    /// The interaction of `request`, if recorded.
+    pub fn play(&self, request: &url::Url) -> Option<&Interaction> {
+        self.interactions
+            .iter()
+            .find(|interaction| interaction.request == *request)
+    }
+}
@@ -0,0 +1,260 @@
+//! The siteinfo header of MediaWiki XML dumps.
+
+use quick_xml::events::Event;
+use std::{fs, io, path};
+use thiserror::Error;
+
+/// What the `<siteinfo>` header of a MediaWiki XML dump tells of the wiki.  The rest of the
+/// configuration is not in dumps, and is filled in from the defaults of MediaWiki and of the parser
+/// by `response`.
+#[derive(Debug, Default)]
+pub struct SiteInfo {
+    /// `<sitename>`.
+    pub sitename: Option<String>,
+    /// The URL of the main page.
+    pub base: Option<String>,
+    /// `<generator>`, e.g. `MediaWiki 1.42.0-wmf.5`.
+    pub generator: Option<String>,
+    /// `<case>` of the titles, e.g. `first-letter`.
+    pub case: Option<String>,
+    /// `<namespaces>`.
+    pub namespaces: Vec<Namespace>,
+}
+
+/// A `<namespace>` of a dump.
+#[derive(Debug)]
+pub struct Namespace {
+    /// The `key` attribute.
+    pub id: i64,
+    /// The `case` attribute.
+    pub case: Option<String>,
+    /// The localized name, empty for the main namespace.
+    pub name: String,
+}
+
+/// A failure to read the header of a dump.
+#[derive(Debug, Error)]
+pub enum DumpError {
+    /// The file cannot be read or decompressed.
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    /// The file is not valid XML.
+    #[error("invalid XML: {0}")]
+    Xml(#[from] quick_xml::Error),
+    /// The `key` of a namespace is not a number.
+    #[error("invalid namespace key {0:?}")]
+    Key(String),
+    /// The dump has no siteinfo header.
+    #[error("no `<siteinfo>` before the first page")]
+    NotFound,
+}
+
+/// The canonical names of the namespaces of MediaWiki, valid on every wiki besides the localized
+/// names of the dump.
+pub const CANONICAL_NAMESPACES: &[(i64, &str)] = &[
+    (-2, "Media"),
+    (-1, "Special"),
+    (1, "Talk"),
+    (2, "User"),
+    (3, "User talk"),
+    (4, "Project"),
+    (5, "Project talk"),
+    (6, "File"),
+    (7, "File talk"),
+    (8, "MediaWiki"),
+    (9, "MediaWiki talk"),
+    (10, "Template"),
+    (11, "Template talk"),
+    (12, "Help"),
+    (13, "Help talk"),
+    (14, "Category"),
+    (15, "Category talk"),
+];
+
+// The aliases of namespaces that MediaWiki defines for every wiki.
+const NAMESPACE_ALIASES: &[(i64, &str)] = &[(6, "Image"), (7, "Image talk")];
+
+// The link trail of MediaWiki's English messages, used by wikis whose language does not define
+// another.
+const LINK_TRAIL: &str = "/^([a-z]+)(.*)$/sD";
+
+impl SiteInfo {
+    /// Only the header is read, so that it is quick even for dumps of all pages.  Dumps compressed
+    /// with bzip2 or gzip are decompressed by their extension.
+    pub fn read(path: &path::Path) -> Result<Self, DumpError> {
+        let file = fs::File::open(path)?;
+        let reader: Box<dyn io::Read> = match path.extension().and_then(|e| e.to_str()) {
+            Some("bz2") => Box::new(bzip2::read::MultiBzDecoder::new(file)),
+            Some("gz") => Box::new(flate2::read::MultiGzDecoder::new(file)),
+            _ => Box::new(file),
+        };
+        Self::parse(io::BufReader::new(reader))
+    }
+
+    fn parse(reader: impl io::BufRead) -> Result<Self, DumpError> {
+        let mut reader = quick_xml::Reader::from_reader(reader);
+        reader.config_mut().trim_text(true);
+        let mut buf = Vec::new();
+        let mut site_info = None::<Self>;
+        // The element whose text is read, and the attributes of a namespace.
+        let mut element = Vec::new();
+        let mut namespace = None;
+        loop {
+            match reader.read_event_into(&mut buf)? {
+                Event::Start(start) => {
+                    let name = start.local_name().as_ref().to_owned();
+                    match name.as_slice() {
+                        b"siteinfo" => site_info = Some(Self::default()),
+                        b"page" => break,
+                        b"namespace" => namespace = Some(Self::namespace(&start)?),
+                        _ => {}
+                    }
+                    element = name;
+                }
+                // E.g. the main namespace, which has no name.
+                Event::Empty(empty) if empty.local_name().as_ref() == b"namespace" => {
+                    if let Some(site_info) = &mut site_info {
+                        let (id, case) = Self::namespace(&empty)?;
+                        site_info.namespaces.push(Namespace {
+                            id,
+                            case,
+                            name: String::new(),
+                        });
+                    }
+                }
+                Event::Text(text) => {
+                    let Some(site_info) = &mut site_info else {
+                        continue;
+                    };
+                    let text = text.unescape()?.into_owned();
+                    match element.as_slice() {
+                        b"sitename" => site_info.sitename = Some(text),
+                        b"base" => site_info.base = Some(text),
+                        b"generator" => site_info.generator = Some(text),
+                        b"case" => site_info.case = Some(text),
+                        b"namespace" => {
+                            if let Some((id, case)) = namespace.take() {
+                                site_info.namespaces.push(Namespace {
+                                    id,
+                                    case,
+                                    name: text,
+                                });
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                Event::End(end) => {
+                    element.clear();
+                    if end.local_name().as_ref() == b"siteinfo" {
+                        break;
+                    }
+                    // A namespace without text, written as a start and an end tag.
+                    if end.local_name().as_ref() == b"namespace" {
+                        if let (Some(site_info), Some((id, case))) =
+                            (&mut site_info, namespace.take())
+                        {
+                            site_info.namespaces.push(Namespace {
+                                id,
+                                case,
+                                name: String::new(),
+                            });
+                        }
+                    }
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+            buf.clear();
+        }
+        site_info.ok_or(DumpError::NotFound)
+    }
+
+    fn namespace(
+        start: &quick_xml::events::BytesStart,
+    ) -> Result<(i64, Option<String>), DumpError> {
+        let attribute = |name: &str| -> Result<Option<String>, DumpError> {
+            match start
+                .try_get_attribute(name)
+                .map_err(quick_xml::Error::from)?
+            {
+                Some(attribute) => Ok(Some(attribute.unescape_value()?.into_owned())),
+                None => Ok(None),
+            }
+        };
+        let key = attribute("key")?.unwrap_or_default();
+        let id = key.parse().map_err(|_| DumpError::Key(key))?;
+        Ok((id, attribute("case")?))
+    }
+
+    /// The host of the main page, e.g. `en.wikipedia.org`.
+    pub fn domain(&self) -> Option<String> {
+        let base = url::Url::parse(self.base.as_deref()?).ok()?;
+        Some(base.host_str()?.to_owned())
+    }
+
+    /// A siteinfo response as returned by the API, of what the dump tells and of the defaults: the
+    /// canonical names and aliases of namespaces, the English link trail, and the extension tags,
+    /// magic words and protocols of the parser defaults.
+    pub fn response(&self) -> serde_json::Value {
+        let defaults = |field: &str| {
+            crate::generate::DEFAULTS
+                .iter()
+                .find(|(name, _)| *name == field)
+                .map_or(&[][..], |(_, values)| values)
+        };
+        let namespaces: serde_json::Map<_, _> = self
+            .namespaces
+            .iter()
+            .map(|namespace| {
+                let canonical = CANONICAL_NAMESPACES
+                    .iter()
+                    .find(|(id, _)| *id == namespace.id)
+                    .map(|(_, name)| name);
+                let value = serde_json::json!({
+                    "id": namespace.id,
+                    "case": namespace.case,
+                    "name": namespace.name,
+                    "canonical": canonical,
+                });
+                (namespace.id.to_string(), value)
+            })
+            .collect();
+        let magic_words = defaults("magic_words")
+            .iter()
+            .map(|name| {
+                serde_json::json!({
+                    "name": name,
+                    "aliases": [format!("__{}__", name.to_uppercase())],
+                })
+            })
+            .chain(defaults("redirect_magic_words").iter().map(|name| {
+                serde_json::json!({
+                    "name": name,
+                    "aliases": [format!("#{}", name.to_uppercase())],
+                })
+            }))
+            .collect::<Vec<_>>();
+        serde_json::json!({
+            "query": {
+                "extensiontags": defaults("extension_tags")
+                    .iter()
+                    .map(|tag| format!("<{}>", tag))
+                    .collect::<Vec<_>>(),
+                "general": {
+                    "case": self.case,
+                    "generator": self.generator,
+                    "linktrail": LINK_TRAIL,
+                    "sitename": self.sitename,
+                },
+                "magicwords": magic_words,
+                "namespacealiases": NAMESPACE_ALIASES
+                    .iter()
+                    .map(|(id, alias)| serde_json::json!({ "id": id, "alias": alias }))
+                    .collect::<Vec<_>>(),
+                "namespaces": namespaces,
+                "protocols": defaults("protocols"),
+            }
+        })
+    }
+}
@@ -0,0 +1,54 @@
+//! Minimized siteinfo responses bundled with the crate, to exercise the extraction and its errors
+//! offline and deterministically.
+
+/// The fixtures, by name.
+pub const FIXTURES: &[(&str, &str)] = &[
+    ("api-error", include_str!("../../fixtures/api-error.json")),
+    (
+        "api-warning",
+        include_str!("../../fixtures/api-warning.json"),
+    ),
+    ("extensions", include_str!("../../fixtures/extensions.json")),
+    (
+        "linktrail-empty",
+        include_str!("../../fixtures/linktrail-empty.json"),
+    ),
+    (
+        "linktrail-named-group",
+        include_str!("../../fixtures/linktrail-named-group.json"),
+    ),
+    (
+        "linktrail-no-group",
+        include_str!("../../fixtures/linktrail-no-group.json"),
+    ),
+    (
+        "linktrail-unicode",
+        include_str!("../../fixtures/linktrail-unicode.json"),
+    ),
+    (
+        "linktrail-unsupported-modifier",
+        include_str!("../../fixtures/linktrail-unsupported-modifier.json"),
+    ),
+    (
+        "magic-word-shared-alias",
+        include_str!("../../fixtures/magic-word-shared-alias.json"),
+    ),
+    ("minimal", include_str!("../../fixtures/minimal.json")),
+    (
+        "namespace-missing",
+        include_str!("../../fixtures/namespace-missing.json"),
+    ),
+    ("no-query", include_str!("../../fixtures/no-query.json")),
+    (
+        "unknown-field",
+        include_str!("../../fixtures/unknown-field.json"),
+    ),
+];
+
+/// The response of the fixture `name`, if there is one.
+pub fn get(name: &str) -> Option<&'static str> {
+    FIXTURES
+        .iter()
+        .find(|(fixture, _)| *fixture == name)
+        .map(|(_, raw)| *raw)
+}
@@ -0,0 +1,498 @@
+//! The configuration set by `LocalSettings.php` files.
+
+use super::dump;
+use std::{collections, fs, io, path};
+
+/// What a `LocalSettings.php` sets of the configuration, for wikis whose API cannot be reached.
+/// The file is not run, only its assignments of literals are read, so that computed settings are
+/// missed and conditions are not evaluated, and the rest is filled in from defaults, as for dumps.
+#[derive(Debug, Default)]
+pub struct LocalSettings {
+    /// `$wgSitename`.
+    pub sitename: Option<String>,
+    /// `$wgServer`.
+    pub server: Option<String>,
+    /// `$wgLanguageCode`.
+    pub language: Option<String>,
+    /// `$wgCapitalLinks`.
+    pub capital_links: Option<bool>,
+    /// `$wgMetaNamespace`.
+    pub meta_namespace: Option<String>,
+    /// `$wgMetaNamespaceTalk`.
+    pub meta_namespace_talk: Option<String>,
+    /// `$wgUrlProtocols` if assigned, replacing the defaults.
+    pub url_protocols: Option<Vec<String>>,
+    /// Added to `$wgUrlProtocols`, e.g. by `$wgUrlProtocols[] = ...`.
+    pub extra_url_protocols: Vec<String>,
+    /// `$wgExtraNamespaces`, by namespace id.
+    pub extra_namespaces: collections::BTreeMap<i64, String>,
+    /// `$wgNamespaceAliases`.
+    pub namespace_aliases: Vec<(String, i64)>,
+    /// Loaded by `wfLoadExtension` or `wfLoadExtensions`.
+    pub extensions: collections::BTreeSet<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Variable(String),
+    String(String),
+    Integer(i64),
+    Identifier(String),
+    Punct(&'static str),
+}
+
+#[derive(Debug)]
+enum Value {
+    String(String),
+    Integer(i64),
+    Bool(bool),
+    Array(Vec<(Option<Value>, Value)>),
+    Other,
+}
+
+// The namespace constants of MediaWiki, besides those defined by the file.
+const NAMESPACE_CONSTANTS: &[(&str, i64)] = &[
+    ("NS_MEDIA", -2),
+    ("NS_SPECIAL", -1),
+    ("NS_MAIN", 0),
+    ("NS_TALK", 1),
+    ("NS_USER", 2),
+    ("NS_USER_TALK", 3),
+    ("NS_PROJECT", 4),
+    ("NS_PROJECT_TALK", 5),
+    ("NS_FILE", 6),
+    ("NS_FILE_TALK", 7),
+    ("NS_IMAGE", 6),
+    ("NS_IMAGE_TALK", 7),
+    ("NS_MEDIAWIKI", 8),
+    ("NS_MEDIAWIKI_TALK", 9),
+    ("NS_TEMPLATE", 10),
+    ("NS_TEMPLATE_TALK", 11),
+    ("NS_HELP", 12),
+    ("NS_HELP_TALK", 13),
+    ("NS_CATEGORY", 14),
+    ("NS_CATEGORY_TALK", 15),
+];
+
+// Longest first, so that e.g. `=>` is not read as `=`.
+const PUNCTS: &[&str] = &[
+    "=>", "[]", "+=", ".=", "=", "[", "]", "(", ")", "{", "}", ",", ";", ".", "-", "?", ":",
+];
+
+impl LocalSettings {
+    /// The settings of the file at `path`.
+    pub fn read(path: &path::Path) -> Result<Self, io::Error> {
+        Ok(Self::parse(&fs::read_to_string(path)?))
+    }
+
+    /// The settings of `source`, ignoring what cannot be read.
+    pub fn parse(source: &str) -> Self {
+        let tokens = tokenize(source);
+        let mut settings = Self::default();
+        let mut constants: collections::BTreeMap<String, i64> = NAMESPACE_CONSTANTS
+            .iter()
+            .map(|(name, value)| (name.to_string(), *value))
+            .collect();
+        let end = |token: &Token| matches!(token, Token::Punct(";" | "{" | "}"));
+        for statement in tokens.split(end) {
+            settings.statement(statement, &mut constants);
+        }
+        settings
+    }
+
+    fn statement(&mut self, tokens: &[Token], constants: &mut collections::BTreeMap<String, i64>) {
+        let integer = |value: &Value, constants: &collections::BTreeMap<String, i64>| match value {
+            Value::Integer(n) => Some(*n),
+            Value::String(name) => constants.get(name).copied(),
+            _ => None,
+        };
+        match tokens {
+            // `$wgName = value`, `$wgName[key] = value` and `$wgName[] = value`.
+            [Token::Variable(name), rest @ ..] => {
+                let (key, rest) = match rest {
+                    [Token::Punct("["), rest @ ..] => {
+                        let mut i = 0;
+                        let key = value(rest, &mut i);
+                        match rest.get(i..) {
+                            Some([Token::Punct("]"), rest @ ..]) => (Some(key), rest),
+                            _ => return,
+                        }
+                    }
+                    [Token::Punct("[]"), rest @ ..] => (None, rest),
+                    _ => {
+                        let [Token::Punct(operator), rest @ ..] = rest else {
+                            return;
+                        };
+                        let mut i = 0;
+                        let value = value(rest, &mut i);
+                        self.assign(name, operator, value, constants);
+                        return;
+                    }
+                };
+                let [Token::Punct("="), rest @ ..] = rest else {
+                    return;
+                };
+                let mut i = 0;
+                let value = value(rest, &mut i);
+                match (name.as_str(), key, value) {
+                    ("wgUrlProtocols", None, Value::String(protocol)) => {
+                        self.extra_url_protocols.push(protocol)
+                    }
+                    ("wgExtraNamespaces", Some(key), Value::String(name)) => {
+                        if let Some(id) = integer(&key, constants) {
+                            self.extra_namespaces.insert(id, name);
+                        }
+                    }
+                    ("wgNamespaceAliases", Some(Value::String(alias)), value) => {
+                        if let Some(id) = integer(&value, constants) {
+                            self.namespace_aliases.push((alias, id));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            // `define('NS_NAME', 100)` and `const NS_NAME = 100`.
+            [Token::Identifier(function), Token::Punct("("), Token::String(name), Token::Punct(","), Token::Integer(value), ..]
+                if function.eq_ignore_ascii_case("define") =>
+            {
+                constants.insert(name.clone(), *value);
+            }
+            [Token::Identifier(keyword), Token::Identifier(name), Token::Punct("="), Token::Integer(value), ..]
+                if keyword == "const" =>
+            {
+                constants.insert(name.clone(), *value);
+            }
+            [Token::Identifier(function), Token::Punct("("), rest @ ..]
+                if function == "wfLoadExtension" || function == "wfLoadExtensions" =>
+            {
+                let mut i = 0;
+                match value(rest, &mut i) {
+                    Value::String(name) => {
+                        self.extensions.insert(name);
+                    }
+                    Value::Array(elements) => {
+                        for (_, element) in elements {
+                            if let Value::String(name) = element {
+                                self.extensions.insert(name);
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            // The legacy entry points, e.g. `require_once "$IP/extensions/Cite/Cite.php"`.
+            [Token::Identifier(keyword), rest @ ..]
+                if matches!(
+                    keyword.as_str(),
+                    "require" | "require_once" | "include" | "include_once"
+                ) =>
+            {
+                for token in rest {
+                    let Token::String(path) = token else {
+                        continue;
+                    };
+                    if let Some((_, rest)) = path.split_once("extensions/") {
+                        if let Some((name, _)) = rest.split_once('/') {
+                            self.extensions.insert(name.to_owned());
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn assign(
+        &mut self,
+        name: &str,
+        operator: &str,
+        value: Value,
+        constants: &collections::BTreeMap<String, i64>,
+    ) {
+        let string = |value: &Value| match value {
+            Value::String(s) => Some(s.clone()),
+            _ => None,
+        };
+        match (name, operator) {
+            ("wgSitename", "=") => self.sitename = string(&value),
+            ("wgServer", "=") => self.server = string(&value),
+            ("wgLanguageCode", "=") => self.language = string(&value),
+            ("wgMetaNamespace", "=") => self.meta_namespace = string(&value),
+            ("wgMetaNamespaceTalk", "=") => self.meta_namespace_talk = string(&value),
+            ("wgCapitalLinks", "=") => {
+                if let Value::Bool(capital_links) = value {
+                    self.capital_links = Some(capital_links);
+                }
+            }
+            ("wgUrlProtocols", "=") => {
+                if let Value::Array(elements) = value {
+                    let protocols = elements.iter().filter_map(|(_, v)| string(v)).collect();
+                    self.url_protocols = Some(protocols);
+                }
+            }
+            ("wgExtraNamespaces", "=" | "+=") | ("wgNamespaceAliases", "=" | "+=") => {
+                let Value::Array(elements) = value else {
+                    return;
+                };
+                let id = |value: &Value| match value {
+                    Value::Integer(n) => Some(*n),
+                    Value::String(name) => constants.get(name).copied(),
+                    _ => None,
+                };
+                for (key, value) in elements {
+                    match (name, key.as_ref().and_then(&id), &value, &key) {
+                        ("wgExtraNamespaces", Some(id), Value::String(name), _) => {
+                            self.extra_namespaces.insert(id, name.clone());
+                        }
+                        ("wgNamespaceAliases", _, value, Some(Value::String(alias))) => {
+                            if let Some(id) = id(value) {
+                                self.namespace_aliases.push((alias.clone(), id));
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The host of `$wgServer`, e.g. `wiki.example.org`.
+    pub fn domain(&self) -> Option<String> {
+        let server = self.server.as_deref()?;
+        let server = match server.strip_prefix("//") {
+            Some(rest) => format!("https://{}", rest),
+            None => server.to_owned(),
+        };
+        Some(url::Url::parse(&server).ok()?.host_str()?.to_owned())
+    }
+
+    /// A siteinfo response as returned by the API, of the settings and of the defaults, as by
+    /// `dump::SiteInfo::response`: the namespaces of MediaWiki by their canonical names, with the
+    /// project namespaces named after the wiki, and the extension tags of the enabled extensions
+    /// known to `extract::EXTENSION_TAG_SOURCES`.
+    pub fn response(&self) -> serde_json::Value {
+        let case = match self.capital_links {
+            Some(false) => "case-sensitive",
+            _ => "first-letter",
+        };
+        let meta = self
+            .meta_namespace
+            .clone()
+            .or_else(|| self.sitename.as_ref().map(|s| s.replace(' ', "_")));
+        let mut namespaces: collections::BTreeMap<i64, String> = dump::CANONICAL_NAMESPACES
+            .iter()
+            .map(|(id, name)| (*id, name.to_string()))
+            .chain([(0, String::new())])
+            .collect();
+        if let Some(meta) = &meta {
+            namespaces.insert(4, meta.clone());
+            let talk = self
+                .meta_namespace_talk
+                .clone()
+                .unwrap_or_else(|| format!("{}_talk", meta));
+            namespaces.insert(5, talk);
+        }
+        namespaces.extend(self.extra_namespaces.clone());
+        let site_info = dump::SiteInfo {
+            sitename: self.sitename.clone(),
+            base: self.server.clone(),
+            generator: None,
+            case: Some(case.to_owned()),
+            namespaces: namespaces
+                .into_iter()
+                .map(|(id, name)| dump::Namespace {
+                    id,
+                    case: Some(case.to_owned()),
+                    name: name.replace('_', " "),
+                })
+                .collect(),
+        };
+        let mut response = site_info.response();
+        let query = &mut response["query"];
+        let protocols = match &self.url_protocols {
+            Some(protocols) => protocols.clone(),
+            None => query["protocols"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|protocol| Some(protocol.as_str()?.to_owned()))
+                .collect(),
+        };
+        query["protocols"] = protocols
+            .into_iter()
+            .chain(self.extra_url_protocols.iter().cloned())
+            .collect();
+        // Compared without spaces and suffixes, e.g. `LabeledSectionTransclusion` and
+        // `SyntaxHighlight_GeSHi` as loaded, and `Labeled Section Transclusion` and
+        // `SyntaxHighlight` as reported by the API.
+        let extensions: collections::BTreeSet<_> = self
+            .extensions
+            .iter()
+            .map(|loaded| {
+                let stem = loaded.split('_').next().unwrap_or(loaded);
+                crate::extract::EXTENSION_TAG_SOURCES
+                    .iter()
+                    .map(|(_, name)| *name)
+                    .find(|name| stem.eq_ignore_ascii_case(&name.replace(' ', "")))
+                    .unwrap_or(loaded)
+            })
+            .collect();
+        query["extensiontags"] = crate::extract::EXTENSION_TAG_SOURCES
+            .iter()
+            .filter(|(_, name)| {
+                *name == crate::extract::CORE_EXTENSION || extensions.contains(name)
+            })
+            .map(|(tag, _)| serde_json::Value::from(format!("<{}>", tag)))
+            .collect();
+        query["extensions"] = extensions
+            .iter()
+            .map(|name| serde_json::json!({ "name": name }))
+            .collect();
+        if let Some(aliases) = query["namespacealiases"].as_array_mut() {
+            for (alias, id) in &self.namespace_aliases {
+                aliases.push(serde_json::json!({ "id": id, "alias": alias.replace('_', " ") }));
+            }
+        }
+        query["general"]["lang"] = self.language.clone().into();
+        response
+    }
+}
+
+fn tokenize(source: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut rest = source;
+    while let Some(c) = rest.chars().next() {
+        if c.is_whitespace() {
+            rest = &rest[c.len_utf8()..];
+        } else if rest.starts_with("<?php") {
+            rest = &rest[5..];
+        } else if rest.starts_with("?>") {
+            rest = &rest[2..];
+        } else if rest.starts_with("//") || c == '#' {
+            rest = rest.find('\n').map_or("", |end| &rest[end..]);
+        } else if rest.starts_with("/*") {
+            rest = rest[2..].find("*/").map_or("", |end| &rest[end + 4..]);
+        } else if c == '\'' || c == '"' {
+            let (s, after) = string(&rest[1..], c);
+            tokens.push(Token::String(s));
+            rest = after;
+        } else if c == '$' || c.is_alphabetic() || c == '_' {
+            let start = usize::from(c == '$');
+            let end = rest[start..]
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .map_or(rest.len(), |end| start + end);
+            let name = rest[start..end].to_owned();
+            tokens.push(match c {
+                '$' => Token::Variable(name),
+                _ => Token::Identifier(name),
+            });
+            rest = &rest[end.max(1)..];
+        } else if c.is_ascii_digit() {
+            let end = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            tokens.push(match rest[..end].parse() {
+                Ok(n) => Token::Integer(n),
+                Err(..) => Token::Punct("?"),
+            });
+            rest = &rest[end..];
+        } else if let Some(punct) = PUNCTS.iter().find(|p| rest.starts_with(**p)) {
+            tokens.push(Token::Punct(punct));
+            rest = &rest[punct.len()..];
+        } else {
+            // Anything else is of no statement read, e.g. `!`.
+            tokens.push(Token::Punct("?"));
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    tokens
+}
+
+// The contents of a string literal up to the `quote` closing it, and what follows.  Variables in
+// double-quoted strings are kept as they are.
+fn string(rest: &str, quote: char) -> (String, &str) {
+    let mut s = String::new();
+    let mut chars = rest.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some((_, c)) if c == quote || c == '\\' => s.push(c),
+                Some((_, 'n')) if quote == '"' => s.push('\n'),
+                Some((_, 't')) if quote == '"' => s.push('\t'),
+                Some((_, c)) => {
+                    s.push('\\');
+                    s.push(c);
+                }
+                None => s.push('\\'),
+            },
+            c if c == quote => return (s, &rest[i + 1..]),
+            c => s.push(c),
+        }
+    }
+    (s, "")
+}
+
+// A literal, a constant (as a string of its name), or an array of them, at `tokens[*i]`.
+fn value(tokens: &[Token], i: &mut usize) -> Value {
+    let Some(token) = tokens.get(*i) else {
+        return Value::Other;
+    };
+    *i += 1;
+    match token {
+        Token::String(s) => Value::String(s.clone()),
+        Token::Integer(n) => Value::Integer(*n),
+        Token::Punct("-") => match tokens.get(*i) {
+            Some(Token::Integer(n)) => {
+                *i += 1;
+                Value::Integer(-n)
+            }
+            _ => Value::Other,
+        },
+        Token::Identifier(name) if name.eq_ignore_ascii_case("true") => Value::Bool(true),
+        Token::Identifier(name) if name.eq_ignore_ascii_case("false") => Value::Bool(false),
+        Token::Identifier(name)
+            if name.eq_ignore_ascii_case("array") && tokens.get(*i) == Some(&Token::Punct("(")) =>
+        {
+            *i += 1;
+            array(tokens, i, ")")
+        }
+        Token::Identifier(name) => Value::String(name.clone()),
+        Token::Punct("[") => array(tokens, i, "]"),
+        _ => Value::Other,
+    }
+}
+
+fn array(tokens: &[Token], i: &mut usize, close: &'static str) -> Value {
+    let mut elements = Vec::new();
+    while *i < tokens.len() {
+        if tokens[*i] == Token::Punct(close) {
+            *i += 1;
+            break;
+        }
+        if tokens[*i] == Token::Punct(",") {
+            *i += 1;
+            continue;
+        }
+        let start = *i;
+        let first = value(tokens, i);
+        let element = if tokens.get(*i) == Some(&Token::Punct("=>")) {
+            *i += 1;
+            (Some(first), value(tokens, i))
+        } else {
+            (None, first)
+        };
+        elements.push(element);
+        // Skip the rest of an element that is not a literal, e.g. a concatenation.
+        while *i < tokens.len()
+            && tokens[*i] != Token::Punct(",")
+            && tokens[*i] != Token::Punct(close)
+        {
+            *i += 1;
+        }
+        if *i == start {
+            *i += 1;
+        }
+    }
+    Value::Array(elements)
+}
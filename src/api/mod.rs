@@ -1,46 +1,862 @@
+//! Fetching the siteinfo of wikis from the MediaWiki Action API, or from the offline sources
+//! standing in for it: saved responses, dumps, `LocalSettings.php`, caches and cassettes.
+
 use convert::TryInto;
-use err_derive::Error;
 use itertools::Itertools;
-use std::{convert, env};
+use std::{collections, convert, env, fmt, fs, io, path, sync, thread, time};
+use thiserror::Error;
 
+#[cfg(feature = "async")]
+pub mod asynchronous;
+pub mod auth;
+pub mod cache;
+pub mod cassette;
+pub mod dump;
+pub mod fixture;
+pub mod local_settings;
 pub mod response;
+pub mod site;
 
-struct Endpoint {
+/// A blocking client of the APIs of wikis, sharing its connections, sessions and request budget
+/// between them.
+pub struct Client {
     client: reqwest::blocking::Client,
+    options: Options,
+    auth: Box<dyn auth::AuthProvider>,
+    // Whether logged in, by wiki, locked apart so that logging in to one does not wait for another.
+    sessions: sync::Mutex<collections::BTreeMap<String, sync::Arc<sync::Mutex<bool>>>>,
+    requests: sync::atomic::AtomicU32,
+    recorded: sync::Mutex<cassette::Cassette>,
+}
+
+struct Endpoint<'c> {
+    client: &'c Client,
     url: url::Url,
+    authorize: bool,
+}
+
+/// The siteinfo of a wiki.
+#[derive(Debug)]
+pub struct Fetched {
+    /// The `query` of the response.
+    pub query: response::Query,
+    /// The response as received, e.g. for saving it.
+    pub raw: String,
+    /// The URL of the response, after any redirect.
+    pub url: url::Url,
+}
+
+/// How the siteinfo is fetched.  The default fetches it from the API of the wiki, with the
+/// timeouts and proxies of reqwest and without retries.
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    /// The path of the API of wikis given without a path, instead of `/w/api.php`, see
+    /// `site::Site::api_url`.
+    pub api_path: Option<String>,
+    /// Fetch the response archived by the Wayback Machine closest to this date (`YYYY-MM-DD`)
+    /// instead.
+    pub as_of: Option<String>,
+    /// Fail unless the requests are authenticated as such.
+    pub assert: Option<auth::Assert>,
+    /// Use and keep the responses in this cache.
+    pub cache: Option<cache::Cache>,
+    /// Replay the responses of this cassette instead of fetching them.
+    pub cassette: Option<sync::Arc<cassette::Cassette>>,
+    /// Also query the `extensions` siteinfo property, for the sources of extension tags.
+    pub extensions: bool,
+    /// Use the bundled fixture of this name instead of fetching, see `fixture::get`.
+    pub fixture: Option<String>,
+    /// Read this saved siteinfo response instead of connecting to the wiki.
+    pub from_file: Option<path::PathBuf>,
+    /// Read the header of this MediaWiki XML dump instead of connecting to the wiki.
+    pub from_dump: Option<path::PathBuf>,
+    /// Read the settings of this `LocalSettings.php` instead of connecting to the wiki.
+    pub from_local_settings: Option<path::PathBuf>,
+    /// Called before each attempt to fetch, and with each response before it is decoded.
+    pub hooks: Option<sync::Arc<dyn FetchHooks>>,
+    /// Send the query as a POST request, with a form-encoded body.
+    pub post: bool,
+    /// Check that the endpoint answers before fetching.
+    pub preflight: bool,
+    /// Query only the siteinfo properties supported by the wiki, as listed by its API.
+    pub probe: bool,
+    /// Fetch from the replay server at this address, e.g. `127.0.0.1:8080`, instead.
+    pub replay: Option<String>,
+    /// Retry transient failures this many times, with exponential backoff.
+    pub retries: u32,
+    /// Retry all failures, not only transient ones.
+    pub retry_all_errors: bool,
+    /// Of each request as a whole; that of reqwest if unset.
+    pub timeout: Option<time::Duration>,
+    /// Of connecting; that of reqwest if unset.
+    pub connect_timeout: Option<time::Duration>,
+    /// The proxy of all requests, instead of those of the environment, e.g.
+    /// `socks5h://localhost:1080`.
+    pub proxy: Option<url::Url>,
+    /// Trusted besides the system roots, see `read_certificates`.
+    pub root_certificates: Vec<reqwest::Certificate>,
+    /// Accept invalid certificates and host names, dangerously.
+    pub insecure: bool,
+    /// The client certificate of mutual TLS, see `read_identity`.
+    pub identity: Option<reqwest::Identity>,
+    /// Cookies sent besides those set by the wikis, see `read_cookies`.
+    pub cookies: Option<sync::Arc<reqwest::cookie::Jar>>,
+    /// The content codings accepted for responses, in order of preference;
+    /// `DEFAULT_COMPRESSION` if unset.
+    pub compression: Option<Vec<Compression>>,
+    /// The redirects followed at most, 0 for none; 10 if unset.
+    pub max_redirects: Option<usize>,
+    /// Sent with all requests, after the default ones, e.g. the API key of a gateway.
+    pub headers: Vec<auth::Header>,
+    /// Fail instead of sending more HTTP requests than this, retries included.
+    pub max_requests: Option<u32>,
+    /// Fail instead of sending requests after this instant, over all wikis and retries.
+    pub deadline: Option<time::Instant>,
+    /// Appended to the query, taking precedence over its parameters of the same keys.
+    pub raw_params: Vec<(String, String)>,
+    /// Record the responses fetched to the cassette at this path.
+    pub record: Option<path::PathBuf>,
+    /// Called with the duration of each request, and of the decoding of each response.
+    pub timing: Option<crate::Timing>,
+}
+
+/// Hooks into each attempt to fetch the siteinfo of a wiki, e.g. to test how a pipeline handles
+/// failures without a flaky network.
+pub trait FetchHooks: fmt::Debug + Send + Sync {
+    /// Called before each attempt; an error fails it like a network error, which is retried.
+    fn before_fetch(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        Ok(())
+    }
+
+    /// Called with each response before it is decoded, which it may change.
+    fn before_decode(&self, _raw: &mut String) {}
+}
+
+/// The content codings accepted by default: gzip and deflate.
+pub const DEFAULT_COMPRESSION: &[Compression] = &[Compression::Gzip, Compression::Deflate];
+
+/// Content codings of responses.
+#[derive(Clone, Copy, Debug, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Compression {
+    /// `gzip`.
+    Gzip,
+    /// `deflate`.
+    Deflate,
+    /// `br`, supported by Wikimedia wikis.
+    #[serde(rename = "br")]
+    Brotli,
+    /// `zstd`, supported by Wikimedia wikis.
+    Zstd,
+    /// Only uncompressed responses, as `identity`.
+    None,
 }
 
+/// An unknown content coding.
+#[derive(Debug, Error)]
+#[error("invalid value: {0:?}")]
+pub struct CompressionParseError(String);
+
+/// A failure to fetch the siteinfo of a wiki.
 #[derive(Debug, Error)]
-pub enum Error {
-    #[error(display = "cannot connect: {}", _0)]
-    New(#[error(source)] EndpointNewError),
-    #[error(display = "cannot fetch: {}", _0)]
-    Fetch(#[error(source)] reqwest::Error),
-    #[error(display = "invalid response: {}", _0)]
-    QueryFromResponse(#[error(source)] QueryFromResponseError),
+#[error("{domain}: {kind}")]
+pub struct Error {
+    domain: String,
+    #[source]
+    kind: ErrorKind,
 }
 
+/// What failed, see `ErrorKind::category`.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// The domain is invalid.
+    #[error("cannot connect: {0}")]
+    New(#[from] EndpointNewError),
+    /// The request failed, or was answered with an error status.
+    #[error("cannot fetch: {0}")]
+    Fetch(#[from] reqwest::Error),
+    /// The endpoint did not answer the preflight check.
+    #[error("preflight failed: {0}")]
+    Preflight(#[from] PreflightError),
+    /// The supported siteinfo properties cannot be listed.
+    #[error("cannot probe supported siteinfo properties: {0}")]
+    Probe(#[from] ProbeError),
+    /// The login failed.
+    #[error("cannot authenticate: {0}")]
+    Auth(#[from] auth::AuthError),
+    /// The API rejected the `assert` parameter, with the errors of the response.
+    #[error("not authenticated as a {0}, the login did not take effect: {1}")]
+    AssertFailed(auth::Assert, response::Errors),
+    /// No requests are left of `Options::max_requests`.
+    #[error("{0}")]
+    BudgetExhausted(#[from] BudgetExhausted),
+    /// `Options::deadline` has passed.
+    #[error("{0}")]
+    DeadlineExceeded(#[from] DeadlineExceeded),
+    /// The archived response of `Options::as_of` cannot be fetched.
+    #[error("archived response: {0}")]
+    Wayback(#[from] WaybackError),
+    /// The response is not a valid siteinfo response, or reports API errors.
+    #[error("invalid response: {0}")]
+    QueryFromResponse(#[from] QueryFromResponseError),
+    /// The attempt was failed by `FetchHooks::before_fetch`.
+    #[error("cannot fetch: {0}")]
+    Hook(#[source] Box<dyn std::error::Error + Send + Sync>),
+    /// The file of `Options::from_file` or `Options::from_local_settings` cannot be read.
+    #[error("cannot read {0:?}: {1}")]
+    Read(path::PathBuf, #[source] io::Error),
+    /// The dump of `Options::from_dump` cannot be read.
+    #[error("cannot read dump {0:?}: {1}")]
+    Dump(path::PathBuf, #[source] dump::DumpError),
+    /// The response is not in the offline cache at this path, for the request with this URL.
+    #[error("siteinfo of {1} not cached in {0:?}, and fetching is disabled")]
+    NotCached(path::PathBuf, String),
+    /// The request with this URL is not in the replayed cassette.
+    #[error("no response to {0} recorded in the cassette")]
+    NotRecorded(String),
+    /// No fixture of `Options::fixture` is bundled by this name.
+    #[error("no bundled fixture named {0:?}")]
+    UnknownFixture(String),
+    /// The response cannot be decompressed.
+    #[error("cannot decompress response: {0}")]
+    Decompress(#[source] io::Error),
+}
+
+/// No requests are left of `Options::max_requests`, of this number.
+#[derive(Debug, Error)]
+#[error("request budget of {0} HTTP requests exhausted")]
+pub struct BudgetExhausted(pub u32);
+
+/// `Options::deadline` has passed.
+#[derive(Debug, Error)]
+#[error("deadline of the run exceeded")]
+pub struct DeadlineExceeded;
+
+/// The URL of the API cannot be built.
 #[derive(Debug, Error)]
 pub enum EndpointNewError {
-    #[error(display = "{}", _0)]
-    Reqwest(#[error(source)] reqwest::Error),
-    #[error(display = "{}", _0)]
-    Url(#[error(source)] url::ParseError),
+    /// The domain is invalid.
+    #[error("{0}")]
+    Site(#[from] site::SiteParseError),
+    /// The resulting URL is invalid.
+    #[error("{0}")]
+    Url(#[from] url::ParseError),
+}
+
+/// A redirect not followed, reported as the source of a `reqwest::Error`.
+#[derive(Debug, Error)]
+pub enum RedirectError {
+    /// To this URL, with `Options::max_redirects` 0.
+    #[error("redirected to {0}, but redirects are disabled")]
+    Disabled(url::Url),
+    /// From HTTPS to this plain HTTP URL.
+    #[error("redirected from HTTPS to plain HTTP {0}")]
+    Insecure(url::Url),
+    /// More than `Options::max_redirects`.
+    #[error("more than {0} redirects")]
+    TooMany(usize),
 }
 
+/// A failure of `Client::fetch_json`.
+#[derive(Debug, Error)]
+pub enum FetchJsonError {
+    /// The request failed, or the response is not JSON.
+    #[error("{0}")]
+    Reqwest(#[from] reqwest::Error),
+    /// No requests are left.
+    #[error("{0}")]
+    BudgetExhausted(#[from] BudgetExhausted),
+    /// The deadline has passed.
+    #[error("{0}")]
+    DeadlineExceeded(#[from] DeadlineExceeded),
+}
+
+/// A failure to list the siteinfo properties supported by the wiki.
+#[derive(Debug, Error)]
+pub enum ProbeError {
+    /// The request failed, or the response is not JSON.
+    #[error("{0}")]
+    Reqwest(#[from] reqwest::Error),
+    /// The response lacks the values of the parameter.
+    #[error("no values of `siprop` in the parameter information")]
+    NotFound,
+}
+
+/// A failure of the preflight check of the endpoint.
+#[derive(Debug, Error)]
+pub enum PreflightError {
+    /// The request failed.
+    #[error("{0}")]
+    Reqwest(#[from] reqwest::Error),
+    /// The endpoint redirected to this URL, e.g. as it is not that of the API.
+    #[error("redirected to {0}")]
+    Redirected(url::Url),
+    /// The endpoint answered with this error status.
+    #[error("{}", PreflightError::describe_status(*.0))]
+    Status(reqwest::StatusCode),
+}
+
+/// A failure to fetch an archived response from the Wayback Machine.
+#[derive(Debug, Error)]
+pub enum WaybackError {
+    /// The request failed.
+    #[error("{0}")]
+    Reqwest(#[from] reqwest::Error),
+    /// The query URL was never archived.
+    #[error("no archived copy of the query URL found at the Wayback Machine")]
+    NotArchived,
+    /// The URL of the archived response is invalid.
+    #[error("{0}")]
+    Url(#[from] url::ParseError),
+}
+
+/// A response that is not a valid siteinfo response.
 #[derive(Debug, Error)]
 pub enum QueryFromResponseError {
-    #[error(display = "{}", _0)]
-    Json(#[error(source)] serde_json::Error),
-    #[error(display = "no errors or warnings, and no query found")]
+    /// The response is not valid JSON, or not of the expected shape.
+    #[error("{0}")]
+    Json(#[from] JsonError),
+    /// The response has no query, nor errors or warnings explaining why.
+    #[error("no errors or warnings, and no query found")]
     QueryNotFound,
-    #[error(display = "{}", _0)]
-    Response(#[error(source)] response::Errors),
+    /// The API reported these errors.
+    #[error("{0}")]
+    Response(#[from] response::Errors),
 }
 
-impl Endpoint {
-    fn fetch(&self) -> Result<response::Response, reqwest::Error> {
+/// Invalid JSON, located by the path to it and a snippet of the response around it, as responses
+/// are usually a single line, so that the position alone does not locate the error.
+#[derive(Debug, Error)]
+#[error("at {path}: {source} (near {snippet:?})")]
+pub struct JsonError {
+    path: String,
+    snippet: String,
+    source: serde_json::Error,
+}
+
+impl std::str::FromStr for Compression {
+    type Err = CompressionParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gzip" => Ok(Self::Gzip),
+            "deflate" => Ok(Self::Deflate),
+            "br" => Ok(Self::Brotli),
+            "zstd" => Ok(Self::Zstd),
+            "none" => Ok(Self::None),
+            _ => Err(CompressionParseError(s.to_owned())),
+        }
+    }
+}
+
+impl Error {
+    fn new(domain: &str, kind: impl Into<ErrorKind>) -> Self {
+        Self {
+            domain: domain.to_owned(),
+            kind: kind.into(),
+        }
+    }
+
+    /// The wiki, as given.
+    pub fn domain(&self) -> &str {
+        &self.domain
+    }
+
+    /// What failed.
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    /// The HTTP status of the response that failed, if any.
+    pub fn status(&self) -> Option<reqwest::StatusCode> {
+        match self.kind() {
+            ErrorKind::Fetch(e)
+            | ErrorKind::Preflight(PreflightError::Reqwest(e))
+            | ErrorKind::Probe(ProbeError::Reqwest(e))
+            | ErrorKind::Wayback(WaybackError::Reqwest(e)) => e.status(),
+            ErrorKind::Preflight(PreflightError::Status(status)) => Some(*status),
+            ErrorKind::Auth(auth::AuthError::Reqwest(e)) => e.status(),
+            ErrorKind::AssertFailed(..)
+            | ErrorKind::Auth(..)
+            | ErrorKind::BudgetExhausted(..)
+            | ErrorKind::DeadlineExceeded(..)
+            | ErrorKind::New(..)
+            | ErrorKind::Hook(..)
+            | ErrorKind::Preflight(PreflightError::Redirected(..))
+            | ErrorKind::Probe(..)
+            | ErrorKind::QueryFromResponse(..)
+            | ErrorKind::Read(..)
+            | ErrorKind::Dump(..)
+            | ErrorKind::NotCached(..)
+            | ErrorKind::NotRecorded(..)
+            | ErrorKind::UnknownFixture(..)
+            | ErrorKind::Decompress(..)
+            | ErrorKind::Wayback(..) => None,
+        }
+    }
+}
+
+impl ErrorKind {
+    /// What failed, independently of the variant.
+    pub fn category(&self) -> crate::ErrorCategory {
+        use crate::ErrorCategory::*;
+        match self {
+            ErrorKind::New(..)
+            | ErrorKind::Read(..)
+            | ErrorKind::Dump(..)
+            | ErrorKind::NotCached(..)
+            | ErrorKind::NotRecorded(..)
+            | ErrorKind::UnknownFixture(..)
+            | ErrorKind::Wayback(WaybackError::Url(..)) => Input,
+            ErrorKind::Auth(..) | ErrorKind::AssertFailed(..) => Authentication,
+            ErrorKind::Probe(ProbeError::NotFound)
+            | ErrorKind::QueryFromResponse(..)
+            | ErrorKind::Decompress(..) => Response,
+            ErrorKind::BudgetExhausted(..)
+            | ErrorKind::DeadlineExceeded(..)
+            | ErrorKind::Fetch(..)
+            | ErrorKind::Hook(..)
+            | ErrorKind::Preflight(..)
+            | ErrorKind::Probe(..)
+            | ErrorKind::Wayback(..) => Network,
+        }
+    }
+
+    // Network errors, server errors and rate limiting are transient; client errors and errors
+    // reported by the API are not, unless `all` is set.  An invalid URL is never retried.
+    fn is_retryable(&self, all: bool) -> bool {
+        fn transient(e: &reqwest::Error) -> bool {
+            match e.status() {
+                Some(status) => {
+                    status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+                }
+                None => !e.is_builder() && !e.is_decode() && !e.is_redirect(),
+            }
+        }
+        match self {
+            ErrorKind::BudgetExhausted(..)
+            | ErrorKind::DeadlineExceeded(..)
+            | ErrorKind::New(..)
+            | ErrorKind::Read(..)
+            | ErrorKind::Dump(..)
+            | ErrorKind::NotCached(..)
+            | ErrorKind::NotRecorded(..)
+            | ErrorKind::UnknownFixture(..) => false,
+            _ if all => true,
+            ErrorKind::Fetch(e)
+            | ErrorKind::Auth(auth::AuthError::Reqwest(e))
+            | ErrorKind::Preflight(PreflightError::Reqwest(e))
+            | ErrorKind::Probe(ProbeError::Reqwest(e))
+            | ErrorKind::Wayback(WaybackError::Reqwest(e)) => transient(e),
+            ErrorKind::Preflight(PreflightError::Status(status)) => {
+                status.is_server_error() || *status == reqwest::StatusCode::TOO_MANY_REQUESTS
+            }
+            ErrorKind::Hook(..) => true,
+            ErrorKind::AssertFailed(..)
+            | ErrorKind::Auth(..)
+            | ErrorKind::Preflight(..)
+            | ErrorKind::Probe(..)
+            | ErrorKind::QueryFromResponse(..)
+            | ErrorKind::Decompress(..)
+            | ErrorKind::Wayback(..) => false,
+        }
+    }
+}
+
+impl PreflightError {
+    fn describe_status(status: reqwest::StatusCode) -> String {
+        use reqwest::StatusCode;
+        match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+                format!(
+                    "access denied ({}), the wiki may require authentication",
+                    status
+                )
+            }
+            StatusCode::NOT_FOUND => format!("API not found ({})", status),
+            _ => format!("unexpected status ({})", status),
+        }
+    }
+}
+
+impl Client {
+    /// A client with these options, authenticating requests to the wikis with `auth`.
+    pub fn new(
+        options: Options,
+        auth: Box<dyn auth::AuthProvider>,
+    ) -> Result<Self, reqwest::Error> {
+        let mut builder = reqwest::blocking::Client::builder()
+            .user_agent(user_agent())
+            .cookie_store(true);
+        if let Some(timeout) = options.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(timeout) = options.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(proxy) = &options.proxy {
+            builder = builder.proxy(reqwest::Proxy::all(proxy.as_str())?);
+        }
+        for certificate in &options.root_certificates {
+            builder = builder.add_root_certificate(certificate.clone());
+        }
+        if options.insecure {
+            builder = builder.danger_accept_invalid_certs(true);
+        }
+        if let Some(identity) = &options.identity {
+            builder = builder.identity(identity.clone());
+        }
+        if let Some(cookies) = &options.cookies {
+            builder = builder.cookie_provider(cookies.clone());
+        }
+        let codings = options
+            .compression
+            .as_deref()
+            .unwrap_or(DEFAULT_COMPRESSION);
+        builder = builder
+            .gzip(codings.contains(&Compression::Gzip))
+            .deflate(codings.contains(&Compression::Deflate))
+            .brotli(codings.contains(&Compression::Brotli));
+        builder = builder
+            .default_headers(default_headers(&options))
+            .redirect(redirect_policy(options.max_redirects.unwrap_or(10)));
+        let client = builder.build()?;
+        Ok(Self {
+            client,
+            options,
+            auth,
+            sessions: Default::default(),
+            requests: Default::default(),
+            recorded: Default::default(),
+        })
+    }
+
+    /// Any JSON response, e.g. of another API module, counted against the budget.
+    pub fn fetch_json(&self, url: &url::Url) -> Result<serde_json::Value, FetchJsonError> {
+        self.check_budget()?;
+        self.check_deadline()?;
+        Ok(self
+            .request(reqwest::Method::GET, url)
+            .send()?
+            .error_for_status()?
+            .json()?)
+    }
+
+    /// The number of HTTP requests sent so far.
+    pub fn requests(&self) -> u32 {
+        self.requests.load(sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Whether no requests are left of `Options::max_requests`.
+    pub fn budget_exhausted(&self) -> bool {
+        self.check_budget().is_err()
+    }
+
+    // The budget is checked before each attempt to fetch, so that an attempt in progress is not
+    // cut short; it may be overrun by the few requests of a single attempt.
+    fn check_budget(&self) -> Result<(), BudgetExhausted> {
+        match self.options.max_requests {
+            Some(max) if self.requests() >= max => Err(BudgetExhausted(max)),
+            _ => Ok(()),
+        }
+    }
+
+    // None without a deadline.
+    fn remaining(&self) -> Option<time::Duration> {
+        let deadline = self.options.deadline?;
+        Some(deadline.saturating_duration_since(time::Instant::now()))
+    }
+
+    fn check_deadline(&self) -> Result<(), DeadlineExceeded> {
+        match self.remaining() {
+            Some(remaining) if remaining.is_zero() => Err(DeadlineExceeded),
+            _ => Ok(()),
+        }
+    }
+
+    // Requests in progress at the deadline are cut short there.
+    fn limit(
+        &self,
+        request: reqwest::blocking::RequestBuilder,
+    ) -> reqwest::blocking::RequestBuilder {
+        match self.remaining() {
+            Some(remaining)
+                if self
+                    .options
+                    .timeout
+                    .is_none_or(|timeout| remaining < timeout) =>
+            {
+                request.timeout(remaining)
+            }
+            _ => request,
+        }
+    }
+
+    fn request(
+        &self,
+        method: reqwest::Method,
+        url: &url::Url,
+    ) -> reqwest::blocking::RequestBuilder {
+        self.requests.fetch_add(1, sync::atomic::Ordering::Relaxed);
+        self.limit(self.client.request(method, url.as_ref()))
+    }
+
+    /// The siteinfo of the wiki at `domain`, as given on the command line, see `site::Site`.
+    pub fn fetch_query(&self, domain: &str) -> Result<Fetched, Error> {
+        let mut attempt = 0;
+        loop {
+            match self.fetch_query_once(domain) {
+                Err(kind)
+                    if attempt < self.options.retries
+                        && kind.is_retryable(self.options.retry_all_errors) =>
+                {
+                    let delay = time::Duration::from_secs(1 << attempt.min(6));
+                    if self.remaining().is_some_and(|remaining| remaining <= delay) {
+                        log::warn!("{}: no time left before the deadline to retry", domain);
+                        return Err(Error::new(domain, kind));
+                    }
+                    attempt += 1;
+                    log::warn!(
+                        "{}: {} (retry {}/{} in {:?})",
+                        domain,
+                        kind,
+                        attempt,
+                        self.options.retries,
+                        delay
+                    );
+                    thread::sleep(delay);
+                }
+                result => return result.map_err(|kind| Error::new(domain, kind)),
+            }
+        }
+    }
+
+    fn fetch_query_once(&self, domain: &str) -> Result<Fetched, ErrorKind> {
+        self.check_budget()?;
+        self.check_deadline()?;
+        if let Some(hooks) = &self.options.hooks {
+            hooks.before_fetch().map_err(ErrorKind::Hook)?;
+        }
+        let site: site::Site = domain.parse().map_err(EndpointNewError::from)?;
+        let key = site.to_string();
+        let mut endpoint = Endpoint::new(self, &site)?;
+        if let Some(name) = &self.options.fixture {
+            log::warn!(
+                "using bundled fixture {:?} instead of the API response",
+                name
+            );
+            let mut raw = fixture::get(name)
+                .ok_or_else(|| ErrorKind::UnknownFixture(name.clone()))?
+                .to_owned();
+            let query = self.decode(&mut raw)?;
+            return Ok(Fetched {
+                query,
+                raw,
+                url: endpoint.url,
+            });
+        }
+        if let Some(path) = &self.options.from_file {
+            let mut fetched = read_file(path, endpoint.url)?;
+            fetched.query = self.decode(&mut fetched.raw)?;
+            return Ok(fetched);
+        }
+        if let Some(path) = &self.options.from_dump {
+            return read_dump(path, endpoint.url);
+        }
+        if let Some(path) = &self.options.from_local_settings {
+            return read_local_settings(path, endpoint.url);
+        }
+        let cache = match &self.options.cache {
+            Some(cache) if self.options.as_of.is_none() => Some((cache, endpoint.url.clone())),
+            _ => None,
+        };
+        if let Some((cache, request)) = &cache {
+            if let Some(entry) = cache.get(&key, request) {
+                log::info!(
+                    "using siteinfo cached {}s ago, from {:?}",
+                    entry.age().as_secs(),
+                    cache.path(&key)
+                );
+                let query = self.timed("decode", || parse_query(&entry.raw))?;
+                return Ok(Fetched {
+                    query,
+                    raw: entry.raw,
+                    url: entry.url,
+                });
+            }
+            if cache.offline {
+                return Err(ErrorKind::NotCached(cache.path(&key), request.to_string()));
+            }
+        }
+        if let Some(timestamp) = &self.options.as_of {
+            log::info!("look up archived response at the Wayback Machine ...");
+            endpoint.url = self.wayback(&endpoint.url, timestamp)?;
+        } else {
+            self.login(&key, &endpoint.url)?;
+            endpoint.authorize = true;
+        }
+        if self.options.probe {
+            log::info!("probe supported siteinfo properties ...");
+            let supported = endpoint.probe()?;
+            let (categories, unsupported): (Vec<_>, Vec<_>) = Endpoint::categories(&self.options)
+                .into_iter()
+                .partition(|category| supported.contains(*category));
+            for category in unsupported {
+                log::warn!(
+                    "siteinfo property {:?} not supported by the wiki, left empty",
+                    category
+                );
+            }
+            endpoint.url = Endpoint::new_url(&site, &self.options, &categories)
+                .map_err(EndpointNewError::from)?;
+        }
+        if self.options.preflight {
+            log::info!("preflight check of API endpoint ...");
+            endpoint.preflight()?;
+        }
+        let (mut raw, mut url) = endpoint.fetch()?;
+        let mut parsed = self.decode(&mut raw);
+        if let Err(QueryFromResponseError::Response(errors)) = &parsed {
+            if endpoint.authorize && self.auth.has_session() && errors.session_expired() {
+                log::warn!("session expired ({}), log in again ...", errors);
+                self.sessions.lock().unwrap().remove(&key);
+                self.login(&key, &endpoint.url)?;
+                (raw, url) = endpoint.fetch()?;
+                parsed = self.decode(&mut raw);
+            }
+        }
+        let query = parsed.map_err(|e| match (e, self.options.assert) {
+            (QueryFromResponseError::Response(errors), Some(assert)) if errors.assert_failed() => {
+                ErrorKind::AssertFailed(assert, errors)
+            }
+            (e, _) => e.into(),
+        })?;
+
+        for (name, value) in [
+            ("extensions", format_args!("({})", query.extensions.len())),
+            (
+                "extensiontags",
+                format_args!("({})", query.extensiontags.len()),
+            ),
+            ("general", format_args!("{:?}", query.general)),
+            ("magicwords", format_args!("({})", query.magicwords.len())),
+            (
+                "namespacealiases",
+                format_args!("({})", query.namespacealiases.len()),
+            ),
+            ("namespaces", format_args!("({})", query.namespaces.len())),
+            ("protocols", format_args!("({})", query.protocols.len())),
+        ] {
+            log::debug!("query {}: {}", name, value);
+        }
+
+        if let Some((cache, request)) = cache {
+            cache.put(
+                &key,
+                &cache::Entry::new(key.clone(), request, url.clone(), raw.clone()),
+            );
+        }
+        Ok(Fetched { query, raw, url })
+    }
+
+    fn decode(&self, raw: &mut String) -> Result<response::Query, QueryFromResponseError> {
+        if let Some(hooks) = &self.options.hooks {
+            hooks.before_decode(raw);
+        }
+        self.timed("decode", || parse_query(raw))
+    }
+
+    // The result of `f`, whose duration is passed to `Options::timing`.
+    fn timed<T>(&self, name: &'static str, f: impl FnOnce() -> T) -> T {
+        let timing = match self.options.timing {
+            Some(timing) => timing,
+            None => return f(),
+        };
+        let start = time::Instant::now();
+        let result = f();
+        timing(name, start.elapsed());
+        result
+    }
+
+    // Log in to each wiki once, keeping the session in the cookie store of the client.
+    fn login(&self, site: &str, url: &url::Url) -> Result<(), auth::AuthError> {
+        let session = sync::Arc::clone(
+            self.sessions
+                .lock()
+                .unwrap()
+                .entry(site.to_owned())
+                .or_default(),
+        );
+        let mut logged_in = session.lock().unwrap();
+        if !*logged_in {
+            // The requests made by a provider to log in are counted as one.
+            if self.auth.has_session() {
+                self.requests.fetch_add(1, sync::atomic::Ordering::Relaxed);
+            }
+            self.auth.login(&self.client, url)?;
+            *logged_in = true;
+        }
+        Ok(())
+    }
+
+    // Find the archived copy of `url` closest to `timestamp` (`YYYYMMDD`), and return the URL of
+    // the original archived response.
+    fn wayback(&self, url: &url::Url, timestamp: &str) -> Result<url::Url, WaybackError> {
+        let lookup = url::Url::parse_with_params(
+            "https://archive.org/wayback/available",
+            [("url", url.as_str()), ("timestamp", timestamp)],
+        )?;
+        let available: serde_json::Value = self
+            .request(reqwest::Method::GET, &lookup)
+            .send()?
+            .error_for_status()?
+            .json()?;
+        let closest = &available["archived_snapshots"]["closest"];
+        log::debug!("wayback closest = {}", closest);
+        match (
+            closest["available"].as_bool(),
+            closest["timestamp"].as_str(),
+        ) {
+            (Some(true), Some(archived)) => {
+                log::warn!("using response archived at {} for {}", archived, timestamp);
+                let raw = format!("https://web.archive.org/web/{}id_/{}", archived, url);
+                Ok(url::Url::parse(&raw)?)
+            }
+            _ => Err(WaybackError::NotArchived),
+        }
+    }
+}
+
+impl<'c> Endpoint<'c> {
+    fn fetch(&self) -> Result<(String, url::Url), ErrorKind> {
+        if let Some(cassette) = &self.client.options.cassette {
+            let interaction = cassette
+                .play(&self.url)
+                .ok_or_else(|| ErrorKind::NotRecorded(self.url.to_string()))?;
+            log::info!("replay recorded response to {}", self.url);
+            return Ok((interaction.body.clone(), interaction.url.clone()));
+        }
+        let (raw, url) = self.client.timed("request", || self.fetch_once())?;
+        if let Some(path) = &self.client.options.record {
+            let mut recorded = self.client.recorded.lock().unwrap();
+            recorded.record(cassette::Interaction {
+                request: self.url.clone(),
+                url: url.clone(),
+                body: raw.clone(),
+            });
+            // Failures are only logged, as for the cache.
+            if let Err(e) = recorded.save(path) {
+                log::warn!("cannot record response in {:?}: {}", path, e);
+            }
+        }
+        Ok((raw, url))
+    }
+
+    fn fetch_once(&self) -> Result<(String, url::Url), ErrorKind> {
         let response = self.fetch_response()?;
+        let url = response.url().clone();
+        log::debug!("response url = {}", url);
 
         for name in [
             reqwest::header::CONNECTION,
@@ -53,40 +869,103 @@ impl Endpoint {
             log::debug!("response {:?}: {:?}", name, response.headers().get(&name));
         }
 
-        response.json()
+        if is_zstd(response.headers()) {
+            return Ok((decode_zstd(&response.bytes()?)?, url));
+        }
+        Ok((response.text()?, url))
+    }
+
+    fn preflight(&self) -> Result<(), PreflightError> {
+        let mut response = self.request(reqwest::Method::HEAD).send()?;
+        if response.status() == reqwest::StatusCode::METHOD_NOT_ALLOWED {
+            log::debug!("preflight: HEAD not allowed, retry with GET");
+            response = self.request(reqwest::Method::GET).send()?;
+        }
+        log::debug!(
+            "preflight: status = {}, url = {}",
+            response.status(),
+            response.url()
+        );
+
+        if response.url() != &self.url {
+            return Err(PreflightError::Redirected(response.url().clone()));
+        }
+        let status = response.status();
+        if status.is_client_error() || status.is_server_error() {
+            return Err(PreflightError::Status(status));
+        }
+        Ok(())
+    }
+
+    // The values of `siprop` accepted by the wiki, from the parameter information of its API.
+    fn probe(&self) -> Result<collections::BTreeSet<String>, ProbeError> {
+        let mut url = self.url.clone();
+        url.query_pairs_mut().clear().extend_pairs([
+            ("action", "paraminfo"),
+            ("modules", "query+siteinfo"),
+            ("format", "json"),
+            ("formatversion", "2"),
+        ]);
+        let mut request = self.client.request(reqwest::Method::GET, &url);
+        if self.authorize {
+            request = self.client.auth.authorize(request);
+        }
+        let response: response::ParamInfoResponse = request.send()?.error_for_status()?.json()?;
+        let values = response
+            .paraminfo
+            .modules
+            .into_iter()
+            .filter(|module| module.name == "siteinfo")
+            .flat_map(|module| module.parameters)
+            .find(|parameter| parameter.name == "prop")
+            .and_then(|parameter| parameter.kind.as_array().cloned())
+            .ok_or(ProbeError::NotFound)?;
+        let supported: collections::BTreeSet<_> = values
+            .iter()
+            .filter_map(|value| value.as_str().map(str::to_owned))
+            .collect();
+        log::debug!("supported siteinfo properties: {:?}", supported);
+        Ok(supported)
     }
 
     fn fetch_response(&self) -> Result<reqwest::blocking::Response, reqwest::Error> {
-        self.client
-            .get(self.url.as_ref())
-            .send()?
-            .error_for_status()
+        let method = if self.client.options.post {
+            reqwest::Method::POST
+        } else {
+            reqwest::Method::GET
+        };
+        self.request(method).send()?.error_for_status()
     }
 
-    fn new(domain: &str) -> Result<Self, EndpointNewError> {
-        let client = Self::new_client()?;
-        let url = Self::new_url(domain)?;
-        log::debug!("url = {}", url);
-        Ok(Self { client, url })
+    // Only requests to the wiki itself are authorized, not those to the Wayback Machine.
+    fn request(&self, method: reqwest::Method) -> reqwest::blocking::RequestBuilder {
+        let request = if method == reqwest::Method::POST {
+            let (url, form) = post_form(&self.url);
+            self.client.request(method, &url).form(&form)
+        } else {
+            self.client.request(method, &self.url)
+        };
+        if self.authorize {
+            self.client.auth.authorize(request)
+        } else {
+            request
+        }
     }
 
-    fn new_client() -> Result<reqwest::blocking::Client, reqwest::Error> {
-        let user_agent = format!(
-            "{}/{} ({})",
-            clap::crate_name!(),
-            clap::crate_version!(),
-            clap::crate_authors!(", ")
-        );
-        log::debug!("user_agent = {:?}", user_agent);
-        reqwest::blocking::Client::builder()
-            .user_agent(user_agent)
-            .https_only(true)
-            .deflate(true)
-            .gzip(true)
-            .build()
+    fn new(client: &'c Client, site: &site::Site) -> Result<Self, EndpointNewError> {
+        let url = Self::new_url(site, &client.options, &Self::categories(&client.options))?;
+        log::debug!("url = {}", url);
+        if site.is_insecure() && client.options.replay.is_none() {
+            log::warn!("fetching over plain HTTP, unencrypted: {}", url);
+        }
+        Ok(Self {
+            client,
+            url,
+            authorize: false,
+        })
     }
 
-    fn new_url(domain: &str) -> Result<url::Url, url::ParseError> {
+    fn categories(options: &Options) -> Vec<&'static str> {
         const CATEGORIES: &[&str] = &[
             "extensiontags",
             "general",
@@ -95,23 +974,171 @@ impl Endpoint {
             "namespaces",
             "protocols",
         ];
-        let mut url = url::Url::parse_with_params(
-            "https://example.org/w/api.php",
-            [
-                ("action", "query"),
-                ("meta", "siteinfo"),
-                ("siprop", &CATEGORIES.iter().format("|").to_string()),
-                ("format", "json"),
-                ("formatversion", "2"),
-                ("errorformat", "plaintext"),
-            ],
-        )
-        .unwrap();
-        url.set_host(Some(domain))?;
+        let mut categories = CATEGORIES.to_vec();
+        if options.extensions {
+            categories.push("extensions");
+        }
+        categories
+    }
+
+    fn new_url(
+        site: &site::Site,
+        options: &Options,
+        categories: &[&str],
+    ) -> Result<url::Url, url::ParseError> {
+        let mut url = match &options.replay {
+            Some(address) => url::Url::parse(&format!("http://{}/{}", address, site))?,
+            None => site.api_url(options.api_path.as_deref())?,
+        };
+        url.query_pairs_mut().extend_pairs([
+            ("action", "query"),
+            ("meta", "siteinfo"),
+            ("siprop", &categories.iter().format("|").to_string()),
+            ("format", "json"),
+            ("formatversion", "2"),
+            ("errorformat", "plaintext"),
+        ]);
+        if let Some(assert) = options.assert {
+            url.query_pairs_mut()
+                .append_pair("assert", &assert.to_string());
+        }
+        url.query_pairs_mut().extend_pairs(&options.raw_params);
         Ok(url)
     }
 }
 
+// The URL of a POST request for the query of `url`, and its query as the form-encoded body, e.g.
+// for gateways which reject long query strings.
+fn post_form(url: &url::Url) -> (url::Url, Vec<(String, String)>) {
+    let form = url.query_pairs().into_owned().collect();
+    let mut url = url.clone();
+    url.set_query(None);
+    (url, form)
+}
+
+/// The certificates of a PEM bundle, e.g. of a private CA.
+pub fn read_certificates(path: &path::Path) -> Result<Vec<reqwest::Certificate>, io::Error> {
+    let pem = fs::read(path)?;
+    let certificates = reqwest::Certificate::from_pem_bundle(&pem)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    if certificates.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "no PEM certificates",
+        ));
+    }
+    Ok(certificates)
+}
+
+/// The client certificate of mutual TLS, from a PEM certificate chain and a PEM PKCS #8 private
+/// key.  Errors are of the file at fault, or else of the certificate.
+pub fn read_identity(
+    certificate: &path::Path,
+    key: &path::Path,
+) -> Result<reqwest::Identity, (path::PathBuf, io::Error)> {
+    let pem = fs::read(certificate).map_err(|e| (certificate.to_owned(), e))?;
+    let key_pem = fs::read(key).map_err(|e| (key.to_owned(), e))?;
+    reqwest::Identity::from_pkcs8_pem(&pem, &key_pem).map_err(|e| {
+        let e = io::Error::new(io::ErrorKind::InvalidData, e);
+        (certificate.to_owned(), e)
+    })
+}
+
+/// The cookies of a cookie file in the Netscape format, as exported by browsers and written by curl,
+/// e.g. of a session authenticated by SSO.  Expired cookies are left out.
+pub fn read_cookies(path: &path::Path) -> Result<reqwest::cookie::Jar, io::Error> {
+    let contents = fs::read_to_string(path)?;
+    let now = time::SystemTime::now()
+        .duration_since(time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let jar = reqwest::cookie::Jar::default();
+    let mut count = 0;
+    for (i, line) in contents.lines().enumerate() {
+        let invalid = |message: &str| {
+            let message = format!("line {}: {}", i + 1, message);
+            io::Error::new(io::ErrorKind::InvalidData, message)
+        };
+        // Cookies hidden from scripts, which makes no difference here.
+        let line = line.strip_prefix("#HttpOnly_").unwrap_or(line);
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<_> = line.split('\t').collect();
+        let [domain, subdomains, path, secure, expires, name, value] = fields[..] else {
+            return Err(invalid("expected 7 fields separated by tabs"));
+        };
+        let expires: u64 = expires.parse().map_err(|_| invalid("invalid expiry"))?;
+        // Expiring with the session if 0.
+        if expires != 0 && expires < now {
+            continue;
+        }
+        let host = domain.trim_start_matches('.');
+        let url = url::Url::parse(&format!("https://{}{}", host, path))
+            .map_err(|e| invalid(&e.to_string()))?;
+        let mut cookie = format!("{}={}; Path={}", name, value, path);
+        if subdomains == "TRUE" {
+            cookie.push_str(&format!("; Domain={}", host));
+        }
+        if secure == "TRUE" {
+            cookie.push_str("; Secure");
+        }
+        jar.add_cookie_str(&cookie, &url);
+        count += 1;
+    }
+    log::info!("loaded {} cookies from {:?}", count, path);
+    Ok(jar)
+}
+
+// The response saved at `path`, e.g. by `--dump-raw`, with its `file:` URL, or else `url`.
+fn read_file(path: &path::Path, url: url::Url) -> Result<Fetched, ErrorKind> {
+    log::info!("read siteinfo response from {:?} ...", path);
+    let raw = fs::read_to_string(path).map_err(|e| ErrorKind::Read(path.to_owned(), e))?;
+    let query = parse_query(&raw)?;
+    let url = path::absolute(path)
+        .ok()
+        .and_then(|path| url::Url::from_file_path(path).ok())
+        .unwrap_or(url);
+    Ok(Fetched { query, raw, url })
+}
+
+// The siteinfo response of the header of the dump at `path`, completed by defaults, see
+// `dump::SiteInfo::response`.
+fn read_dump(path: &path::Path, url: url::Url) -> Result<Fetched, ErrorKind> {
+    log::info!("read siteinfo from the header of dump {:?} ...", path);
+    let site_info = dump::SiteInfo::read(path).map_err(|e| ErrorKind::Dump(path.to_owned(), e))?;
+    log::warn!(
+        "dumps lack the extension tags, magic words, protocols, namespace aliases and link trail, \
+            filled in from defaults"
+    );
+    let raw = site_info.response().to_string();
+    let query = parse_query(&raw)?;
+    let url = path::absolute(path)
+        .ok()
+        .and_then(|path| url::Url::from_file_path(path).ok())
+        .unwrap_or(url);
+    Ok(Fetched { query, raw, url })
+}
+
+// The siteinfo response of the settings of `path`, completed by defaults, see
+// `local_settings::LocalSettings::response`.
+fn read_local_settings(path: &path::Path, url: url::Url) -> Result<Fetched, ErrorKind> {
+    log::info!("read settings from {:?} ...", path);
+    let settings = local_settings::LocalSettings::read(path)
+        .map_err(|e| ErrorKind::Read(path.to_owned(), e))?;
+    log::warn!(
+        "settings are read without running PHP, so that computed ones are missed and conditions \
+            are not evaluated; the rest is filled in from defaults"
+    );
+    let raw = settings.response().to_string();
+    let query = parse_query(&raw)?;
+    let url = path::absolute(path)
+        .ok()
+        .and_then(|path| url::Url::from_file_path(path).ok())
+        .unwrap_or(url);
+    Ok(Fetched { query, raw, url })
+}
+
 impl convert::TryFrom<response::Response> for response::Query {
     type Error = QueryFromResponseError;
 
@@ -122,36 +1149,140 @@ impl convert::TryFrom<response::Response> for response::Query {
         if let Some(warnings) = response.warnings {
             return Err(warnings.into());
         }
-        serde_json::from_str(
-            response
-                .query
-                .ok_or(QueryFromResponseError::QueryNotFound)?
-                .get(),
-        )
-        .map_err(Into::into)
+        let query = response
+            .query
+            .ok_or(QueryFromResponseError::QueryNotFound)?;
+        deserialize(query.get()).map_err(|e| e.within("query").into())
     }
 }
 
-pub fn fetch_query(domain: &str) -> Result<response::Query, Error> {
-    let endpoint = Endpoint::new(domain)?;
-    let query: response::Query = endpoint.fetch()?.try_into()?;
+// The accepted content codings, and the headers given, which take precedence.  Of a repeated
+// name, the last header only, as reqwest replaces default headers by name.
+fn default_headers(options: &Options) -> reqwest::header::HeaderMap {
+    let mut map = reqwest::header::HeaderMap::new();
+    // Set explicitly, as reqwest does not know zstd.
+    let codings = options
+        .compression
+        .as_deref()
+        .unwrap_or(DEFAULT_COMPRESSION);
+    let value = codings
+        .iter()
+        .filter_map(|coding| match coding {
+            Compression::Gzip => Some("gzip"),
+            Compression::Deflate => Some("deflate"),
+            Compression::Brotli => Some("br"),
+            Compression::Zstd => Some("zstd"),
+            Compression::None => None,
+        })
+        .format(", ")
+        .to_string();
+    let value = if value.is_empty() { "identity" } else { &value };
+    map.insert(
+        reqwest::header::ACCEPT_ENCODING,
+        reqwest::header::HeaderValue::from_str(value).unwrap(),
+    );
+    for header in &options.headers {
+        map.insert(header.name.clone(), header.value.clone());
+    }
+    map
+}
 
-    for (name, value) in [
-        (
-            "extensiontags",
-            format_args!("({})", query.extensiontags.len()),
-        ),
-        ("general", format_args!("{:?}", query.general)),
-        ("magicwords", format_args!("({})", query.magicwords.len())),
-        (
-            "namespacealiases",
-            format_args!("({})", query.namespacealiases.len()),
-        ),
-        ("namespaces", format_args!("({})", query.namespaces.len())),
-        ("protocols", format_args!("({})", query.protocols.len())),
-    ] {
-        log::debug!("query {}: {}", name, value);
+// Redirects to another host are logged, as they usually mean that the domain given is an alias,
+// e.g. `wikipedia.com` of `www.wikipedia.org`, and the authorization is not sent along.
+fn redirect_policy(max: usize) -> reqwest::redirect::Policy {
+    reqwest::redirect::Policy::custom(move |attempt| {
+        if max == 0 {
+            let url = attempt.url().clone();
+            return attempt.error(RedirectError::Disabled(url));
+        }
+        if attempt.previous().len() > max {
+            return attempt.error(RedirectError::TooMany(max));
+        }
+        // Plain HTTP is only fetched if given, see `site::Site`.
+        let previous = attempt.previous().last();
+        if previous.is_some_and(|url| url.scheme() == "https") && attempt.url().scheme() == "http" {
+            let url = attempt.url().clone();
+            return attempt.error(RedirectError::Insecure(url));
+        }
+        let from = previous.map(url::Url::host_str);
+        if from.is_some_and(|from| from != attempt.url().host_str()) {
+            log::info!("redirected to another host: {}", attempt.url());
+        } else {
+            log::debug!("redirected to {}", attempt.url());
+        }
+        attempt.follow()
+    })
+}
+
+// Whether the body of a response is left compressed by reqwest, which only decodes zstd as of
+// version 0.12.
+fn is_zstd(headers: &reqwest::header::HeaderMap) -> bool {
+    headers
+        .get(reqwest::header::CONTENT_ENCODING)
+        .is_some_and(|coding| coding == "zstd")
+}
+
+fn decode_zstd(body: &[u8]) -> Result<String, ErrorKind> {
+    let body = zstd::decode_all(body).map_err(ErrorKind::Decompress)?;
+    String::from_utf8(body)
+        .map_err(|e| ErrorKind::Decompress(io::Error::new(io::ErrorKind::InvalidData, e)))
+}
+
+fn user_agent() -> String {
+    let user_agent = format!(
+        "{}/{} ({})",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION"),
+        env!("CARGO_PKG_AUTHORS").replace(':', ", ")
+    );
+    log::debug!("user_agent = {:?}", user_agent);
+    user_agent
+}
+
+/// The query of a siteinfo response.
+pub fn parse_query(raw: &str) -> Result<response::Query, QueryFromResponseError> {
+    let response: response::Response = deserialize(raw)?;
+    response.try_into()
+}
+
+fn deserialize<'de, T: serde::Deserialize<'de>>(raw: &'de str) -> Result<T, JsonError> {
+    serde_path_to_error::deserialize(&mut serde_json::Deserializer::from_str(raw)).map_err(|e| {
+        let path = e.path().to_string();
+        let source = e.into_inner();
+        JsonError {
+            path,
+            snippet: JsonError::excerpt(raw, &source),
+            source,
+        }
+    })
+}
+
+impl JsonError {
+    fn within(mut self, field: &str) -> Self {
+        self.path = match self.path.as_str() {
+            "." => field.to_owned(),
+            path => format!("{}.{}", field, path),
+        };
+        self
     }
 
-    Ok(query)
+    // Up to 40 bytes on either side of the error position, widened to character boundaries.
+    fn excerpt(raw: &str, e: &serde_json::Error) -> String {
+        const CONTEXT: usize = 40;
+        let line_start: usize = raw
+            .split_inclusive('\n')
+            .take(e.line().saturating_sub(1))
+            .map(str::len)
+            .sum();
+        let position = (line_start + e.column()).min(raw.len());
+        let mut start = position.saturating_sub(CONTEXT);
+        while !raw.is_char_boundary(start) {
+            start -= 1;
+        }
+        let mut end = (position + CONTEXT).min(raw.len());
+        while !raw.is_char_boundary(end) {
+            end += 1;
+        }
+        raw[start..end].to_owned()
+    }
 }
@@ -1,79 +1,196 @@
-use err_derive::Error;
+//! The responses of the siteinfo and paraminfo API modules, as deserialized.
+
 use itertools::Itertools;
 use serde::Deserialize;
 use std::{collections, error, fmt};
+use thiserror::Error;
 
+/// A response of the API, with its query left raw to be deserialized once it has no errors.
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct Response {
+    /// The `query` result, missing if the request failed.
     pub query: Option<Box<serde_json::value::RawValue>>,
 
+    /// The errors, in `errorformat=plaintext`.
     pub errors: Option<Errors>,
+    /// The warnings, in `errorformat=plaintext`.
     pub warnings: Option<Errors>,
 }
 
+/// The properties other than `general` and `namespaces` may be left out of the query by probing.
 #[derive(Debug, Deserialize)]
 #[serde(deny_unknown_fields, rename_all = "kebab-case")]
 pub struct Query {
+    /// `siprop=extensions`.
+    #[serde(default)]
+    pub extensions: Vec<Extension>,
+    /// `siprop=extensiontags`.
+    #[serde(default)]
     pub extensiontags: Vec<ExtensionTag>,
+    /// `siprop=general`.
     pub general: General,
+    /// `siprop=magicwords`.
+    #[serde(default)]
     pub magicwords: Vec<MagicWord>,
+    /// `siprop=namespacealiases`.
+    #[serde(default)]
     pub namespacealiases: Vec<NamespaceAlias>,
+    /// `siprop=namespaces`, by namespace id.
     pub namespaces: collections::BTreeMap<String, Namespace>,
+    /// `siprop=protocols`.
+    #[serde(default)]
     pub protocols: Vec<Protocol>,
 }
 
+/// A response of `action=paraminfo`, used to probe which properties siteinfo supports.
+#[derive(Debug, Deserialize)]
+pub struct ParamInfoResponse {
+    /// The `paraminfo` result.
+    pub paraminfo: ParamInfo,
+}
+
+/// The `paraminfo` result.
+#[derive(Debug, Deserialize)]
+pub struct ParamInfo {
+    /// The modules asked for.
+    pub modules: Vec<ParamInfoModule>,
+}
+
+/// An API module, e.g. `siteinfo`.
+#[derive(Debug, Deserialize)]
+pub struct ParamInfoModule {
+    /// The name of the module.
+    pub name: String,
+    /// The parameters of the module.
+    pub parameters: Vec<ParamInfoParameter>,
+}
+
+/// The `type` of a parameter is either the name of a type, or the list of its allowed values.
+#[derive(Debug, Deserialize)]
+pub struct ParamInfoParameter {
+    /// The name of the parameter, e.g. `prop`.
+    pub name: String,
+    /// The type of the parameter.
+    #[serde(rename = "type")]
+    pub kind: serde_json::Value,
+}
+
+/// An installed extension.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Extension {
+    /// The name of the extension, e.g. `Cite`.
+    pub name: String,
+}
+
+/// A tag of a parser extension, with its angle brackets, e.g. `<ref>`.
 #[derive(Debug, Deserialize)]
 #[serde(transparent)]
 pub struct ExtensionTag(pub String);
 
+/// The general information of a wiki.
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct General {
+    /// The case of the first letter of titles, e.g. `first-letter`.
+    pub case: Option<String>,
+    /// The version of MediaWiki, e.g. `MediaWiki 1.42.0-wmf.5`.
+    pub generator: Option<String>,
+    /// The language code of the content.
+    pub lang: Option<String>,
+    /// The characters allowed in titles, as a PCRE character class.
+    pub legaltitlechars: Option<String>,
+    /// The PCRE pattern of the link trail.
     pub linktrail: String,
+    /// The name of the wiki.
+    pub sitename: Option<String>,
 }
 
+/// A magic word, with its localized aliases.
 #[derive(Debug, Deserialize)]
 #[serde(deny_unknown_fields, rename_all = "kebab-case")]
 pub struct MagicWord {
+    /// The aliases, e.g. `__NOTOC__`.
     pub aliases: Vec<String>,
+    /// Whether the aliases are matched case-sensitively.
     pub case_sensitive: Option<bool>,
+    /// The internal name, e.g. `notoc`.
     pub name: String,
 }
 
+/// An additional name of a namespace.
 #[derive(Debug, Deserialize)]
 #[serde(deny_unknown_fields, rename_all = "kebab-case")]
 pub struct NamespaceAlias {
+    /// The id of the namespace.
     pub id: i64,
+    /// The additional name.
     pub alias: String,
 }
 
+/// A namespace.
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct Namespace {
+    /// The id of the namespace, e.g. 6 for files.
     pub id: i64,
+    /// The localized name, empty for the main namespace.
     pub name: String,
+    /// The English name, e.g. `File`.
     pub canonical: Option<String>,
+    /// The case of the first letter of titles in the namespace.
+    pub case: Option<String>,
 }
 
+/// A URL protocol allowed in external links, e.g. `https://`.
 #[derive(Debug, Deserialize)]
 #[serde(transparent)]
 pub struct Protocol(pub String);
 
+/// The errors or warnings of a response.
 #[derive(Debug, Deserialize)]
 #[serde(transparent)]
 pub struct Errors(pub Vec<Error>);
 
+/// An error or warning of a response.
 #[derive(Debug, Deserialize, Error)]
-#[error(display = "siteinfo API [{}] {} {} ({:?})", module, code, text, data)]
+#[error("siteinfo API [{module}] {code} {text} ({data:?})")]
 #[serde(deny_unknown_fields, rename_all = "kebab-case")]
 pub struct Error {
+    /// The code, e.g. `readapidenied`.
     pub code: String,
+    /// The data of the error, if any.
     pub data: Option<serde_json::Value>,
+    /// The module that returned it, e.g. `query`.
     pub module: String,
+    /// The text, in the language of the wiki.
     pub text: String,
 }
 
+impl Errors {
+    /// Errors returned when the request is no longer authenticated, e.g. after the session cookie
+    /// expired.
+    pub fn session_expired(&self) -> bool {
+        const CODES: &[&str] = &[
+            "assertbotfailed",
+            "assertnameduserfailed",
+            "assertuserfailed",
+            "readapidenied",
+        ];
+        self.0.iter().any(|e| CODES.contains(&e.code.as_str()))
+    }
+}
+
+impl Errors {
+    /// Errors returned when an `assert` or `assertuser` parameter fails.
+    pub fn assert_failed(&self) -> bool {
+        self.0
+            .iter()
+            .any(|e| e.code.starts_with("assert") && e.code.ends_with("failed"))
+    }
+}
+
 impl fmt::Display for Errors {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", self.0.iter().format("; "))
@@ -0,0 +1,114 @@
+//! Addressing wikis by host, script path or URL.
+
+use std::{fmt, str};
+use thiserror::Error;
+
+/// A wiki, addressed by its host, and by its script path if it shares the host with other wikis,
+/// e.g. `example.org/wiki-a`.  Sessions, caches and pinned endpoints are keyed by its canonical
+/// form, so that such wikis are kept apart.  A wiki may also be addressed by the complete HTTP or
+/// HTTPS URL of its API, e.g. `https://wiki.example.com:8443/custom/api?uselang=en`, which is then
+/// used as is.
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct Site {
+    host: String,
+    path: Option<String>,
+    url: Option<url::Url>,
+}
+
+/// A wiki that is neither a host with an optional path nor a URL.
+#[derive(Debug, Error)]
+#[error("invalid wiki {0:?}: expected a host, optionally followed by a path, or a URL")]
+pub struct SiteParseError(String);
+
+impl Site {
+    /// Whether the wiki was given as a plain HTTP URL.
+    pub fn is_insecure(&self) -> bool {
+        self.url.as_ref().is_some_and(|url| url.scheme() == "http")
+    }
+
+    /// The API endpoint: `api.php` under the script path, which is `default_path` unless given,
+    /// and else `/w`.  A path ending in `.php` is taken to be the endpoint itself, and an empty
+    /// path, e.g. `/`, is the root.
+    pub fn api_url(&self, default_path: Option<&str>) -> Result<url::Url, url::ParseError> {
+        if let Some(url) = &self.url {
+            return Ok(url.clone());
+        }
+        let path = self
+            .path
+            .as_deref()
+            .or_else(|| default_path.map(|path| path.trim_matches('/')));
+        let path = match path {
+            None => "w/api.php".to_owned(),
+            Some("") => "api.php".to_owned(),
+            Some(path) if path.ends_with(".php") => path.to_owned(),
+            Some(path) => format!("{}/api.php", path),
+        };
+        url::Url::parse(&format!("https://{}/{}", self.host, path))
+    }
+}
+
+/// The canonical form of a wiki given on the command line or in a manifest, or the wiki unchanged if
+/// it is invalid, e.g. `Example.org/wiki-a/` as `example.org/wiki-a`.
+pub fn key(site: &str) -> String {
+    site.parse::<Site>()
+        .map_or_else(|_| site.to_owned(), |site| site.to_string())
+}
+
+/// The domain, with the separators of a script path, e.g. of `example.org/wiki-a`, replaced.
+pub fn file_name(domain: &str) -> String {
+    domain
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '.' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+impl str::FromStr for Site {
+    type Err = SiteParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(mut url) = url::Url::parse(s) {
+            let http = url.scheme() == "http" || url.scheme() == "https";
+            if let Some(host) = url.host_str().filter(|_| http) {
+                let host = match url.port() {
+                    Some(port) => format!("{}:{}", host, port),
+                    None => host.to_owned(),
+                };
+                let path = url.path().trim_matches('/').to_owned();
+                url.set_fragment(None);
+                return Ok(Self {
+                    host,
+                    path: (!path.is_empty()).then_some(path),
+                    url: Some(url),
+                });
+            }
+        }
+        let (host, path) = s.split_once('/').unwrap_or((s, ""));
+        let path = path.trim_matches('/');
+        if host.is_empty()
+            || host.ends_with(':')
+            || host.contains(|c: char| c.is_whitespace() || c == '?' || c == '#')
+        {
+            return Err(SiteParseError(s.to_owned()));
+        }
+        Ok(Self {
+            host: host.to_lowercase(),
+            path: (!path.is_empty()).then(|| path.to_owned()),
+            url: None,
+        })
+    }
+}
+
+impl fmt::Display for Site {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.path {
+            Some(path) => write!(f, "{}/{}", self.host, path),
+            None => write!(f, "{}", self.host),
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use crate::api::cache;
+use std::{fs, io, io::Read, io::Write, path};
+
+// Write the entries of the wikis with the canonical forms `keys`, or else all entries, to a tar
+// archive at `path`, compressed with zstd or gzip by its extension, to be imported on another
+// machine.  Returns the number of entries written.
+pub fn export(
+    cache: &cache::Cache,
+    path: &path::Path,
+    keys: &[String],
+) -> Result<usize, io::Error> {
+    let paths = match keys {
+        [] => cache.paths()?,
+        keys => keys.iter().map(|key| cache.path(key)).collect(),
+    };
+    let file = fs::File::create(path)?;
+    let writer: Box<dyn io::Write> = match path.extension().and_then(|e| e.to_str()) {
+        Some("zst") => Box::new(zstd::Encoder::new(file, 0)?.auto_finish()),
+        Some("gz") => Box::new(flate2::write::GzEncoder::new(
+            file,
+            flate2::Compression::default(),
+        )),
+        _ => Box::new(file),
+    };
+    let mut builder = tar::Builder::new(writer);
+    builder.mode(tar::HeaderMode::Deterministic);
+    for path in &paths {
+        let name = path.file_name().unwrap_or_default();
+        builder.append_path_with_name(path, name)?;
+    }
+    builder.into_inner()?.flush()?;
+    Ok(paths.len())
+}
+
+// Add the entries of an archive written by `export`, unless those cached are more recent.  Returns
+// the numbers of entries added and kept.
+pub fn import(cache: &cache::Cache, path: &path::Path) -> Result<(usize, usize), io::Error> {
+    let file = fs::File::open(path)?;
+    let reader: Box<dyn io::Read> = match path.extension().and_then(|e| e.to_str()) {
+        Some("zst") => Box::new(zstd::Decoder::new(file)?),
+        Some("gz") => Box::new(flate2::read::MultiGzDecoder::new(file)),
+        _ => Box::new(file),
+    };
+    let (mut added, mut kept) = (0, 0);
+    for file in tar::Archive::new(reader).entries()? {
+        let mut file = file?;
+        if file.header().entry_type() != tar::EntryType::Regular {
+            continue;
+        }
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents)?;
+        // Placed by the domain it is of, whatever the name of the file.
+        let entry = cache::Entry::parse(&contents).map_err(|e| {
+            let name = file.path().map(|p| p.display().to_string());
+            let message = format!("invalid entry {:?}: {}", name.unwrap_or_default(), e);
+            io::Error::new(io::ErrorKind::InvalidData, message)
+        })?;
+        let target = cache.path(&entry.domain);
+        match cache::Entry::load(&target) {
+            Ok(cached) if cached.fetched_at > entry.fetched_at => kept += 1,
+            _ => {
+                entry.save(&target)?;
+                added += 1;
+            }
+        }
+    }
+    Ok((added, kept))
+}
@@ -0,0 +1,385 @@
+use crate::{diff, extract};
+use itertools::Itertools;
+use std::{collections, fs, io, path, str};
+use syn::{spanned::Spanned, visit::Visit};
+use thiserror::Error;
+
+#[derive(Clone, Copy, Debug)]
+pub enum CheckFormat {
+    Text,
+    Annotations,
+    Github,
+}
+
+#[derive(Debug)]
+pub struct Finding {
+    pub line: usize,
+    pub column: usize,
+    pub changes: diff::FieldChanges,
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    #[error("{}:{}: {}", .0.span().start().line, .0.span().start().column + 1, .0)]
+    Rust(#[from] syn::Error),
+    #[error("{0}")]
+    Json(#[from] serde_json::Error),
+    #[error("no `ConfigurationSource` found")]
+    NotFound,
+    #[error("a delta cannot be checked, only a whole configuration")]
+    Delta,
+    #[error("{line}: unexpected value of field `{field}`")]
+    FieldInvalid { field: String, line: usize },
+}
+
+#[derive(Debug, Error)]
+#[error("invalid value: {0:?}")]
+pub struct OptionParseError(String);
+
+struct GeneratedField {
+    name: String,
+    values: collections::BTreeSet<String>,
+    line: usize,
+    column: usize,
+}
+
+#[derive(Default)]
+struct Visitor<'a> {
+    constants: collections::BTreeMap<String, &'a syn::Expr>,
+    fields: Option<&'a syn::punctuated::Punctuated<syn::FieldValue, syn::Token![,]>>,
+    // Whether a function takes a `ConfigurationSource`, as that of `--emit delta` takes the
+    // defaults.
+    delta: bool,
+}
+
+// Compare the configuration previously generated into `path`, as Rust or JSON, with
+// `configuration_source`, field by field.  Fields that are not generated in the format of `path`
+// are not compared.
+pub fn check(
+    path: &path::Path,
+    configuration_source: &extract::ConfigurationSource,
+) -> Result<Vec<Finding>, Error> {
+    let raw = fs::read_to_string(path)?;
+    let generated = match path.extension().and_then(|e| e.to_str()) {
+        Some("json") => parse_json(&raw)?,
+        _ => parse_rust(&raw, path.parent().unwrap_or_else(|| path::Path::new("")))?,
+    };
+    let current = diff::fields(configuration_source);
+    Ok(generated
+        .into_iter()
+        .filter_map(|field| {
+            let (name, values) = current.iter().find(|(name, _)| *name == field.name)?;
+            let changes = diff::field_changes(name, &field.values, values)?;
+            Some(Finding {
+                line: field.line,
+                column: field.column,
+                changes,
+            })
+        })
+        .collect())
+}
+
+pub fn write(
+    mut out: impl io::Write,
+    format: CheckFormat,
+    path: &path::Path,
+    findings: &[Finding],
+) -> Result<(), io::Error> {
+    let path = path.display();
+    match format {
+        CheckFormat::Text => {
+            if findings.is_empty() {
+                writeln!(out, "{}: up to date", path)?;
+            }
+            for finding in findings {
+                writeln!(out, "{}: {}", path, summary(&finding.changes))?;
+                for added in &finding.changes.added {
+                    writeln!(out, "  + {}", added)?;
+                }
+                for removed in &finding.changes.removed {
+                    writeln!(out, "  - {}", removed)?;
+                }
+            }
+        }
+        // The format of compiler diagnostics, recognized by most problem matchers.
+        CheckFormat::Annotations => {
+            for finding in findings {
+                writeln!(
+                    out,
+                    "{}:{}:{}: error: {}",
+                    path,
+                    finding.line,
+                    finding.column,
+                    message(&finding.changes)
+                )?;
+            }
+        }
+        // Workflow commands, annotated by GitHub Actions without a problem matcher.
+        CheckFormat::Github => {
+            for finding in findings {
+                writeln!(
+                    out,
+                    "::error file={},line={},col={},title=Out-of-date wiki configuration::{}",
+                    path,
+                    finding.line,
+                    finding.column,
+                    message(&finding.changes)
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn summary(changes: &diff::FieldChanges) -> String {
+    format!(
+        "`{}` is out of date: {} added, {} removed",
+        changes.field,
+        changes.added.len(),
+        changes.removed.len()
+    )
+}
+
+fn message(changes: &diff::FieldChanges) -> String {
+    let mut message = summary(changes);
+    for (sign, values) in [("+", &changes.added), ("-", &changes.removed)] {
+        if !values.is_empty() {
+            message += &format!(
+                " ({})",
+                values
+                    .iter()
+                    .map(|v| format!("{}{:?}", sign, v))
+                    .format(", ")
+            );
+        }
+    }
+    message
+}
+
+// Either a bare expression, or items such as those of `--const-name`.  Data files included by
+// fields, see `generate::split_data`, are read relative to `directory`.
+fn parse_rust(raw: &str, directory: &path::Path) -> Result<Vec<GeneratedField>, Error> {
+    let expr: syn::Expr;
+    let file: syn::File;
+    let mut visitor = Visitor::default();
+    if let Ok(parsed) = syn::parse_str(raw) {
+        expr = parsed;
+        visitor.visit_expr(&expr);
+    } else {
+        file = syn::parse_str(raw)?;
+        visitor.visit_file(&file);
+    }
+    if visitor.delta {
+        return Err(Error::Delta);
+    }
+    let fields = visitor.fields.ok_or(Error::NotFound)?;
+
+    fields
+        .iter()
+        .filter_map(|field| match &field.member {
+            syn::Member::Named(name) => Some((name.to_string(), &field.expr)),
+            syn::Member::Unnamed(..) => None,
+        })
+        .map(|(name, expr)| {
+            // The ranges of a link trail built from them, see `generate::link_trail_ranges`.
+            let expr = match expr {
+                syn::Expr::Block(block) => block
+                    .block
+                    .stmts
+                    .iter()
+                    .find_map(|stmt| match stmt {
+                        syn::Stmt::Item(syn::Item::Const(item)) if item.ident == "RANGES" => {
+                            Some(&*item.expr)
+                        }
+                        _ => None,
+                    })
+                    .unwrap_or(expr),
+                _ => expr,
+            };
+            let expr = match expr {
+                syn::Expr::Path(path) => path
+                    .path
+                    .get_ident()
+                    .and_then(|ident| visitor.constants.get(&ident.to_string()))
+                    .copied()
+                    .unwrap_or(expr),
+                _ => expr,
+            };
+            let start = expr.span().start();
+            let values = match included(expr) {
+                Some(included) => {
+                    let data = fs::read_to_string(directory.join(included))?;
+                    match expr {
+                        syn::Expr::Macro(..) => data.chars().map(String::from).collect(),
+                        _ => data.lines().map(String::from).collect(),
+                    }
+                }
+                None => rust_values(expr).ok_or_else(|| Error::FieldInvalid {
+                    field: name.clone(),
+                    line: start.line,
+                })?,
+            };
+            Ok(GeneratedField {
+                name,
+                values,
+                line: start.line,
+                column: start.column + 1,
+            })
+        })
+        .collect()
+}
+
+// The path of `include_str!`, directly or in the constant of a block, as by `--split-output`.
+fn included(expr: &syn::Expr) -> Option<String> {
+    match expr {
+        syn::Expr::Macro(mac) if mac.mac.path.is_ident("include_str") => {
+            mac.mac.parse_body::<syn::LitStr>().ok().map(|s| s.value())
+        }
+        syn::Expr::Block(block) => block.block.stmts.iter().find_map(|stmt| match stmt {
+            syn::Stmt::Item(syn::Item::Const(item)) => included(&item.expr),
+            _ => None,
+        }),
+        syn::Expr::Call(call) => call.args.iter().find_map(included),
+        _ => None,
+    }
+}
+
+fn rust_values(expr: &syn::Expr) -> Option<collections::BTreeSet<String>> {
+    fn string(expr: &syn::Expr) -> Option<String> {
+        match expr {
+            syn::Expr::Lit(syn::ExprLit {
+                lit: syn::Lit::Str(s),
+                ..
+            }) => Some(s.value()),
+            _ => None,
+        }
+    }
+    // E.g. `('a', 'z')`, as its characters.
+    fn range(expr: &syn::Expr) -> Option<Vec<String>> {
+        let character = |expr: &syn::Expr| match expr {
+            syn::Expr::Lit(syn::ExprLit {
+                lit: syn::Lit::Char(c),
+                ..
+            }) => Some(c.value()),
+            _ => None,
+        };
+        match expr {
+            syn::Expr::Tuple(tuple) if tuple.elems.len() == 2 => {
+                let start = character(&tuple.elems[0])?;
+                let end = character(&tuple.elems[1])?;
+                Some((start..=end).map(String::from).collect())
+            }
+            _ => None,
+        }
+    }
+    match expr {
+        syn::Expr::Reference(reference) => rust_values(&reference.expr),
+        syn::Expr::Array(array)
+            if array
+                .elems
+                .iter()
+                .all(|e| matches!(e, syn::Expr::Tuple(..))) =>
+        {
+            let ranges = array.elems.iter().map(range).collect::<Option<Vec<_>>>()?;
+            Some(ranges.into_iter().flatten().collect())
+        }
+        syn::Expr::Array(array) => array.elems.iter().map(string).collect(),
+        _ => string(expr).map(|s| s.chars().map(String::from).collect()),
+    }
+}
+
+fn parse_json(raw: &str) -> Result<Vec<GeneratedField>, Error> {
+    let object: collections::BTreeMap<String, serde_json::Value> = serde_json::from_str(raw)?;
+    object
+        .into_iter()
+        .map(|(name, value)| {
+            // The first line of the key, since the position of values is not kept by `serde_json`.
+            let line = raw
+                .find(&format!("{:?}:", name))
+                .map_or(1, |index| raw[..index].lines().count().max(1));
+            let values = json_values(&value).ok_or_else(|| Error::FieldInvalid {
+                field: name.clone(),
+                line,
+            })?;
+            Ok(GeneratedField {
+                name,
+                values,
+                line,
+                column: 1,
+            })
+        })
+        .collect()
+}
+
+fn json_values(value: &serde_json::Value) -> Option<collections::BTreeSet<String>> {
+    use serde_json::Value;
+    match value {
+        Value::String(s) => Some(s.chars().map(String::from).collect()),
+        Value::Array(values) => values
+            .iter()
+            .map(|value| match value {
+                Value::String(s) => Some(s.clone()),
+                Value::Object(namespace) => Some(format!(
+                    "{}: {}",
+                    namespace.get("id")?,
+                    namespace.get("name")?.as_str()?
+                )),
+                _ => None,
+            })
+            .collect(),
+        // E.g. `extension_tag_sources`, not compared.
+        _ => Some(Default::default()),
+    }
+}
+
+impl<'a> Visit<'a> for Visitor<'a> {
+    fn visit_item_const(&mut self, item: &'a syn::ItemConst) {
+        self.constants.insert(item.ident.to_string(), &item.expr);
+        syn::visit::visit_item_const(self, item);
+    }
+
+    fn visit_item_fn(&mut self, item: &'a syn::ItemFn) {
+        self.delta |= item.sig.inputs.iter().any(|input| match input {
+            syn::FnArg::Typed(pat) => match &*pat.ty {
+                syn::Type::Reference(reference) => match &*reference.elem {
+                    syn::Type::Path(path) => path
+                        .path
+                        .segments
+                        .last()
+                        .is_some_and(|segment| segment.ident == "ConfigurationSource"),
+                    _ => false,
+                },
+                _ => false,
+            },
+            syn::FnArg::Receiver(..) => false,
+        });
+        syn::visit::visit_item_fn(self, item);
+    }
+
+    fn visit_expr_struct(&mut self, expr: &'a syn::ExprStruct) {
+        if expr
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "ConfigurationSource")
+        {
+            self.fields = Some(&expr.fields);
+        }
+        syn::visit::visit_expr_struct(self, expr);
+    }
+}
+
+impl str::FromStr for CheckFormat {
+    type Err = OptionParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "annotations" => Ok(Self::Annotations),
+            "github" => Ok(Self::Github),
+            _ => Err(OptionParseError(s.to_owned())),
+        }
+    }
+}
@@ -0,0 +1,1894 @@
+use crate::{api, check, extract, generate, inject};
+use clap::CommandFactory;
+use clap_mangen::roff;
+use std::{env, io, path, time};
+
+#[derive(Clone, Debug, clap::Parser)]
+#[command(
+    after_long_help = examples_help(),
+    about,
+    long_about = "\
+        Fetch the site configuration of a MediaWiki based wiki, and output rust code for creating \
+        a configuration for `parse_wiki_text` specific to that wiki.  Write generated code to \
+        stdout, as a constant expression of type `parse_wiki_text::ConfigurationSource`.  Write \
+        log messages to stderr.\
+        ",
+    subcommand_negates_reqs = true,
+    version
+)]
+pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[arg(
+        help = "The domain name of the wiki (e.g. `en.wikipedia.org`), or of several wikis",
+        long_help = "The domain name of the wiki (e.g. `en.wikipedia.org`), whose API is at \
+            `/w/api.php` unless `--api-path` is given.  For wikis sharing a host, followed by the \
+            script path of the wiki, under which its API is at `api.php` (e.g. \
+            `example.org/wiki-a`), or by the path of the API itself (e.g. \
+            `example.org/wiki-a/w/api.php`).  Or the complete URL of the API, used as is (e.g. \
+            `https://wiki.example.com:8443/custom/api`), over plain HTTP only if the URL says so, \
+            with a warning.  Several wikis are fetched in \
+            turn: each output file is then written once per wiki, with the domain before its \
+            extension, e.g. `config.en.wikipedia.org.rs`, and Rust output to stdout as a module \
+            per wiki, e.g. `en_wikipedia_org`",
+        env = "FETCH_MEDIAWIKI_CONFIGURATION_DOMAIN",
+        required_unless_present_any = [
+            "changelog",
+            "compact_snapshots",
+            "crawl",
+            "domains_from",
+            "fixture",
+            "from_dump",
+            "from_file",
+            "from_local_settings",
+            "man_page",
+            "replay_serve",
+            "rpc",
+            "stdin_protocol",
+            "sync",
+            "verify_signature"
+        ]
+    )]
+    pub domain: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Read domains from FILE, one per line, or from stdin with `-`",
+        long_help = "Read further domains from FILE, one per line, or from stdin with `-`, for \
+            batches such as all language editions of a project.  Blank lines and lines starting \
+            with `#` are skipped",
+        conflicts_with_all = [
+            "changelog",
+            "compact_snapshots",
+            "crawl",
+            "replay_serve",
+            "rpc",
+            "stdin_protocol",
+            "sync",
+            "verify_signature"
+        ]
+    )]
+    pub domains_from: Option<path::PathBuf>,
+
+    #[arg(
+        long,
+        help = "Go on with the other domains when one fails, and list those that failed",
+        long_help = "Go on with the other domains when one fails, instead of stopping at the \
+            first failure, then list each domain with whether it succeeded, or else the category \
+            of its error (input, network, authentication, response, extraction, lock, generation \
+            or output) and the error, and exit with an error if any failed",
+        conflicts_with_all = ["check", "crawl", "rpc", "stdin_protocol", "sync"]
+    )]
+    pub keep_going: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        help = "Output format of stdout, and of output files with an unrecognized extension",
+        help_heading = "Output",
+        default_value_t = Format::Rust
+    )]
+    pub format: Format,
+
+    #[arg(
+        short,
+        long,
+        value_name = "PATH",
+        help = "Write output to this file instead of stdout (may be repeated)",
+        long_help = "Write output to this file instead of stdout (may be repeated).  The format is \
+            chosen by the file extension: `.rs` for Rust, `.json` for JSON, `.md` for a Markdown \
+            report, otherwise `--format`, e.g. `--format parsertests` for parser tests, which are \
+            not inferred from `.txt`.  The output is written to a temporary file next to it \
+            and renamed into place, so that an interrupted run never leaves it half written",
+        help_heading = "Output"
+    )]
+    pub output: Vec<path::PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "DIR",
+        help = "Write the output of each wiki to DIR, named by `--name-template`",
+        long_help = "Write the output of each wiki to DIR, named by `--name-template`, creating \
+            missing directories, so that the code of each of several wikis lands in a \
+            predictable file",
+        help_heading = "Output",
+        conflicts_with_all = ["check", "crawl", "output", "rpc", "stdin_protocol", "sync"]
+    )]
+    pub out_dir: Option<path::PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "TEMPLATE",
+        help = "File name of the outputs of `--out-dir`, with `{domain}`, `{lang}` and `{date}`",
+        long_help = "File name of the outputs of `--out-dir`, relative to it, in which `{domain}` \
+            is replaced by the domain of the wiki, `{lang}` by its language code (`und` if it \
+            has none), and `{date}` by the current UTC date as `YYYY-MM-DD`, e.g. \
+            `{lang}/{domain}.rs`.  The format is chosen by the extension, as by `--output`",
+        help_heading = "Output",
+        default_value = "{domain}.rs",
+        requires = "out_dir",
+        value_parser = parse_name_template
+    )]
+    pub name_template: String,
+
+    #[arg(
+        long,
+        help = "Create the missing parent directories of output files",
+        help_heading = "Output",
+        requires = "output"
+    )]
+    pub create_dirs: bool,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Sign each output file with the hex-encoded Ed25519 secret key in this file",
+        long_help = "Write a detached Ed25519 signature `<OUTPUT>.sig` next to each output file, \
+            with the hex-encoded 32-byte secret key in this file (e.g. from `openssl rand -hex 32`); \
+            the matching public key is logged",
+        help_heading = "Output",
+        requires = "output"
+    )]
+    pub sign_key: Option<path::PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Write metadata of the fetched configuration to PATH, as JSON",
+        long_help = "Write metadata of the fetched configuration to PATH, as JSON: the fetch time \
+            in seconds since the Unix epoch, the query URL, the MediaWiki version, the siteinfo \
+            `general` fields, and the number of values of each configuration field, so that the \
+            freshness of generated files can be tracked without parsing them",
+        help_heading = "Output",
+        conflicts_with_all = ["crawl", "rpc", "stdin_protocol", "sync"]
+    )]
+    pub metadata_json: Option<path::PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Write the siteinfo response to PATH, pretty-printed, or to stdout with `-`",
+        long_help = "Write the raw siteinfo response to PATH, pretty-printed, as read by \
+            `--from-file`, in addition to the outputs.  With `-`, write it to stdout instead of \
+            the generated code, which is then only written to output files if any",
+        help_heading = "Output",
+        conflicts_with_all = ["check", "crawl", "rpc", "stdin_protocol", "sync"]
+    )]
+    pub dump_raw: Option<path::PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "DIR",
+        help = "Write the link trail, magic words and protocols to data files in DIR",
+        long_help = "Write the link trail, magic words and protocols to data files in DIR, \
+            `link_trail.txt`, `magic_words.txt` and `protocols.txt`, with a line per value, and \
+            emit Rust code loading them with `include_str!` at compile time (requires Rust 1.71), \
+            so that the Rust output stays small, and changes of the data are diffed by line.  The \
+            files are included relative to the Rust output file if DIR is in its directory or \
+            below",
+        help_heading = "Output",
+        conflicts_with_all = ["crawl", "link_trail_encoding", "rpc", "stdin_protocol", "sync"]
+    )]
+    pub split_output: Option<path::PathBuf>,
+
+    #[arg(
+        long,
+        help = "Fail if the generated code would require `std`",
+        long_help = "Fail if the generated code would require `std` or `alloc`, instead of only \
+            `core`, for inclusion in `no_std` crates",
+        help_heading = "Output"
+    )]
+    pub no_std: bool,
+
+    #[arg(
+        long,
+        help = "Check the generated arrays with compile-time assertions",
+        long_help = "Emit the generated arrays as constants, with compile-time assertions that \
+            each is strictly sorted, and non-empty unless generated empty, so that hand edits of \
+            the generated code are caught when it is compiled (requires Rust 1.57)",
+        help_heading = "Output"
+    )]
+    pub const_assertions: bool,
+
+    #[arg(
+        long,
+        value_name = "NAME",
+        help = "Emit a constant item of this name, e.g. `ENWIKI_CONFIG`, instead of an expression",
+        long_help = "Emit a complete constant item of this name, e.g. `pub const ENWIKI_CONFIG: \
+            ::parse_wiki_text::ConfigurationSource<'static> = ...;`, to drop into a module, instead \
+            of the bare constant expression; it is documented with a summary of the wiki and its \
+            values",
+        help_heading = "Output",
+        value_parser = parse_const_name
+    )]
+    pub const_name: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "VISIBILITY",
+        help = "Visibility of the items of `--const-name` and `--emit constructor`",
+        help_heading = "Output",
+        default_value = "pub",
+        value_parser = option_parser::<generate::Visibility>(&["pub", "pub(crate)", "private"])
+    )]
+    pub visibility: generate::Visibility,
+
+    #[arg(
+        long,
+        value_name = "WHAT",
+        help = "Emit the configuration as an expression, with a constructor, or as a delta",
+        long_help = "Emit the configuration as a constant expression, or as a constant together \
+            with a function `configuration()` returning the `::parse_wiki_text::Configuration` \
+            built from it, initialized on first use (requires `std`, Rust 1.70), or as the \
+            differences from the default configuration of `parse_wiki_text`: constants `ADDED` \
+            and `REMOVED`, and a function `configuration(defaults)` merging them into the given \
+            defaults, for much smaller outputs of wikis close to stock MediaWiki",
+        help_heading = "Output",
+        default_value = "expression",
+        value_parser = option_parser::<generate::Emit>(&["expression", "constructor", "delta"])
+    )]
+    pub emit: generate::Emit,
+
+    #[arg(
+        long,
+        value_name = "ENCODING",
+        help = "Emit the link trail as a string, or as ranges of characters",
+        long_help = "Emit the link trail as a string literal, or as ranges of characters from which \
+            the string is built at compile time (requires Rust 1.63), for the link trails of e.g. \
+            CJK wikis, of tens of thousands of characters.  With `--const-name` or `--emit \
+            constructor`, the ranges are also emitted as a constant `LINK_TRAIL_RANGES`, with a \
+            function `is_link_trail_character` matching characters against them",
+        help_heading = "Output",
+        default_value = "string",
+        value_parser = option_parser::<generate::LinkTrailEncoding>(&["string", "ranges"])
+    )]
+    pub link_trail_encoding: generate::LinkTrailEncoding,
+
+    #[arg(
+        long,
+        help = "Escape non-ASCII characters of generated Rust code as `\\u{...}`",
+        long_help = "Escape every non-ASCII character in the string and character literals of \
+            generated Rust code as `\\u{...}`, and in its comments, so that it is ASCII only",
+        help_heading = "Output"
+    )]
+    pub escape_unicode: bool,
+
+    #[arg(
+        long,
+        value_name = "WIDTH",
+        help = "Wrap the arrays of generated Rust code at this line width",
+        long_help = "Wrap the arrays of generated Rust code that would make a line wider than \
+            WIDTH, with as many elements per line as fit, so that diffs of regenerated code show \
+            the changed elements instead of a single changed line",
+        help_heading = "Output"
+    )]
+    pub max_width: Option<usize>,
+
+    #[arg(
+        long,
+        help = "Also emit a function `namespace_id` resolving namespace names to ids",
+        long_help = "Also emit a function `namespace_id(name: &str) -> Option<i64>`, resolving the \
+            names, canonical names and aliases of all namespaces of the wiki to their ids, \
+            case-insensitively and with underscores for spaces as by MediaWiki, backed by a \
+            sorted constant table `NAMESPACE_IDS` (requires `--const-name` or `--emit` other \
+            than `expression`)",
+        help_heading = "Output"
+    )]
+    pub namespace_ids: bool,
+
+    #[arg(
+        long,
+        value_name = "CRATE",
+        help = "Parser crate to generate Rust code for (may be repeated, or `all`)",
+        long_help = "Parser crate to generate Rust code for: `parse-wiki-text`, or its maintained \
+            fork `parse-wiki-text-2`, whose `ConfigurationSource` has the same fields.  May be \
+            repeated, or `all`, to generate for several crates from a single fetch: each Rust \
+            output file is then written once per crate, with the crate name before its \
+            extension, e.g. `enwiki.parse_wiki_text_2.rs`",
+        help_heading = "Output",
+        default_value = "parse-wiki-text",
+        value_parser = option_parser::<TargetSelection>(&[
+            "parse-wiki-text",
+            "parse-wiki-text-2",
+            "all"
+        ])
+    )]
+    pub target: Vec<TargetSelection>,
+
+    #[arg(
+        long,
+        value_name = "SIZE",
+        help = "Warn or fail if generated Rust code is larger than SIZE bytes (suffix K, M or G)",
+        long_help = "Warn or fail (see `--output-size-policy`) if generated Rust code is larger than \
+            SIZE bytes, with an optional binary suffix `K`, `M` or `G`.  The link trails of some \
+            wikis, e.g. CJK ones, produce literals of several megabytes",
+        help_heading = "Output",
+        value_parser = parse_size
+    )]
+    pub max_output_size: Option<u64>,
+
+    #[arg(
+        long,
+        value_enum,
+        value_name = "POLICY",
+        help = "Whether to warn or fail when `--max-output-size` is exceeded",
+        help_heading = "Output",
+        default_value_t = SizePolicy::Warn
+    )]
+    pub output_size_policy: SizePolicy,
+
+    #[arg(
+        long,
+        value_name = "WORDS",
+        help = "Prefix the names of the emitted items with these words, e.g. `en-wikipedia`",
+        long_help = "Prefix the names of the emitted constants and functions with these words, \
+            separated by non-alphanumeric characters or case changes, e.g. `en-wikipedia` for \
+            `EN_WIKIPEDIA_MAGIC_WORDS`, so that outputs for several wikis can share a namespace",
+        help_heading = "Output",
+        value_parser = parse_item_words
+    )]
+    pub item_prefix: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "WORDS",
+        help = "Suffix the names of the emitted items with these words",
+        help_heading = "Output",
+        value_parser = parse_item_words
+    )]
+    pub item_suffix: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "STYLE",
+        help = "Case style of the names of the emitted items [default: per item kind]",
+        help_heading = "Output",
+        value_parser = option_parser::<generate::CaseStyle>(&[
+            "camel",
+            "pascal",
+            "screaming-snake",
+            "snake"
+        ])
+    )]
+    pub item_case: Option<generate::CaseStyle>,
+
+    #[arg(
+        long,
+        value_name = "POLICY",
+        help = "Keep or strip the trailing `://` or `:` of protocols",
+        help_heading = "Extraction",
+        default_value = "keep",
+        value_parser = option_parser::<extract::ProtocolSuffix>(&["keep", "strip"])
+    )]
+    pub protocol_suffix: extract::ProtocolSuffix,
+
+    #[arg(
+        long,
+        value_name = "POLICY",
+        help = "Lowercase protocols, or preserve their case (case variants are dropped either way)",
+        help_heading = "Extraction",
+        default_value = "lower",
+        value_parser = option_parser::<extract::ProtocolCase>(&["lower", "preserve"])
+    )]
+    pub protocol_case: extract::ProtocolCase,
+
+    #[arg(
+        long = "protocol-allow",
+        value_name = "PROTOCOL",
+        help = "Only keep protocols in this allowlist (may be repeated)",
+        help_heading = "Extraction"
+    )]
+    pub protocol_allowlist: Vec<String>,
+
+    #[arg(
+        long = "extra-category-namespace",
+        value_name = "NAME",
+        help = "Also recognize NAME as a category namespace (may be repeated)",
+        help_heading = "Extraction"
+    )]
+    pub extra_category_namespaces: Vec<String>,
+
+    #[arg(
+        long = "extra-file-namespace",
+        value_name = "NAME",
+        help = "Also recognize NAME as a file namespace (may be repeated)",
+        help_heading = "Extraction"
+    )]
+    pub extra_file_namespaces: Vec<String>,
+
+    #[arg(
+        long,
+        help = "Use the default protocols of MediaWiki if the wiki returns none",
+        long_help = "Use the default `$wgUrlProtocols` of MediaWiki 1.39 if the wiki returns no \
+            protocols, with a warning and a note in the output, instead of generating a \
+            configuration that recognizes no external links",
+        help_heading = "Extraction"
+    )]
+    pub protocol_fallback: bool,
+
+    #[arg(
+        long,
+        value_name = "FOLDING",
+        help = "Case folding of namespace names",
+        long_help = "Case folding of the names of category and file namespaces: `lowercase` as \
+            `str::to_lowercase`, or `unicode` for full Unicode case folding, which e.g. also maps \
+            `ß` to `ss` and final `ς` to `σ`",
+        help_heading = "Extraction",
+        default_value = "lowercase",
+        value_parser = option_parser::<extract::CaseFolding>(&["lowercase", "unicode"])
+    )]
+    pub case_folding: extract::CaseFolding,
+
+    #[arg(
+        long,
+        value_name = "FORM",
+        help = "Unicode normalization of namespace names, magic words and their aliases",
+        long_help = "Unicode normalization of namespace names, magic words and their aliases, \
+            around case folding: `nfc` composes decomposed characters, so that they match composed \
+            wikitext, and names differing only in their decomposition are merged; `nfkc` also \
+            replaces compatibility characters, e.g. full-width letters",
+        help_heading = "Extraction",
+        default_value = "none",
+        value_parser = option_parser::<extract::Normalization>(&["none", "nfc", "nfkc"])
+    )]
+    pub normalization: extract::Normalization,
+
+    #[arg(
+        long,
+        value_name = "SET",
+        help = "Which namespace set keeps a name that is both a category and a file namespace",
+        long_help = "Which namespace set keeps a name that is both a category and a file \
+            namespace name, e.g. through an odd alias, which is warned about: `both`, or only \
+            `category` or `file`",
+        help_heading = "Extraction",
+        default_value = "both",
+        value_parser = option_parser::<extract::DuplicateNamespaces>(&["both", "category", "file"])
+    )]
+    pub duplicate_namespaces: extract::DuplicateNamespaces,
+
+    #[arg(
+        long,
+        help = "Maximum log level",
+        help_heading = "Logging",
+        env = "FETCH_MEDIAWIKI_CONFIGURATION_LOG_LEVEL",
+        default_value = "info",
+        ignore_case = true,
+        value_parser = option_parser::<log::LevelFilter>(&[
+            "off", "error", "warn", "info", "debug", "trace"
+        ])
+    )]
+    pub log_level: log::LevelFilter,
+
+    #[arg(
+        long,
+        value_enum,
+        value_name = "WHEN",
+        help = "Print a summary of the run to stderr at its end",
+        long_help = "Print a summary of the run to stderr at its end: the wikis and outputs, the \
+            warnings by module, and the time spent in each phase",
+        help_heading = "Logging",
+        default_value_t = SummaryMode::Auto
+    )]
+    pub summary: SummaryMode,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Write the timings of the run to PATH, as JSON",
+        long_help = "Write the timings of the run to PATH at its end: the phases, and the spans \
+            of fetching, decoding, each extraction function and generation, as JSON.  If PATH \
+            ends in `.folded`, write the self time of each span in microseconds as folded stacks \
+            instead, for flame graph tools such as `inferno-flamegraph`",
+        help_heading = "Logging"
+    )]
+    pub telemetry: Option<path::PathBuf>,
+
+    #[arg(
+        long,
+        help = "Check that the API endpoint is reachable before fetching",
+        long_help = "Check that the API endpoint is reachable before fetching, with a cheap \
+            request that fails early on authentication walls, redirects or missing endpoints",
+        help_heading = "Connection"
+    )]
+    pub preflight: bool,
+
+    #[arg(
+        long,
+        help = "Query only the siteinfo properties supported by the wiki",
+        long_help = "Before fetching, look up the siteinfo properties supported by the wiki with \
+            `action=paraminfo`, and leave the unsupported ones out of the query with a warning, \
+            instead of failing on minimal or heavily customized installations.  The fields \
+            extracted from the left out properties are empty",
+        help_heading = "Connection",
+        conflicts_with = "as_of"
+    )]
+    pub probe: bool,
+
+    #[arg(
+        long,
+        value_name = "DATE",
+        help = "Use the response archived by the Wayback Machine closest to this date",
+        long_help = "Use the response archived by the Wayback Machine closest to this date \
+            (`YYYY-MM-DD`), to approximate the configuration of the wiki at that time.  Only works \
+            if the exact query URL was archived",
+        help_heading = "Connection",
+        value_parser = parse_date
+    )]
+    pub as_of: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 2,
+        help = "Retry failed requests up to N times, with exponential backoff",
+        long_help = "Retry failed requests up to N times, waiting 1, 2, 4, ... seconds in between.  \
+            Only network errors, server errors (5xx) and rate limiting (429) are retried, unless \
+            `--retry-all-errors` is given",
+        help_heading = "Connection"
+    )]
+    pub retries: u32,
+
+    #[arg(
+        long,
+        value_name = "DURATION",
+        default_value = "30s",
+        value_parser = parse_duration,
+        help = "Fail each request not completed within DURATION, e.g. `10s`",
+        long_help = "Fail each request, from connecting to reading the whole response, not \
+            completed within DURATION, e.g. `10s` or `2m`, so that slow wikis fail predictably \
+            instead of hanging.  Timeouts are network errors, retried as such",
+        help_heading = "Connection"
+    )]
+    pub timeout: time::Duration,
+
+    #[arg(
+        long,
+        value_name = "DURATION",
+        default_value = "10s",
+        value_parser = parse_duration,
+        help = "Fail each request not connected within DURATION, e.g. `3s`",
+        long_help = "Fail each request whose connection is not established within DURATION, e.g. \
+            `3s`, so that unreachable wikis fail quickly.  Timeouts are network errors, retried \
+            as such",
+        help_heading = "Connection"
+    )]
+    pub connect_timeout: time::Duration,
+
+    #[arg(
+        long,
+        value_name = "URL",
+        value_parser = parse_proxy,
+        help = "Send all requests through the proxy at URL, e.g. `socks5h://localhost:1080`",
+        long_help = "Send all requests through the HTTP, HTTPS or SOCKS5 proxy at URL, e.g. \
+            `http://proxy:3128` or `socks5h://localhost:1080` for an SSH tunnel (`ssh -D 1080`) \
+            or Tor, to reach internal or region-blocked wikis; `socks5h` resolves host names \
+            through the proxy, `socks5` locally.  Credentials may be given in the URL.  Without \
+            it, the proxies of the `HTTP_PROXY`, `HTTPS_PROXY`, `ALL_PROXY` and `NO_PROXY` \
+            environment variables are used",
+        help_heading = "Connection"
+    )]
+    pub proxy: Option<url::Url>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Trust the CA certificates of the PEM file at PATH, besides the system ones",
+        long_help = "Trust the CA certificates of the PEM file at PATH besides those of the \
+            system, e.g. the private CA of a self-hosted wiki with an internal certificate.  May \
+            be given several times",
+        help_heading = "Connection",
+        conflicts_with = "insecure"
+    )]
+    pub ca_cert: Vec<path::PathBuf>,
+
+    #[arg(
+        long,
+        help = "DANGEROUS: do not verify TLS certificates",
+        long_help = "DANGEROUS: accept any TLS certificate, expired, self-signed or of another \
+            host, so that anyone on the network path can tamper with the responses, and with \
+            them the generated code, and read any credentials sent.  Only for testing; trust \
+            the CA of a self-hosted wiki with `--ca-cert` instead",
+        help_heading = "Connection"
+    )]
+    pub insecure: bool,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Authenticate with the client certificate of the PEM file at PATH, for mutual TLS",
+        long_help = "Authenticate to wikis requiring mutual TLS with the client certificate, \
+            followed by any intermediate certificates, of the PEM file at PATH, and the private \
+            key of `--client-key`",
+        help_heading = "Connection",
+        requires = "client_key"
+    )]
+    pub client_cert: Option<path::PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "The private key of `--client-cert`, as PEM PKCS #8",
+        long_help = "The private key of `--client-cert`, unencrypted as PEM PKCS #8 (`BEGIN \
+            PRIVATE KEY`); convert other keys with `openssl pkcs8 -topk8 -nocrypt`",
+        help_heading = "Connection",
+        requires = "client_cert"
+    )]
+    pub client_key: Option<path::PathBuf>,
+
+    #[arg(
+        long,
+        help = "Also retry client errors (4xx) and errors reported by the API",
+        long_help = "Also retry client errors (4xx), invalid responses and errors reported by the \
+            API, for flaky wikis behind appliances that fail with arbitrary errors",
+        help_heading = "Connection"
+    )]
+    pub retry_all_errors: bool,
+
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Stop after N HTTP requests, in total over all wikis and retries",
+        long_help = "Stop after N HTTP requests, in total over all wikis and retries, as a safety \
+            valve for the batch and crawl modes.  The budget is checked before each attempt, so it \
+            may be overrun by the few requests of the last attempt",
+        help_heading = "Connection"
+    )]
+    pub max_requests: Option<u32>,
+
+    #[arg(
+        long,
+        value_name = "DURATION",
+        value_parser = parse_duration,
+        help = "Fail if the whole run takes longer than DURATION, in seconds or e.g. `10m`",
+        long_help = "Fail if the whole run, over all wikis and retries, takes longer than \
+            DURATION, in seconds or e.g. `10m`, so that CI jobs cannot hang.  Requests in progress \
+            are cut short at the deadline, no retries are waited for past it, and the wikis left \
+            fail without being fetched",
+        help_heading = "Connection",
+        conflicts_with_all = ["rpc", "stdin_protocol"]
+    )]
+    pub deadline: Option<time::Duration>,
+
+    #[arg(
+        long,
+        value_name = "DURATION",
+        default_value = "1h",
+        value_parser = parse_duration,
+        help = "Use siteinfo responses cached for up to DURATION, e.g. `30m` or `1d`",
+        long_help = "Use the siteinfo response of a wiki cached on disk by an earlier run of the \
+            same request for up to DURATION, e.g. `30m` or `1d`, instead of fetching it again.  \
+            Responses are cached in the user cache directory, except those of `--as-of`, \
+            `--fixture`, `--from-file`, `--from-dump` and `--rpc`",
+        help_heading = "Connection"
+    )]
+    pub cache_ttl: time::Duration,
+
+    #[arg(
+        long,
+        help = "Neither use nor update cached siteinfo responses",
+        help_heading = "Connection",
+        conflicts_with_all = ["cache_ttl", "refresh"]
+    )]
+    pub no_cache: bool,
+
+    #[arg(
+        long,
+        help = "Fetch siteinfo responses even if cached, and cache them again",
+        help_heading = "Connection"
+    )]
+    pub refresh: bool,
+
+    #[arg(
+        long,
+        help = "Never fetch: use cached siteinfo responses however old, and fail for those not \
+            cached",
+        long_help = "Never fetch: use the cached siteinfo responses of the same requests however \
+            old, and fail for wikis not cached, e.g. on an offline build machine with the cache \
+            of a bundle from `cache import`",
+        help_heading = "Connection",
+        conflicts_with_all = [
+            "as_of", "crawl", "fixture", "from_dump", "from_file", "from_local_settings",
+            "no_cache", "refresh", "rpc"
+        ]
+    )]
+    pub from_cache_only: bool,
+
+    #[arg(
+        long,
+        help = "Never touch the network, and fail if a wiki is not cached or not as locked",
+        long_help = "Never touch the network, as `cargo --frozen`: use the cached siteinfo \
+            responses however old, as `--from-cache-only`, and fail clearly for wikis not \
+            cached, and with `--lockfile` for responses not as recorded in it, as `--locked`, \
+            for reproducible offline builds.  Fixtures and saved responses, dumps and settings \
+            are read as usual",
+        help_heading = "Connection",
+        conflicts_with_all = ["as_of", "crawl", "no_cache", "refresh", "rpc"]
+    )]
+    pub frozen: bool,
+
+    #[arg(
+        short,
+        long,
+        value_name = "N",
+        default_value_t = 1,
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..),
+        help = "Fetch up to N of several domains at a time",
+        long_help = "Fetch, extract and write the outputs of up to N of several domains at a \
+            time, on as many threads sharing the HTTP client and its sessions.  Once a wiki fails, \
+            no more are started, and the first failure in the order of the domains is reported",
+        help_heading = "Connection"
+    )]
+    pub jobs: usize,
+
+    #[arg(
+        long,
+        value_name = "NAME",
+        value_parser = clap::builder::PossibleValuesParser::new(
+            api::fixture::FIXTURES.iter().map(|(name, _)| *name)
+        ),
+        help = "Use a siteinfo response bundled with the crate instead of connecting to the wiki",
+        long_help = "Use a minimized siteinfo response bundled with the crate instead of connecting \
+            to the wiki, to exercise extraction and its errors offline; the domain defaults to the \
+            fixture name",
+        help_heading = "Connection",
+        conflicts_with_all = ["as_of", "preflight"]
+    )]
+    pub fixture: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Read a saved siteinfo response instead of connecting to the wiki",
+        long_help = "Read a siteinfo response saved earlier, e.g. by `--dump-raw`, instead of \
+            connecting to the wiki, for air-gapped builds and deterministic CI.  The domain \
+            defaults to the server name of the response, or else to the name of the file without \
+            its extension",
+        help_heading = "Connection",
+        conflicts_with_all = [
+            "as_of", "crawl", "domains_from", "fixture", "from_local_settings", "preflight",
+            "probe", "replay", "replay_cassette", "rpc", "stdin_protocol", "sync"
+        ]
+    )]
+    pub from_file: Option<path::PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Read the namespaces from the header of a MediaWiki XML dump, and the rest from \
+            defaults",
+        long_help = "Read the site name, case and namespaces from the `<siteinfo>` header of a \
+            MediaWiki XML dump, e.g. `pages-articles.xml.bz2`, compressed with bzip2 or gzip by \
+            its extension, instead of connecting to the wiki.  Dumps lack the rest, which is \
+            filled in from defaults: the canonical namespace names and aliases of MediaWiki, its \
+            English link trail, and the extension tags, magic words and protocols of the parser \
+            defaults.  The domain defaults to the host of the main page of the dump",
+        help_heading = "Connection",
+        conflicts_with_all = [
+            "as_of", "crawl", "domains_from", "fixture", "from_file", "from_local_settings",
+            "preflight", "probe", "replay", "replay_cassette", "rpc", "stdin_protocol", "sync"
+        ]
+    )]
+    pub from_dump: Option<path::PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Derive a best-effort configuration from a `LocalSettings.php`, for wikis whose API \
+            is disabled",
+        long_help = "Derive a best-effort configuration from the `LocalSettings.php` of a wiki \
+            whose API is disabled or unreachable, instead of connecting to it.  The file is not \
+            run: only assignments of literals are read, of `$wgSitename`, `$wgLanguageCode`, \
+            `$wgServer`, `$wgCapitalLinks`, `$wgMetaNamespace`, `$wgUrlProtocols`, \
+            `$wgExtraNamespaces` and `$wgNamespaceAliases`, with the extensions loaded by \
+            `wfLoadExtension` or `require_once`, whose extension tags are added if known.  \
+            Computed settings are missed and conditions are not evaluated, and the rest is filled \
+            in from defaults as for `--from-dump`.  The domain defaults to the host of \
+            `$wgServer`",
+        help_heading = "Connection",
+        conflicts_with_all = [
+            "as_of", "crawl", "domains_from", "fixture", "from_dump", "from_file", "preflight",
+            "probe", "replay", "replay_cassette", "rpc", "stdin_protocol", "sync"
+        ]
+    )]
+    pub from_local_settings: Option<path::PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "USER",
+        help = "Log in with a bot password as this user (`<user>@<bot name>`)",
+        long_help = "Log in to the wiki with a bot password (from `Special:BotPasswords`) as this \
+            user, in the form `<user>@<bot name>`, to fetch from private wikis.  The password is \
+            taken from `--login-password` or its environment variable, or else obtained from \
+            `--credential-command`, or else prompted for on the terminal",
+        help_heading = "Connection",
+        conflicts_with_all = ["auth_header", "oauth_token"]
+    )]
+    pub login_user: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "PASSWORD",
+        help = "Bot password for `--login-user`",
+        long_help = "Bot password for `--login-user`.  Prefer the environment variable, \
+            `--credential-command` or the prompt, so that the password is not in the shell \
+            history",
+        help_heading = "Connection",
+        env = "FETCH_MEDIAWIKI_CONFIGURATION_LOGIN_PASSWORD",
+        hide_env_values = true,
+        requires = "login_user"
+    )]
+    pub login_password: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "COMMAND",
+        help = "Obtain the bot password of `--login-user` from a git-style credential helper",
+        long_help = "Obtain the bot password of `--login-user` by running COMMAND with the \
+            argument `get` through the shell, as a git credential helper, e.g. \
+            `git credential-store` or a script: the protocol, host and user name of the wiki are \
+            written to its stdin as `key=value` lines, and the password read from its stdout, as \
+            `password=<password>`",
+        help_heading = "Connection",
+        requires = "login_user",
+        conflicts_with = "login_password"
+    )]
+    pub credential_command: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Send the cookies of FILE, in the Netscape format, e.g. of a browser session",
+        long_help = "Send the cookies of FILE, in the Netscape `cookies.txt` format exported by \
+            browser extensions and written by `curl -c`, with the requests to their wikis, e.g. \
+            to reuse a session authenticated in a browser behind SSO.  Expired cookies are left \
+            out",
+        help_heading = "Connection"
+    )]
+    pub cookies: Option<path::PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "HEADER",
+        help = "Send this header (`Name: value`) with all requests (may be repeated)",
+        long_help = "Send this header, in the form `Name: value`, with all requests, e.g. the API \
+            key of a gateway in front of the wiki or `X-Forwarded-For`.  May be given several \
+            times; of the same name, the last one is sent, and replaces a default header, e.g. \
+            `User-Agent`",
+        help_heading = "Connection"
+    )]
+    pub header: Vec<api::auth::Header>,
+
+    #[arg(
+        long,
+        value_name = "CODINGS",
+        value_delimiter = ',',
+        value_parser = option_parser::<api::Compression>(&[
+            "gzip",
+            "deflate",
+            "br",
+            "zstd",
+            "none"
+        ]),
+        help = "Accept responses compressed with these codings [default: gzip,deflate]",
+        long_help = "Accept responses compressed with these codings, separated by commas, in \
+            order of preference, e.g. `br,zstd,gzip` for smaller responses from Wikimedia wikis, \
+            or `none` for uncompressed responses, when debugging a proxy which mangles \
+            compressed ones [default: gzip,deflate]",
+        help_heading = "Connection"
+    )]
+    pub compression: Option<Vec<api::Compression>>,
+
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Follow at most N redirects [default: 10]",
+        long_help = "Follow at most N redirects of a request, failing with more [default: 10].  \
+            Redirects to another host, e.g. of an alias domain, are logged",
+        help_heading = "Connection",
+        conflicts_with = "no_redirects"
+    )]
+    pub max_redirects: Option<usize>,
+
+    #[arg(
+        long,
+        help = "Fail if a request is redirected",
+        long_help = "Fail if a request is redirected, e.g. to find out whether the domain given \
+            is an alias of the wiki; the same as `--max-redirects 0`",
+        help_heading = "Connection"
+    )]
+    pub no_redirects: bool,
+
+    #[arg(
+        long,
+        value_name = "HEADER",
+        help = "Authenticate requests to the wiki with this header (`Name: value`)",
+        long_help = "Authenticate requests to the wiki with this header, in the form `Name: value`, \
+            e.g. the header expected by an SSO gateway; see `--oauth-token` for OAuth 2 \
+            owner-only consumers",
+        help_heading = "Connection",
+        env = "FETCH_MEDIAWIKI_CONFIGURATION_AUTH_HEADER",
+        hide_env_values = true
+    )]
+    pub auth_header: Option<api::auth::Header>,
+
+    #[arg(
+        long,
+        value_name = "TOKEN",
+        help = "Authenticate requests to the wiki with an OAuth 2 access token",
+        long_help = "Authenticate requests to the wiki with the access token of an OAuth 2 \
+            owner-only consumer (`Special:OAuthConsumerRegistration/propose/oauth2`), sent as \
+            `Authorization: Bearer <TOKEN>`, instead of logging in with a bot password.  Prefer \
+            the environment variable, so that the token is not in the shell history",
+        help_heading = "Connection",
+        env = "FETCH_MEDIAWIKI_CONFIGURATION_OAUTH_TOKEN",
+        hide_env_values = true,
+        value_parser = parse_oauth_token,
+        conflicts_with = "auth_header"
+    )]
+    pub oauth_token: Option<api::auth::Header>,
+
+    #[arg(
+        long,
+        value_name = "ROLE",
+        help = "Fail unless the requests are authenticated as a user, or as a bot",
+        long_help = "Add `assert=<ROLE>` to the query, so that the API fails with a clear error \
+            unless the requests are authenticated as a user, or as a user with the `bot` right, \
+            instead of silently returning the configuration visible to anonymous users",
+        help_heading = "Connection",
+        value_parser = option_parser::<api::auth::Assert>(&["user", "bot"]),
+        conflicts_with = "as_of"
+    )]
+    pub assert: Option<api::auth::Assert>,
+
+    #[arg(
+        long,
+        value_name = "KEY=VALUE",
+        help = "Append a parameter to the API query URL",
+        long_help = "Append a parameter to the API query URL, such as `uselang=en`, \
+            `variant=sr-el` or `origin=*`, to work around wiki-specific behavior.  Appended \
+            last, so that it takes precedence over a parameter of the query with the same key.  \
+            Can be given several times",
+        help_heading = "Connection",
+        value_parser = parse_raw_param
+    )]
+    pub raw_param: Vec<(String, String)>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "The path of the API, for wikis not at `/w/api.php`",
+        long_help = "The path of the API under the domain, for wikis given without a path whose \
+            API is not at `/w/api.php`, e.g. `/api.php` of Fandom wikis or \
+            `/mediawiki/api.php`.  A path not ending in `.php` is the script path, under which \
+            the API is at `api.php`, e.g. `/` for `/api.php`",
+        help_heading = "Connection"
+    )]
+    pub api_path: Option<String>,
+
+    #[arg(
+        long,
+        help = "Send the query as a POST request, with a form-encoded body",
+        long_help = "Send the siteinfo query as a POST request, with its parameters in a \
+            form-encoded body instead of the URL, as required by some restrictive gateways and \
+            for long queries, e.g. with many `--raw-param`.  The requests of `--preflight` and \
+            `--probe` are still sent with GET",
+        help_heading = "Connection",
+        conflicts_with = "as_of"
+    )]
+    pub post: bool,
+
+    #[arg(
+        long,
+        help = "Pin the resolved API endpoint, and warn if it changes on later runs",
+        long_help = "Record the resolved API endpoint URL (after redirects) of the wiki in the \
+            state file on first use, and warn if it differs on later runs",
+        help_heading = "Connection"
+    )]
+    pub pin_endpoint: bool,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "State file for pinned endpoints [default: in the user state directory]",
+        help_heading = "Connection",
+        env = "FETCH_MEDIAWIKI_CONFIGURATION_STATE_FILE"
+    )]
+    pub state_file: Option<path::PathBuf>,
+
+    #[arg(
+        long,
+        help = "Leave the outputs of wikis whose siteinfo and options did not change as they were",
+        long_help = "Leave the output files of wikis whose siteinfo response and options did not \
+            change since they were last generated as they were, recording a hash of both in the \
+            state file, and of the others rewrite only those whose contents differ.  Batches and \
+            manifests log how many wikis were updated and how many were unchanged",
+        help_heading = "Output",
+        conflicts_with_all = ["check", "crawl", "rpc", "stdin_protocol"]
+    )]
+    pub incremental: bool,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Record the MediaWiki version and a hash of the siteinfo of each wiki in a lockfile",
+        long_help = "Record the MediaWiki version, the time of fetching and a SHA-256 hash of the \
+            raw siteinfo response of each wiki generated in a TOML lockfile, keeping the entries \
+            of wikis whose siteinfo did not change as they were",
+        help_heading = "Output",
+        conflicts_with_all = ["crawl", "rpc", "stdin_protocol"]
+    )]
+    pub lockfile: Option<path::PathBuf>,
+
+    #[arg(
+        long,
+        help = "Generate a module per domain, and a function returning the configuration of each",
+        long_help = "Generate the Rust output of the domains to stdout as a module per domain, \
+            followed by a function `configuration_for(domain: &str) -> \
+            Option<&'static ConfigurationSource>` returning the named constant of the module of \
+            each domain, in its canonical form, e.g. `en.wikipedia.org`, for applications parsing \
+            the wikitext of several known wikis at runtime.  The constant is named as by \
+            `--const-name`, by default `CONFIGURATION_SOURCE`",
+        help_heading = "Output",
+        conflicts_with_all = ["check", "crawl", "rpc", "stdin_protocol", "sync"]
+    )]
+    pub registry: bool,
+
+    #[arg(
+        long,
+        help = "Fail if the siteinfo of a wiki is not the one in the lockfile",
+        long_help = "Fail, before writing any output, if the siteinfo response of a wiki is not \
+            the one recorded in the lockfile, or the wiki is not in it, leaving the lockfile as it \
+            was, e.g. for reproducible builds in CI",
+        help_heading = "Output",
+        requires = "lockfile"
+    )]
+    pub locked: bool,
+
+    #[arg(
+        long,
+        help = "Serve requests read from stdin, one JSON object per line",
+        long_help = "Serve requests read from stdin, one JSON object per line, such as \
+            `{\"domain\": \"en.wikipedia.org\", \"format\": \"json\"}`.  Write one JSON \
+            object per line to stdout for each request, with either an `output` or an `error` \
+            field",
+        help_heading = "Modes",
+        conflicts_with = "domain"
+    )]
+    pub stdin_protocol: bool,
+
+    #[arg(
+        long,
+        help = "Serve JSON-RPC 2.0 requests read from stdin, one per line",
+        long_help = "Serve JSON-RPC 2.0 requests read from stdin, one per line, writing responses \
+            to stdout.  Methods: `fetchSiteinfo`, `extract` and `generate` with parameters \
+            `domain`, optional `format` and `refresh`, and `cacheStatus` to list the responses \
+            cached by the server",
+        help_heading = "Modes",
+        conflicts_with_all = ["domain", "stdin_protocol"]
+    )]
+    pub rpc: bool,
+
+    #[arg(
+        long,
+        value_name = "URL",
+        help = "List the wikis of a wiki farm, from the URL of its index (may be repeated)",
+        long_help = "List the domains of the wikis of a wiki farm to stdout, one per line, from the \
+            URL of a JSON index listing the wikis with their `url`, such as the `sitematrix` API \
+            module of Wikimedia or the `wikidiscover` API module of Miraheze (may be repeated)",
+        help_heading = "Modes",
+        conflicts_with_all = ["domain", "rpc", "stdin_protocol"]
+    )]
+    pub crawl: Vec<url::Url>,
+
+    #[arg(
+        long,
+        num_args = 2,
+        value_names = ["OLD", "NEW"],
+        help = "Summarize the configuration changes between two saved API responses",
+        long_help = "Summarize the configuration changes between two saved siteinfo API responses \
+            of a wiki, as text, or as JSON with `--format json`.  Responses compressed with gzip \
+            are decompressed transparently",
+        help_heading = "Modes",
+        conflicts_with_all = ["crawl", "domain", "rpc", "stdin_protocol"]
+    )]
+    pub changelog: Vec<path::PathBuf>,
+
+    #[arg(
+        long,
+        num_args = 1..,
+        value_name = "PATH",
+        help = "Compress saved API responses with gzip, replacing PATH with PATH.gz",
+        long_help = "Compress saved siteinfo API responses with gzip, replacing each PATH with \
+            PATH.gz once its response is checked to be valid.  Already compressed responses are \
+            skipped",
+        help_heading = "Modes",
+        conflicts_with_all = ["changelog", "crawl", "domain", "rpc", "stdin_protocol"]
+    )]
+    pub compact_snapshots: Vec<path::PathBuf>,
+
+    #[arg(
+        long,
+        visible_alias = "manifest",
+        value_name = "MANIFEST",
+        help = "Regenerate the outputs of all wikis listed in a TOML manifest, e.g. `wikis.toml`",
+        long_help = "Regenerate the outputs of all wikis listed in a TOML manifest, e.g. \
+            `wikis.toml`, with a `[[wiki]]` table per wiki giving its `domain`, `output` path \
+            (relative to the manifest), optional `format`, optional `api-path` of its API under \
+            the domain (e.g. `/wiki-a/w/api.php`), and optional `[wiki.overrides]` of the output \
+            and extraction options (e.g. `target`, or `extra-category-namespaces`), which default \
+            to the `[defaults]` table and then to the command line.  An optional top-level \
+            `by-language` path gets a Rust module `by_language` selecting the Rust outputs by the \
+            language code of their wiki",
+        help_heading = "Modes",
+        conflicts_with_all = ["changelog", "crawl", "domain", "output", "rpc", "stdin_protocol"]
+    )]
+    pub sync: Option<path::PathBuf>,
+
+    #[arg(
+        long,
+        help = "Commit the outputs of each wiki of `--sync` as soon as it is done",
+        long_help = "Commit the outputs of each wiki of `--sync` as soon as it is done, instead of \
+            committing all outputs only once all wikis are done.  Outputs are written to hidden \
+            files next to them, and renamed into place on commit, so that an interrupted or \
+            failed run never leaves an output half written",
+        help_heading = "Modes",
+        requires = "sync"
+    )]
+    pub partial_commit: bool,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Check that a previously generated file is up to date, instead of generating",
+        long_help = "Check that a file previously generated as Rust or JSON (by extension) is up to \
+            date with the wiki, comparing each field by value rather than by text, and fail if \
+            any field is out of date",
+        help_heading = "Modes",
+        conflicts_with_all = ["changelog", "crawl", "output", "rpc", "stdin_protocol", "sync"]
+    )]
+    pub check: Option<path::PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        help = "Format of the findings of `--check`",
+        long_help = "Format of the findings of `--check`: `text` for a readable diff, `annotations` \
+            for `<file>:<line>:<column>: error: <message>` lines recognized by problem matchers, \
+            or `github` for GitHub Actions workflow commands annotating the file",
+        help_heading = "Modes",
+        default_value = "text",
+        value_parser = option_parser::<check::CheckFormat>(&["text", "annotations", "github"])
+    )]
+    pub check_format: check::CheckFormat,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Verify the detached signature `<FILE>.sig` of a generated file",
+        long_help = "Verify the detached signature `<FILE>.sig` of a generated file, with the \
+            public key given by `--verify-key`; exits with an error if it does not match",
+        help_heading = "Modes",
+        requires = "verify_key",
+        conflicts_with_all = ["changelog", "crawl", "domain", "rpc", "stdin_protocol"]
+    )]
+    pub verify_signature: Option<path::PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "File containing the hex-encoded Ed25519 public key for `--verify-signature`",
+        help_heading = "Modes",
+        requires = "verify_signature"
+    )]
+    pub verify_key: Option<path::PathBuf>,
+
+    #[arg(
+        long,
+        help = "Never write anything but the generated output to stdout",
+        long_help = "Never write anything but the generated output to stdout: help, version, the \
+            findings of `--check` and the reports of `cache` are written to stderr, and the \
+            output is written only once it was generated in full, so that `> config.rs` can \
+            never capture anything else",
+        help_heading = "Output"
+    )]
+    pub stdout_only_output: bool,
+
+    #[arg(long, help = "Write a manual page to stdout", hide = true)]
+    pub man_page: bool,
+
+    #[arg(
+        long,
+        value_name = "DIR",
+        help = "Serve saved API responses over HTTP, for fetching with `--replay`",
+        long_help = "Serve the saved siteinfo API responses in DIR over HTTP at \
+            `--replay-address`, the response of each wiki from `<DIR>/<wiki>.json`, for fetching \
+            with `--replay` in examples, tests and bug reproductions without touching the network",
+        hide = true,
+        conflicts_with_all = ["changelog", "crawl", "domain", "rpc", "stdin_protocol", "sync"]
+    )]
+    pub replay_serve: Option<path::PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "ADDRESS",
+        help = "Address of the replay server",
+        default_value = "127.0.0.1:8700",
+        hide = true
+    )]
+    pub replay_address: String,
+
+    #[arg(
+        long,
+        value_name = "ADDRESS",
+        help = "Fetch from the replay server at ADDRESS instead of the wikis",
+        hide = true,
+        conflicts_with_all = ["as_of", "fixture"]
+    )]
+    pub replay: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Replay the responses recorded in PATH by `--record`, instead of fetching",
+        long_help = "Replay the responses recorded by `--record` in PATH instead of fetching, e.g. \
+            to reproduce a bug report about a malformed response.  A request not recorded fails.  \
+            Only the responses are recorded, so logging in, probing and preflight checks cannot \
+            be replayed",
+        help_heading = "Connection",
+        conflicts_with_all = [
+            "as_of", "auth_header", "fixture", "login_user", "oauth_token", "preflight", "probe",
+            "record", "replay"
+        ]
+    )]
+    pub replay_cassette: Option<path::PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Record the siteinfo responses fetched in FILE, for `--replay-cassette`",
+        long_help = "Record the successful siteinfo responses fetched, with the URLs of their \
+            requests, in the JSON file FILE, for replaying them with `--replay-cassette`, e.g. to \
+            capture a malformed response once for a bug report.  Cached responses are not used",
+        help_heading = "Connection",
+        conflicts_with_all = ["frozen", "from_cache_only"]
+    )]
+    pub record: Option<path::PathBuf>,
+
+    #[arg(
+        long,
+        value_enum,
+        value_name = "STAGE",
+        help = "Make the run fail at STAGE, for testing retries and alerting",
+        hide = true
+    )]
+    pub inject_failure: Option<inject::Stage>,
+
+    #[arg(
+        long,
+        value_name = "DURATION",
+        help = "Delay each request by DURATION, e.g. `500ms` or `3s`, for testing timeouts",
+        hide = true,
+        value_parser = parse_duration
+    )]
+    pub inject_latency: Option<time::Duration>,
+}
+
+#[derive(Clone, Copy, Debug, clap::ValueEnum, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Format {
+    #[value(
+        help = "Rust constant expression of type `ConfigurationSource` of the `--target` crate"
+    )]
+    Rust,
+    #[value(help = "JSON object of the extracted configuration, including the namespace table")]
+    Json,
+    #[value(help = "Markdown report of the extracted configuration")]
+    Markdown,
+    #[value(
+        name = "parsertests",
+        help = "Prologue of a MediaWiki parser tests file, with the extension tags as hooks"
+    )]
+    #[serde(rename = "parsertests")]
+    ParserTests,
+    #[value(
+        help = "Compact JSON ruleset for title normalization: case mode, legal title \
+        characters and namespaces"
+    )]
+    TitleRules,
+}
+
+#[derive(Clone, Debug, clap::Subcommand)]
+pub enum Command {
+    #[command(
+        about = "List, inspect, clear, export or import the siteinfo responses cached by \
+            `--cache-ttl`",
+        subcommand_required = true
+    )]
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
+}
+
+#[derive(Clone, Debug, clap::Subcommand)]
+pub enum CacheAction {
+    #[command(about = "List the cached wikis, with the age of each response")]
+    List,
+    #[command(about = "Show the request, the wiki version and the age of the response of a wiki")]
+    Inspect {
+        #[arg(help = "The domain name of the wiki, as given when fetching it")]
+        domain: String,
+    },
+    #[command(about = "Remove the cached responses of the given wikis, or else of all")]
+    Clear {
+        #[arg(help = "The domain names of the wikis, as given when fetching them")]
+        domains: Vec<String>,
+    },
+    #[command(
+        about = "Write the cached responses of the given wikis, or else of all, to a bundle",
+        long_about = "Write the cached responses of the given wikis, or else of all, to a tar \
+            archive, compressed with zstd or gzip by its extension, e.g. `bundle.tar.zst`, to be \
+            imported by `cache import` on a machine without internet access"
+    )]
+    Export {
+        #[arg(value_name = "PATH", help = "The bundle to write")]
+        path: path::PathBuf,
+        #[arg(help = "The domain names of the wikis, as given when fetching them")]
+        domains: Vec<String>,
+    },
+    #[command(
+        about = "Add the responses of a bundle written by `cache export` to the cache",
+        long_about = "Add the responses of a bundle written by `cache export` to the cache, \
+            except those older than the responses cached, for `--from-cache-only`"
+    )]
+    Import {
+        #[arg(value_name = "PATH", help = "The bundle to read")]
+        path: path::PathBuf,
+    },
+}
+
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+pub enum SummaryMode {
+    #[value(help = "If stderr is a terminal")]
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum TargetSelection {
+    One(generate::Target),
+    All,
+}
+
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+pub enum SizePolicy {
+    #[value(help = "Log a warning, and write the output anyway")]
+    Warn,
+    #[value(help = "Fail without writing the output")]
+    Fail,
+}
+
+impl std::str::FromStr for TargetSelection {
+    type Err = generate::OptionParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "all" => Ok(Self::All),
+            _ => s.parse().map(Self::One),
+        }
+    }
+}
+
+impl Format {
+    // Chosen by the file extension, otherwise `default`.
+    pub fn from_path(path: &path::Path, default: Self) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("rs") => Format::Rust,
+            Some("json") => Format::Json,
+            Some("md") => Format::Markdown,
+            _ => default,
+        }
+    }
+}
+
+pub fn generate(
+    format: Format,
+    mut out: impl io::Write,
+    configuration_source: &extract::ConfigurationSource,
+    args: &Args,
+) -> Result<(), generate::Error> {
+    args.generator(format)
+        .generate(&mut out, configuration_source)
+}
+
+impl Args {
+    // Each Rust output once per target, the others once for the first target.
+    pub fn outputs(&self) -> Vec<(Format, generate::Target, Option<path::PathBuf>)> {
+        let targets = self.targets();
+        // The paths of `--out-dir` are rendered once the wiki is fetched, see `name_path`.
+        let template = self
+            .out_dir
+            .as_ref()
+            .map(|directory| directory.join(&self.name_template));
+        let paths: Vec<_> = if let Some(template) = &template {
+            vec![(
+                Format::from_path(template, self.format),
+                Some(template.as_path()),
+            )]
+        } else if self.output.is_empty() && self.dump_raw_stdout() {
+            Vec::new()
+        } else if self.output.is_empty() {
+            vec![(self.format, None)]
+        } else {
+            self.output
+                .iter()
+                .map(|path| (Format::from_path(path, self.format), Some(path.as_path())))
+                .collect()
+        };
+        let mut outputs = Vec::new();
+        for (format, path) in paths {
+            match format {
+                Format::Rust => outputs.extend(targets.iter().map(|&target| {
+                    (
+                        format,
+                        target,
+                        path.map(|p| target_path(p, &targets, target)),
+                    )
+                })),
+                _ => outputs.push((format, targets[0], path.map(path::Path::to_owned))),
+            }
+        }
+        outputs
+    }
+
+    pub fn targets(&self) -> Vec<generate::Target> {
+        let mut targets = Vec::new();
+        for selection in &self.target {
+            let selected = match selection {
+                TargetSelection::One(target) => std::slice::from_ref(target),
+                TargetSelection::All => generate::Target::ALL,
+            };
+            for target in selected {
+                if !targets.contains(target) {
+                    targets.push(*target);
+                }
+            }
+        }
+        targets
+    }
+
+    // Whether the siteinfo response is written to stdout, instead of the generated code.
+    pub fn dump_raw_stdout(&self) -> bool {
+        self.dump_raw.as_deref() == Some(path::Path::new("-"))
+    }
+
+    // The options of one of several domains, with metadata and data files of its own.
+    pub fn for_domain(&self, domain: &str) -> Self {
+        Self {
+            dump_raw: match &self.dump_raw {
+                Some(path) if !self.dump_raw_stdout() => Some(domain_path(path, domain)),
+                path => path.clone(),
+            },
+            metadata_json: self
+                .metadata_json
+                .as_deref()
+                .map(|path| domain_path(path, domain)),
+            split_output: self
+                .split_output
+                .as_ref()
+                .map(|directory| directory.join(api::site::file_name(domain))),
+            ..self.clone()
+        }
+    }
+
+    // The options with only `target` selected, to generate its output.
+    pub fn for_target(&self, target: generate::Target) -> Self {
+        Self {
+            target: vec![TargetSelection::One(target)],
+            ..self.clone()
+        }
+    }
+
+    pub fn auth_provider(&self) -> Box<dyn api::auth::AuthProvider> {
+        if let Some(user) = &self.login_user {
+            let password = if let Some(password) = &self.login_password {
+                api::auth::Password::Given(password.clone())
+            } else if let Some(command) = &self.credential_command {
+                api::auth::Password::Command(command.clone())
+            } else {
+                api::auth::Password::Prompt(|prompt| rpassword::prompt_password(prompt))
+            };
+            Box::new(api::auth::BotPassword::new(user.clone(), password))
+        } else if let Some(header) = self.auth_header.as_ref().or(self.oauth_token.as_ref()) {
+            Box::new(header.clone())
+        } else {
+            Box::new(api::auth::NoAuth)
+        }
+    }
+
+    // Whether the siteinfo is read from a fixture or a file instead of being fetched.
+    pub fn reads_locally(&self) -> bool {
+        self.replay_cassette.is_some()
+            || self.fixture.is_some()
+            || self.from_file.is_some()
+            || self.from_dump.is_some()
+            || self.from_local_settings.is_some()
+    }
+
+    // None without a user cache directory.  The RPC server caches in memory, refreshed on request.
+    pub fn cache(&self) -> Option<api::cache::Cache> {
+        if self.no_cache
+            || self.reads_locally()
+            || self.record.is_some()
+            || self.replay.is_some()
+            || self.rpc
+        {
+            return None;
+        }
+        Some(api::cache::Cache {
+            directory: api::cache::Cache::default_directory()?,
+            ttl: self.cache_ttl,
+            refresh: self.refresh,
+            offline: self.from_cache_only || self.frozen,
+        })
+    }
+
+    pub fn extract_options(&self) -> extract::Options {
+        extract::Options {
+            case_folding: self.case_folding,
+            duplicate_namespaces: self.duplicate_namespaces,
+            extra_category_namespaces: self.extra_category_namespaces.clone(),
+            extra_file_namespaces: self.extra_file_namespaces.clone(),
+            normalization: self.normalization,
+            protocols: extract::ProtocolPolicy {
+                suffix: self.protocol_suffix,
+                case: self.protocol_case,
+                allowlist: if self.protocol_allowlist.is_empty() {
+                    None
+                } else {
+                    Some(self.protocol_allowlist.iter().cloned().collect())
+                },
+                fallback: self.protocol_fallback,
+            },
+            // Set by the caller, as these options are part of the fingerprints of `--incremental`.
+            timing: None,
+        }
+    }
+}
+
+impl Args {
+    pub fn generator(&self, format: Format) -> Box<dyn generate::CodeGenerator> {
+        match format {
+            Format::Rust => Box::new(generate::Rust(self.generate_options())),
+            Format::Json => Box::new(generate::Json),
+            Format::Markdown => Box::new(generate::Markdown),
+            Format::ParserTests => Box::new(generate::ParserTests),
+            Format::TitleRules => Box::new(generate::TitleRules),
+        }
+    }
+
+    pub fn generate_options(&self) -> generate::Options {
+        generate::Options {
+            const_assertions: self.const_assertions,
+            const_name: self.const_name.clone(),
+            emit: self.emit,
+            escape_unicode: self.escape_unicode,
+            link_trail: self.link_trail_encoding,
+            max_width: self.max_width,
+            namespace_ids: self.namespace_ids,
+            naming: generate::Naming {
+                prefix: self.item_prefix.clone().unwrap_or_default(),
+                suffix: self.item_suffix.clone().unwrap_or_default(),
+                case: self.item_case,
+            },
+            no_std: self.no_std,
+            split: self.split_output.clone(),
+            target: self.targets()[0],
+            visibility: self.visibility,
+        }
+    }
+}
+
+pub struct Example {
+    pub description: &'static str,
+    pub command: &'static str,
+}
+
+pub const EXAMPLES: &[Example] = &[
+    Example {
+        description: "Generate the configuration of a single wiki into a file",
+        command: "fetch_mediawiki_configuration en.wikipedia.org > src/configuration.rs",
+    },
+    Example {
+        description: "Generate the configurations of the wikis listed in a file, one per line",
+        command: "fetch_mediawiki_configuration --domains-from wikis.txt --out-dir src",
+    },
+    Example {
+        description: "Generate the configuration offline, from a siteinfo response saved earlier",
+        command: "fetch_mediawiki_configuration --from-file siteinfo.json > src/configuration.rs",
+    },
+    Example {
+        description: "Check that a generated configuration is up to date, e.g. in CI",
+        command: "fetch_mediawiki_configuration en.wikipedia.org --check src/configuration.rs",
+    },
+    Example {
+        description: "List the wikis of a wiki farm",
+        command: "fetch_mediawiki_configuration --crawl \
+            'https://meta.wikimedia.org/w/api.php?action=sitematrix&format=json' > wikis.txt",
+    },
+    Example {
+        description: "Regenerate the configurations of all wikis listed in a manifest",
+        command: "fetch_mediawiki_configuration --sync wikis.toml",
+    },
+];
+
+fn option_parser<T>(
+    values: &'static [&'static str],
+) -> impl clap::builder::TypedValueParser<Value = T>
+where
+    T: std::str::FromStr + Clone + Send + Sync + 'static,
+    T::Err: std::fmt::Debug,
+{
+    use clap::builder::TypedValueParser;
+
+    clap::builder::PossibleValuesParser::new(values).map(|s| s.parse().unwrap())
+}
+
+// Checked on the raw arguments, since help and version are displayed when parsing fails.
+pub fn stdout_only_output_requested() -> bool {
+    env::args_os()
+        .take_while(|arg| arg != "--")
+        .any(|arg| arg == "--stdout-only-output")
+}
+
+// Words of item names must produce identifiers, even at the start of a name.
+fn parse_item_words(s: &str) -> Result<String, String> {
+    let start = s.trim_start_matches(|c: char| !c.is_ascii_alphanumeric());
+    if !s.is_ascii() {
+        Err("expected ASCII characters".to_owned())
+    } else if start.starts_with(|c: char| c.is_ascii_digit()) {
+        Err("expected a letter first".to_owned())
+    } else {
+        Ok(s.to_owned())
+    }
+}
+
+// With several targets, the crate name goes before the extension, e.g. `enwiki.parse_wiki_text.rs`.
+pub fn target_path(
+    path: &path::Path,
+    targets: &[generate::Target],
+    target: generate::Target,
+) -> path::PathBuf {
+    if targets.len() <= 1 {
+        return path.to_owned();
+    }
+    let mut name = path.file_stem().unwrap_or_default().to_owned();
+    name.push(".");
+    name.push(target.crate_name());
+    if let Some(extension) = path.extension() {
+        name.push(".");
+        name.push(extension);
+    }
+    path.with_file_name(name)
+}
+
+// The path of an output of one of several domains, with the domain before its extension, e.g.
+// `config.en.wikipedia.org.rs`.
+pub fn domain_path(path: &path::Path, domain: &str) -> path::PathBuf {
+    let mut name = path.file_stem().unwrap_or_default().to_owned();
+    name.push(".");
+    name.push(api::site::file_name(domain));
+    if let Some(extension) = path.extension() {
+        name.push(".");
+        name.push(extension);
+    }
+    path.with_file_name(name)
+}
+
+// An output path of `--out-dir`, with the variables of `--name-template` replaced.
+pub fn name_path(
+    path: &path::Path,
+    domain: &str,
+    language: Option<&str>,
+    date: &str,
+) -> path::PathBuf {
+    path.to_string_lossy()
+        .replace("{domain}", &api::site::file_name(domain))
+        .replace("{lang}", &api::site::file_name(language.unwrap_or("und")))
+        .replace("{date}", date)
+        .into()
+}
+
+// The UTC date of `time` as `YYYY-MM-DD`, from its days since 1970-01-01 by the `civil_from_days`
+// algorithm of Howard Hinnant.
+pub fn utc_date(time: time::SystemTime) -> String {
+    let days = time
+        .duration_since(time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+        / 86400;
+    let z = days + 719468;
+    let era = z / 146097;
+    let day_of_era = z - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+fn parse_name_template(s: &str) -> Result<String, String> {
+    let mut rest = s;
+    while let Some(start) = rest.find('{') {
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| "expected `}` after `{`".to_owned())?
+            + start;
+        match &rest[start + 1..end] {
+            "domain" | "lang" | "date" => {}
+            name => {
+                return Err(format!(
+                    "unknown variable `{{{}}}`, expected `{{domain}}`, `{{lang}}` or `{{date}}`",
+                    name
+                ))
+            }
+        }
+        rest = &rest[end + 1..];
+    }
+    Ok(s.to_owned())
+}
+
+fn parse_const_name(s: &str) -> Result<String, String> {
+    syn::parse_str::<syn::Ident>(s)
+        .map(|_| s.to_owned())
+        .map_err(|_| "expected an identifier".to_owned())
+}
+
+fn parse_raw_param(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some(("", _)) => Err("expected a non-empty key".to_owned()),
+        Some((key, value)) => Ok((key.to_owned(), value.to_owned())),
+        None => Err("expected `KEY=VALUE`".to_owned()),
+    }
+}
+
+fn parse_oauth_token(s: &str) -> Result<api::auth::Header, String> {
+    api::auth::Header::bearer(s).map_err(|e| e.to_string())
+}
+
+fn parse_proxy(s: &str) -> Result<url::Url, String> {
+    let url = url::Url::parse(s).map_err(|e| e.to_string())?;
+    match url.scheme() {
+        "http" | "https" | "socks5" | "socks5h" => Ok(url),
+        scheme => Err(format!(
+            "unsupported scheme `{}`, expected `http`, `https`, `socks5` or `socks5h`",
+            scheme
+        )),
+    }
+}
+
+// Parse a duration in milliseconds, seconds, minutes, hours or days, e.g. `500ms` or `3s`, or in
+// seconds without a unit.
+fn parse_duration(s: &str) -> Result<time::Duration, String> {
+    let (digits, unit) = if let Some(digits) = s.strip_suffix("ms") {
+        return digits
+            .parse()
+            .map(time::Duration::from_millis)
+            .map_err(|e| format!("{}", e));
+    } else if let Some(digits) = s.strip_suffix('s') {
+        (digits, 1)
+    } else if let Some(digits) = s.strip_suffix('m') {
+        (digits, 60)
+    } else if let Some(digits) = s.strip_suffix('h') {
+        (digits, 60 * 60)
+    } else if let Some(digits) = s.strip_suffix('d') {
+        (digits, 24 * 60 * 60)
+    } else if s.bytes().all(|b| b.is_ascii_digit()) {
+        (s, 1)
+    } else {
+        return Err(
+            "expected a duration as `<N>`, `<N>ms`, `<N>s`, `<N>m`, `<N>h` or `<N>d`".to_owned(),
+        );
+    };
+    let n: u64 = digits.parse().map_err(|e| format!("{}", e))?;
+    n.checked_mul(unit)
+        .map(time::Duration::from_secs)
+        .ok_or_else(|| "duration too large".to_owned())
+}
+
+// Parse a size in bytes, with an optional binary suffix.
+fn parse_size(s: &str) -> Result<u64, String> {
+    let (digits, unit) = match s.char_indices().last() {
+        Some((i, 'K')) | Some((i, 'k')) => (&s[..i], 1 << 10),
+        Some((i, 'M')) | Some((i, 'm')) => (&s[..i], 1 << 20),
+        Some((i, 'G')) | Some((i, 'g')) => (&s[..i], 1 << 30),
+        _ => (s, 1),
+    };
+    let size: u64 = digits.parse().map_err(|e| format!("{}", e))?;
+    size.checked_mul(unit)
+        .ok_or_else(|| "size too large".to_owned())
+}
+
+// Parse a `YYYY-MM-DD` date into a Wayback Machine timestamp.
+// `YYYY-MM-DD`, or `YYYYMMDD`.
+fn parse_date(s: &str) -> Result<String, String> {
+    let dashed = s.len() == 10 && s.as_bytes()[4] == b'-' && s.as_bytes()[7] == b'-';
+    let digits = if dashed {
+        format!("{}{}{}", &s[..4], &s[5..7], &s[8..])
+    } else {
+        s.to_owned()
+    };
+    let valid = digits.len() == 8
+        && digits.bytes().all(|b| b.is_ascii_digit())
+        && (1..=12).contains(&digits[4..6].parse::<u8>().unwrap_or(0))
+        && (1..=31).contains(&digits[6..].parse::<u8>().unwrap_or(0));
+    if valid {
+        Ok(digits)
+    } else {
+        Err("expected a date as `YYYY-MM-DD`".to_owned())
+    }
+}
+
+fn examples_help() -> String {
+    let mut help = String::from("Examples:\n");
+    for example in EXAMPLES {
+        help.push_str(&format!(
+            "  {}:\n    $ {}\n",
+            example.description, example.command
+        ));
+    }
+    help
+}
+
+pub fn man_page(mut out: impl io::Write) -> Result<(), io::Error> {
+    let man = clap_mangen::Man::new(Args::command());
+    man.render_title(&mut out)?;
+    man.render_name_section(&mut out)?;
+    man.render_synopsis_section(&mut out)?;
+    man.render_description_section(&mut out)?;
+    man.render_options_section(&mut out)?;
+
+    let mut examples = roff::Roff::new();
+    examples.control("SH", ["EXAMPLES"]);
+    for example in EXAMPLES {
+        examples
+            .control("TP", [])
+            .text([roff::roman(example.description)])
+            .control("IP", [])
+            .text([roff::bold(example.command)]);
+    }
+    examples.to_writer(&mut out)?;
+
+    man.render_version_section(&mut out)?;
+    Ok(())
+}
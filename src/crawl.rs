@@ -0,0 +1,82 @@
+use crate::api;
+use serde_json::Value;
+use std::{collections, io};
+
+// Keys marking wikis which are listed by a farm, but not usable.
+const EXCLUDE_KEYS: &[&str] = &["closed", "deleted", "fishbowl", "nonglobal", "private"];
+
+pub fn run(
+    client: &api::Client,
+    seeds: &[url::Url],
+    mut output: impl io::Write,
+) -> Result<(), Error> {
+    let mut domains = collections::BTreeSet::new();
+    for seed in seeds {
+        log::info!("crawl seed {} ...", seed);
+        let index = client.fetch_json(seed).map_err(|e| Error::Seed {
+            url: seed.clone(),
+            source: e,
+        })?;
+        let count = domains.len();
+        collect_domains(&index, &mut domains);
+        log::info!("found {} new wikis", domains.len() - count);
+    }
+
+    for domain in &domains {
+        writeln!(output, "{}", domain)?;
+    }
+    Ok(())
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    #[error("seed {url}: {source}")]
+    Seed {
+        url: url::Url,
+        #[source]
+        source: api::FetchJsonError,
+    },
+}
+
+// Find wikis in any JSON structure, as objects with a `url` string field, such as the `sitematrix`
+// API module of Wikimedia, or the `wikidiscover` API module of Miraheze.
+fn collect_domains(value: &Value, domains: &mut collections::BTreeSet<String>) {
+    match value {
+        Value::Array(values) => {
+            for value in values {
+                collect_domains(value, domains);
+            }
+        }
+        Value::Object(object) => {
+            let excluded = EXCLUDE_KEYS
+                .iter()
+                .any(|key| !matches!(object.get(*key), None | Some(Value::Bool(false))));
+            match object.get("url").and_then(Value::as_str) {
+                Some(url) if !excluded => match url::Url::parse(url) {
+                    Ok(url) if url.host_str().is_some() => {
+                        domains.insert(site(&url));
+                    }
+                    _ => log::debug!("skip invalid wiki url: {:?}", url),
+                },
+                Some(url) => log::debug!("skip excluded wiki: {:?}", url),
+                None => {}
+            }
+            for value in object.values() {
+                collect_domains(value, domains);
+            }
+        }
+        Value::Bool(..) | Value::Null | Value::Number(..) | Value::String(..) => {}
+    }
+}
+
+// The host of a wiki, followed by the path of its URL if it shares the host with other wikis,
+// e.g. `https://example.org/wiki-a/` as `example.org/wiki-a`.
+fn site(url: &url::Url) -> String {
+    let host = url.host_str().unwrap_or_default();
+    match url.path().trim_matches('/') {
+        "" => host.to_owned(),
+        path => format!("{}/{}", host, path),
+    }
+}
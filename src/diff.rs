@@ -0,0 +1,177 @@
+use crate::{api, extract};
+use serde::Serialize;
+use std::{
+    collections, fs,
+    io::{self, Read, Write},
+    path,
+};
+use thiserror::Error;
+
+#[derive(Debug, Serialize)]
+pub struct FieldChanges {
+    pub field: &'static str,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+#[derive(Debug, Error)]
+pub enum SnapshotError {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    #[error("invalid response: {0}")]
+    QueryFromResponse(#[from] api::QueryFromResponseError),
+    #[error("cannot extract configuration data: {0}")]
+    Extract(#[from] extract::Errors),
+}
+
+pub fn load_snapshot(
+    path: &path::Path,
+    options: &extract::Options,
+) -> Result<extract::ConfigurationSource, SnapshotError> {
+    log::info!("load snapshot {:?} ...", path);
+    let raw = read_snapshot(path)?;
+    let query = api::parse_query(&raw)?;
+    Ok(extract::configuration_source(&query, options)?)
+}
+
+// Compress the snapshot at `path` into `path.gz`, and remove it, unless it is already compressed.
+pub fn compact_snapshot(path: &path::Path) -> Result<(), SnapshotError> {
+    let bytes = fs::read(path)?;
+    if bytes.starts_with(GZIP_MAGIC) {
+        log::info!("snapshot {:?} already compressed", path);
+        return Ok(());
+    }
+    let raw =
+        String::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    api::parse_query(&raw)?;
+
+    let mut compressed = path.as_os_str().to_owned();
+    compressed.push(".gz");
+    let compressed = path::PathBuf::from(compressed);
+    let mut encoder = flate2::write::GzEncoder::new(
+        io::BufWriter::new(fs::File::create(&compressed)?),
+        flate2::Compression::best(),
+    );
+    encoder.write_all(raw.as_bytes())?;
+    encoder.finish()?.flush()?;
+    fs::remove_file(path)?;
+    log::info!(
+        "compressed snapshot {:?} into {:?}: {} to {} bytes",
+        path,
+        compressed,
+        raw.len(),
+        fs::metadata(&compressed)?.len()
+    );
+    Ok(())
+}
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+
+// Read a snapshot, decompressing it if it starts with the gzip magic number, whatever its name.
+pub fn read_snapshot(path: &path::Path) -> Result<String, io::Error> {
+    let bytes = fs::read(path)?;
+    if !bytes.starts_with(GZIP_MAGIC) {
+        return String::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e));
+    }
+    let mut raw = String::new();
+    flate2::read::GzDecoder::new(bytes.as_slice()).read_to_string(&mut raw)?;
+    Ok(raw)
+}
+
+pub fn diff(
+    old: &extract::ConfigurationSource,
+    new: &extract::ConfigurationSource,
+) -> Vec<FieldChanges> {
+    fields(old)
+        .into_iter()
+        .zip(fields(new))
+        .filter_map(|((field, old), (_, new))| field_changes(field, &old, &new))
+        .collect()
+}
+
+pub fn field_changes(
+    field: &'static str,
+    old: &collections::BTreeSet<String>,
+    new: &collections::BTreeSet<String>,
+) -> Option<FieldChanges> {
+    let added: Vec<_> = new.difference(old).cloned().collect();
+    let removed: Vec<_> = old.difference(new).cloned().collect();
+    if added.is_empty() && removed.is_empty() {
+        None
+    } else {
+        Some(FieldChanges {
+            field,
+            added,
+            removed,
+        })
+    }
+}
+
+pub fn write_text(mut out: impl io::Write, changes: &[FieldChanges]) -> Result<(), io::Error> {
+    if changes.is_empty() {
+        writeln!(out, "no configuration changes")?;
+    }
+    for changes in changes {
+        writeln!(
+            out,
+            "{}: {} added, {} removed",
+            changes.field,
+            changes.added.len(),
+            changes.removed.len()
+        )?;
+        for added in &changes.added {
+            writeln!(out, "  + {}", added)?;
+        }
+        for removed in &changes.removed {
+            writeln!(out, "  - {}", removed)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn write_json(mut out: impl io::Write, changes: &[FieldChanges]) -> Result<(), io::Error> {
+    serde_json::to_writer_pretty(&mut out, changes)?;
+    writeln!(out)
+}
+
+// The fields of the configuration as sets of strings: link trail characters, and namespaces as
+// `<id>: <name>`.
+pub fn fields(
+    configuration_source: &extract::ConfigurationSource,
+) -> Vec<(&'static str, collections::BTreeSet<String>)> {
+    let extract::ConfigurationSource {
+        case: _,
+        category_namespaces,
+        extension_tags,
+        extension_tag_sources: _,
+        file_namespaces,
+        language: _,
+        legal_title_chars: _,
+        link_trail,
+        magic_words,
+        namespaces,
+        notes: _,
+        protocols,
+        redirect_magic_words,
+        site_name: _,
+    } = configuration_source;
+    vec![
+        ("category_namespaces", category_namespaces.clone()),
+        ("extension_tags", extension_tags.clone()),
+        ("file_namespaces", file_namespaces.clone()),
+        (
+            "link_trail",
+            link_trail.iter().map(char::to_string).collect(),
+        ),
+        ("magic_words", magic_words.clone()),
+        (
+            "namespaces",
+            namespaces
+                .iter()
+                .map(|ns| format!("{}: {}", ns.id, ns.name))
+                .collect(),
+        ),
+        ("protocols", protocols.clone()),
+        ("redirect_magic_words", redirect_magic_words.clone()),
+    ]
+}
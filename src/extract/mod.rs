@@ -1,59 +1,392 @@
+//! Extracting the configuration of `parse_wiki_text` from the siteinfo of a wiki, as a
+//! `ConfigurationSource`.
+
 use crate::api;
-use err_derive::Error;
+use itertools::Itertools;
 use pcre::HirExt;
 use regex_syntax::hir;
-use std::{collections, iter};
+use serde::{Deserialize, Serialize};
+use std::{collections, iter, str, time};
+use thiserror::Error;
 
-mod pcre;
+pub mod pcre;
 
-#[derive(Debug)]
+/// The configuration of a wiki, as needed by `parse_wiki_text`, and the metadata of the wiki for
+/// the generated outputs.
+#[derive(Debug, Serialize)]
 pub struct ConfigurationSource {
+    /// The case of the first letter of titles, e.g. `first-letter`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub case: Option<String>,
+    /// The names of the category namespace, folded.
     pub category_namespaces: collections::BTreeSet<String>,
+    /// The names of the extension tags, lowercase and without angle brackets.
     pub extension_tags: collections::BTreeSet<String>,
+    /// The extension providing each extension tag, if known, and empty unless the wiki reports its
+    /// extensions.
+    #[serde(skip_serializing_if = "collections::BTreeMap::is_empty")]
+    pub extension_tag_sources: collections::BTreeMap<String, Option<String>>,
+    /// The names of the file namespace, folded.
     pub file_namespaces: collections::BTreeSet<String>,
+    /// The language code of the content.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    /// The characters allowed in titles, as a PCRE character class.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub legal_title_chars: Option<String>,
+    /// The characters of the link trail.
+    #[serde(serialize_with = "serialize_link_trail")]
     pub link_trail: collections::BTreeSet<char>,
+    /// The behavior switches, lowercase and without underscores, e.g. `notoc`.
     pub magic_words: collections::BTreeSet<String>,
+    /// All namespaces, by id.
+    pub namespaces: Vec<Namespace>,
+    /// Where the configuration does not come from the wiki itself, for the generated outputs.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub notes: Vec<String>,
+    /// The URL protocols of external links, as set by `ProtocolPolicy`.
     pub protocols: collections::BTreeSet<String>,
+    /// The aliases of `#REDIRECT`, lowercase and without `#`.
     pub redirect_magic_words: collections::BTreeSet<String>,
+    /// The name of the wiki.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub site_name: Option<String>,
+}
+
+/// A namespace, with its aliases and its talk or subject namespace.
+#[derive(Debug, Serialize)]
+pub struct Namespace {
+    /// The id of the namespace, e.g. 6 for files.
+    pub id: i64,
+    /// The localized name, empty for the main namespace.
+    pub name: String,
+    /// The English name, e.g. `File`.
+    pub canonical: Option<String>,
+    /// The additional names.
+    pub aliases: collections::BTreeSet<String>,
+    /// The case of the first letter of titles in the namespace.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub case: Option<String>,
+    /// The subject namespace of a talk namespace.
+    pub subject: Option<i64>,
+    /// The talk namespace of a subject namespace.
+    pub talk: Option<i64>,
+}
+
+/// How to extract a `ConfigurationSource`.
+#[derive(Debug, Default)]
+pub struct Options {
+    /// How to fold the case of namespace names.
+    pub case_folding: CaseFolding,
+    /// Where to keep a name that is both a category and a file namespace name.
+    pub duplicate_namespaces: DuplicateNamespaces,
+    /// Names added to `category_namespaces`, e.g. of namespaces configured by extensions the wiki
+    /// does not report.
+    pub extra_category_namespaces: Vec<String>,
+    /// Names added to `file_namespaces`, like `extra_category_namespaces`.
+    pub extra_file_namespaces: Vec<String>,
+    /// The Unicode normalization of names.
+    pub normalization: Normalization,
+    /// How to extract the URL protocols.
+    pub protocols: ProtocolPolicy,
+    /// Called with the duration of the extraction of each field.
+    pub timing: Option<crate::Timing>,
 }
 
+/// Applied to the namespace names of `category_namespaces` and `file_namespaces`; magic words are
+/// always lowercased.
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CaseFolding {
+    /// Lowercase, e.g. `Datei` to `datei`.
+    #[default]
+    Lowercase,
+    /// Full Unicode case folding, e.g. `ß` to `ss`.
+    Unicode,
+}
+
+/// Which of `category_namespaces` and `file_namespaces` keeps a name found in both.
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DuplicateNamespaces {
+    /// Keep it in both, with a warning.
+    #[default]
+    Both,
+    /// Keep it in `category_namespaces` only.
+    Category,
+    /// Keep it in `file_namespaces` only.
+    File,
+}
+
+/// Applied to namespace names and magic words, as given by the wiki.
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Normalization {
+    /// As given.
+    #[default]
+    None,
+    /// Normalization form C.
+    Nfc,
+    /// Normalization form KC.
+    Nfkc,
+}
+
+/// How to extract the URL protocols.
+#[derive(Debug, Default)]
+pub struct ProtocolPolicy {
+    /// Whether to keep the `:` or `://` suffix.
+    pub suffix: ProtocolSuffix,
+    /// Whether to lowercase the protocols.
+    pub case: ProtocolCase,
+    /// The protocols to keep, case-insensitively, if not all.
+    pub allowlist: Option<collections::BTreeSet<String>>,
+    /// Whether to use the defaults of MediaWiki if the wiki returns none.
+    pub fallback: bool,
+}
+
+// The default `$wgUrlProtocols` of MediaWiki 1.39.
+const DEFAULT_PROTOCOLS: &[&str] = &[
+    "bitcoin:",
+    "ftp://",
+    "ftps://",
+    "geo:",
+    "git://",
+    "gopher://",
+    "http://",
+    "https://",
+    "irc://",
+    "ircs://",
+    "magnet:",
+    "mailto:",
+    "matrix:",
+    "mms://",
+    "news:",
+    "nntp://",
+    "redis://",
+    "sftp://",
+    "sip:",
+    "sips:",
+    "sms:",
+    "ssh://",
+    "svn://",
+    "tel:",
+    "telnet://",
+    "urn:",
+    "worldwind://",
+    "xmpp:",
+    "//",
+];
+
+/// The suffix of a URL protocol.
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProtocolSuffix {
+    /// As given, e.g. `https://`.
+    #[default]
+    Keep,
+    /// Without the suffix, e.g. `https`.
+    Strip,
+}
+
+/// The case of a URL protocol, whose variants are kept only once either way.
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProtocolCase {
+    /// Lowercase.
+    #[default]
+    Lower,
+    /// As given.
+    Preserve,
+}
+
+/// An unknown value of an option.
 #[derive(Debug, Error)]
+#[error("invalid value: {0:?}")]
+pub struct OptionParseError(String);
+
+/// All errors of a single extraction, one per field at most, so that a broken wiki can be fixed in
+/// one go.
+#[derive(Debug, Error)]
+#[error("{}", .0.iter().format("; "))]
+pub struct Errors(Vec<Error>);
+
+/// A failure to extract a field.
+#[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum Error {
-    #[error(display = "{}", _0)]
-    LinkTrail(#[error(source)] LinkTrailError),
-    #[error(display = "{}", _0)]
-    MalformedExtensionTag(#[error(source)] MalformedExtensionTagError),
-    #[error(display = "{}", _0)]
-    NamespaceNotFound(#[error(source)] NamespaceNotFoundError),
+    /// Of `link_trail`.
+    #[error("{0}")]
+    LinkTrail(#[from] LinkTrailError),
+    /// Of `extension_tags`.
+    #[error("{0}")]
+    MalformedExtensionTag(#[from] MalformedExtensionTagError),
+    /// Of `category_namespaces` or `file_namespaces`.
+    #[error("{0}")]
+    NamespaceNotFound(#[from] NamespaceNotFoundError),
 }
 
+/// No namespace has the canonical name looked for.
 #[derive(Debug, Error)]
-#[error(display = "namespace not found: {:?}", _0)]
-pub struct NamespaceNotFoundError(pub String);
+#[error("namespace not found: {0:?}")]
+pub struct NamespaceNotFoundError(String);
 
+/// An extension tag not enclosed in angle brackets.
 #[derive(Debug, Error)]
-#[error(display = "malformed extension tag: {:?}", _0)]
-pub struct MalformedExtensionTagError(pub String);
+#[error("malformed extension tag: {0:?}")]
+pub struct MalformedExtensionTagError(String);
 
+/// A link trail pattern the characters cannot be extracted from.
 #[derive(Debug, Error)]
 pub enum LinkTrailError {
-    #[error(
-        display = "group {} not found in link trail pattern: {:?}",
-        index,
-        pattern
-    )]
-    GroupNotFound { pattern: String, index: u32 },
-    #[error(
-        display = "group {} of invalid structure in link trail pattern: {:?}",
-        index,
-        pattern
-    )]
-    GroupInvalid { pattern: String, index: u32 },
-    #[error(display = "link trail pattern: {}", _0)]
-    Pcre(#[error(source)] pcre::PatternParseError),
+    /// The pattern has no group of the characters.
+    #[error("group {index} not found in link trail pattern: {pattern:?}")]
+    GroupNotFound {
+        /// The link trail pattern.
+        pattern: String,
+        /// The index of the group looked for.
+        index: u32,
+    },
+    /// The group of the characters is not a repeated set of single characters.
+    #[error("group {index} of invalid structure in link trail pattern: {pattern:?}")]
+    GroupInvalid {
+        /// The link trail pattern.
+        pattern: String,
+        /// The index of the group.
+        index: u32,
+    },
+    /// The pattern is not a valid PCRE pattern.
+    #[error("link trail pattern: {0}")]
+    Pcre(#[from] pcre::PatternParseError),
+}
+
+impl str::FromStr for ProtocolSuffix {
+    type Err = OptionParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "keep" => Ok(Self::Keep),
+            "strip" => Ok(Self::Strip),
+            _ => Err(OptionParseError(s.to_owned())),
+        }
+    }
+}
+
+impl str::FromStr for CaseFolding {
+    type Err = OptionParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lowercase" => Ok(Self::Lowercase),
+            "unicode" => Ok(Self::Unicode),
+            _ => Err(OptionParseError(s.to_owned())),
+        }
+    }
+}
+
+impl str::FromStr for DuplicateNamespaces {
+    type Err = OptionParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "both" => Ok(Self::Both),
+            "category" => Ok(Self::Category),
+            "file" => Ok(Self::File),
+            _ => Err(OptionParseError(s.to_owned())),
+        }
+    }
+}
+
+impl str::FromStr for Normalization {
+    type Err = OptionParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Self::None),
+            "nfc" => Ok(Self::Nfc),
+            "nfkc" => Ok(Self::Nfkc),
+            _ => Err(OptionParseError(s.to_owned())),
+        }
+    }
+}
+
+impl CaseFolding {
+    // `Unicode` is full case folding, which unlike lowercasing maps e.g. `ß` to `ss`, and `ς` to
+    // `σ`.
+    fn fold(self, s: &str) -> String {
+        match self {
+            CaseFolding::Lowercase => s.to_lowercase(),
+            CaseFolding::Unicode => caseless::default_case_fold_str(s),
+        }
+    }
+}
+
+impl Normalization {
+    fn normalize(self, s: &str) -> String {
+        use unicode_normalization::UnicodeNormalization;
+        match self {
+            Normalization::None => s.to_owned(),
+            Normalization::Nfc => s.nfc().collect(),
+            Normalization::Nfkc => s.nfkc().collect(),
+        }
+    }
+}
+
+// Normalize before case folding, since compatibility characters may fold differently once
+// decomposed, and after, since case folding may decompose characters.
+fn fold(s: &str, case_folding: CaseFolding, normalization: Normalization) -> String {
+    normalization.normalize(&case_folding.fold(&normalization.normalize(s)))
+}
+
+impl str::FromStr for ProtocolCase {
+    type Err = OptionParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lower" => Ok(Self::Lower),
+            "preserve" => Ok(Self::Preserve),
+            _ => Err(OptionParseError(s.to_owned())),
+        }
+    }
+}
+
+impl Errors {
+    /// The errors, in the order of the fields.
+    pub fn iter(&self) -> impl Iterator<Item = &Error> {
+        self.0.iter()
+    }
+}
+
+impl NamespaceNotFoundError {
+    /// The canonical name of the namespace.
+    pub fn canonical(&self) -> &str {
+        &self.0
+    }
+}
+
+impl MalformedExtensionTagError {
+    /// The extension tag as given.
+    pub fn tag(&self) -> &str {
+        &self.0
+    }
 }
 
 impl LinkTrailError {
+    /// The link trail pattern.
+    pub fn pattern(&self) -> &str {
+        match self {
+            Self::GroupNotFound { pattern, .. } | Self::GroupInvalid { pattern, .. } => pattern,
+            Self::Pcre(e) => e.pattern(),
+        }
+    }
+
+    /// The index of the group, unless the pattern is invalid.
+    pub fn group_index(&self) -> Option<u32> {
+        match self {
+            Self::GroupNotFound { index, .. } | Self::GroupInvalid { index, .. } => Some(*index),
+            Self::Pcre(..) => None,
+        }
+    }
+
     fn group_not_found(pattern: &str, index: u32) -> Self {
         Self::GroupNotFound {
             pattern: pattern.to_owned(),
@@ -69,59 +402,159 @@ impl LinkTrailError {
     }
 }
 
-pub fn configuration_source(query: &api::response::Query) -> Result<ConfigurationSource, Error> {
-    let category_namespaces = namespaces(query, "Category")?;
+// The result of `f`, whose duration is passed to `options.timing`.
+fn timed<T>(options: &Options, name: &'static str, f: impl FnOnce() -> T) -> T {
+    let timing = match options.timing {
+        Some(timing) => timing,
+        None => return f(),
+    };
+    let start = time::Instant::now();
+    let result = f();
+    timing(name, start.elapsed());
+    result
+}
+
+/// Extract the whole configuration, collecting the errors of all fields.
+pub fn configuration_source(
+    query: &api::response::Query,
+    options: &Options,
+) -> Result<ConfigurationSource, Errors> {
+    let mut errors = Vec::new();
+    let mut notes = Vec::new();
+
+    let mut category_namespaces = collect(
+        &mut errors,
+        timed(options, "category_namespaces", || {
+            namespaces(query, "Category", options)
+        }),
+    );
+    let mut file_namespaces = collect(
+        &mut errors,
+        timed(options, "file_namespaces", || {
+            namespaces(query, "File", options)
+        }),
+    );
+    for (names, extra) in [
+        (&mut category_namespaces, &options.extra_category_namespaces),
+        (&mut file_namespaces, &options.extra_file_namespaces),
+    ] {
+        names.extend(
+            extra
+                .iter()
+                .map(|name| fold(name, options.case_folding, options.normalization)),
+        );
+    }
+    deduplicate_namespaces(
+        &mut category_namespaces,
+        &mut file_namespaces,
+        options.duplicate_namespaces,
+    );
     log::debug!(
         "category namespaces: ({}) {:?}",
         category_namespaces.len(),
         category_namespaces
     );
-    let file_namespaces = namespaces(query, "File")?;
     log::debug!(
         "file namespaces: ({}) {:?}",
         file_namespaces.len(),
         file_namespaces
     );
 
-    let extension_tags = extension_tags(query)?;
+    let namespaces = timed(options, "namespaces", || {
+        namespace_table(query, options.normalization)
+    });
+    log::debug!("namespaces: ({})", namespaces.len());
+
+    let extension_tags = collect(
+        &mut errors,
+        timed(options, "extension_tags", || extension_tags(query)),
+    );
     log::debug!(
         "extension tags: ({}) {:?}",
         extension_tags.len(),
         extension_tags
     );
-    let protocols = protocols(query);
+    let extension_tag_sources = timed(options, "extension_tag_sources", || {
+        extension_tag_sources(query, &extension_tags)
+    });
+    log::debug!(
+        "extension tag sources: ({}) {:?}",
+        extension_tag_sources.len(),
+        extension_tag_sources
+    );
+    let protocols = timed(options, "protocols", || {
+        protocols(query, &options.protocols, &mut notes)
+    });
     log::debug!("protocols: ({}) {:?}", protocols.len(), protocols);
 
-    let link_trail = link_trail(query)?;
+    let link_trail = collect(
+        &mut errors,
+        timed(options, "link_trail", || link_trail(query)),
+    );
     if link_trail.len() <= (1 << 7) {
         log::debug!("link trail: ({}) {:?}", link_trail.len(), link_trail);
     } else {
         log::debug!("link trail: ({}) {{...}}", link_trail.len());
     }
 
-    let magic_words = magic_words(query);
+    let magic_words = timed(options, "magic_words", || {
+        magic_words(query, options.normalization)
+    });
     log::debug!("magic words: ({}) {:?}", magic_words.len(), magic_words);
-    let redirect_magic_words = magic_words_redirect(query);
+    let redirect_magic_words = timed(options, "redirect_magic_words", || {
+        magic_words_redirect(query, options.normalization)
+    });
     log::debug!(
         "redirect magic words: ({}) {:?}",
         redirect_magic_words.len(),
         redirect_magic_words
     );
 
+    if !errors.is_empty() {
+        return Err(Errors(errors));
+    }
     Ok(ConfigurationSource {
+        case: query.general.case.clone(),
         category_namespaces,
         extension_tags,
+        extension_tag_sources,
         file_namespaces,
+        language: query.general.lang.clone(),
+        legal_title_chars: query.general.legaltitlechars.clone(),
         link_trail,
         magic_words,
+        namespaces,
+        notes,
         protocols,
         redirect_magic_words,
+        site_name: query.general.sitename.clone(),
     })
 }
 
-fn namespaces(
+// Record the error of a field and continue with an empty field, so that the remaining fields are
+// still checked.
+fn collect<T: Default, E: Into<Error>>(errors: &mut Vec<Error>, result: Result<T, E>) -> T {
+    result.unwrap_or_else(|e| {
+        errors.push(e.into());
+        T::default()
+    })
+}
+
+fn serialize_link_trail<S: serde::Serializer>(
+    link_trail: &collections::BTreeSet<char>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(&link_trail.iter().collect::<String>())
+}
+
+// The steps of `configuration_source`, each usable alone on a query however it was obtained, e.g.
+// parsed by `api::parse_query` from a saved response.
+
+/// The names of the namespace of the canonical name `canonical`, with its aliases, folded.
+pub fn namespaces(
     query: &api::response::Query,
     canonical: &str,
+    options: &Options,
 ) -> Result<collections::BTreeSet<String>, NamespaceNotFoundError> {
     let namespace = query
         .namespaces
@@ -136,11 +569,87 @@ fn namespaces(
         .map(|na| na.alias.as_str())
         .chain(iter::once(canonical))
         .chain(iter::once(namespace.name.as_str()))
-        .map(str::to_lowercase);
+        .map(|name| fold(name, options.case_folding, options.normalization));
     Ok(names.collect())
 }
 
-fn extension_tags(
+/// Names in both sets make the namespace of a link ambiguous to parsers.
+pub fn deduplicate_namespaces(
+    category_namespaces: &mut collections::BTreeSet<String>,
+    file_namespaces: &mut collections::BTreeSet<String>,
+    policy: DuplicateNamespaces,
+) {
+    let duplicates: Vec<_> = category_namespaces
+        .intersection(file_namespaces)
+        .cloned()
+        .collect();
+    for name in duplicates {
+        match policy {
+            DuplicateNamespaces::Both => {
+                log::warn!(
+                    "namespace name {:?} is both a category and a file namespace",
+                    name
+                );
+            }
+            DuplicateNamespaces::Category => {
+                log::warn!(
+                    "namespace name {:?} is both a category and a file namespace, kept as a \
+                        category namespace only",
+                    name
+                );
+                file_namespaces.remove(&name);
+            }
+            DuplicateNamespaces::File => {
+                log::warn!(
+                    "namespace name {:?} is both a category and a file namespace, kept as a file \
+                        namespace only",
+                    name
+                );
+                category_namespaces.remove(&name);
+            }
+        }
+    }
+}
+
+/// All namespaces, with their aliases, sorted by id.
+pub fn namespace_table(
+    query: &api::response::Query,
+    normalization: Normalization,
+) -> Vec<Namespace> {
+    let ids: collections::BTreeSet<_> = query.namespaces.values().map(|ns| ns.id).collect();
+    let mut namespaces: Vec<_> = query
+        .namespaces
+        .values()
+        .map(|ns| {
+            // Following MediaWiki conventions, talk namespaces have odd ids, and follow their
+            // subject namespace.  Virtual namespaces have negative ids, and no talk namespace.
+            let (subject, talk) = match ns.id {
+                id if id < 0 => (None, None),
+                id if id % 2 == 0 => (None, Some(id + 1)),
+                id => (Some(id - 1), None),
+            };
+            Namespace {
+                id: ns.id,
+                name: normalization.normalize(&ns.name),
+                canonical: ns.canonical.clone(),
+                aliases: query
+                    .namespacealiases
+                    .iter()
+                    .filter(|na| na.id == ns.id)
+                    .map(|na| normalization.normalize(&na.alias))
+                    .collect(),
+                case: ns.case.clone(),
+                subject: subject.filter(|id| ids.contains(id)),
+                talk: talk.filter(|id| ids.contains(id)),
+            }
+        })
+        .collect();
+    namespaces.sort_by_key(|ns| ns.id);
+    namespaces
+}
+
+/// The names of the extension tags, lowercase and without angle brackets.
+pub fn extension_tags(
     query: &api::response::Query,
 ) -> Result<collections::BTreeSet<String>, MalformedExtensionTagError> {
     query
@@ -156,34 +665,139 @@ fn extension_tags(
         .collect()
 }
 
-fn protocols(query: &api::response::Query) -> collections::BTreeSet<String> {
-    query.protocols.iter().map(|p| p.0.to_lowercase()).collect()
+/// Extension tags of MediaWiki itself, and of well-known extensions, by extension name as reported
+/// by the `extensions` siteinfo property.
+pub const EXTENSION_TAG_SOURCES: &[(&str, &str)] = &[
+    ("categorytree", "CategoryTree"),
+    ("ce", "Math"),
+    ("charinsert", "CharInsert"),
+    ("chem", "Math"),
+    ("gallery", CORE_EXTENSION),
+    ("graph", "Graph"),
+    ("hiero", "wikihiero"),
+    ("imagemap", "ImageMap"),
+    ("indicator", CORE_EXTENSION),
+    ("inputbox", "InputBox"),
+    ("langconvert", CORE_EXTENSION),
+    ("mapframe", "Kartographer"),
+    ("maplink", "Kartographer"),
+    ("math", "Math"),
+    ("nowiki", CORE_EXTENSION),
+    ("pages", "ProofreadPage"),
+    ("poem", "Poem"),
+    ("pre", CORE_EXTENSION),
+    ("ref", "Cite"),
+    ("references", "Cite"),
+    ("score", "Score"),
+    ("section", "Labeled Section Transclusion"),
+    ("source", "SyntaxHighlight"),
+    ("syntaxhighlight", "SyntaxHighlight"),
+    ("templatedata", "TemplateData"),
+    ("templatestyles", "TemplateStyles"),
+    ("timeline", "EasyTimeline"),
+];
+/// The source of the extension tags of MediaWiki itself.
+pub const CORE_EXTENSION: &str = "MediaWiki";
+
+/// The installed extension providing each of `extension_tags`, if known, or nothing if the wiki
+/// does not report its extensions.
+pub fn extension_tag_sources(
+    query: &api::response::Query,
+    extension_tags: &collections::BTreeSet<String>,
+) -> collections::BTreeMap<String, Option<String>> {
+    if query.extensions.is_empty() {
+        return Default::default();
+    }
+    extension_tags
+        .iter()
+        .map(|tag| {
+            let source = EXTENSION_TAG_SOURCES
+                .iter()
+                .find(|(t, _)| t == tag)
+                .map(|(_, name)| *name)
+                .filter(|name| {
+                    *name == CORE_EXTENSION || query.extensions.iter().any(|e| e.name == *name)
+                });
+            (tag.clone(), source.map(str::to_owned))
+        })
+        .collect()
+}
+
+/// The URL protocols, as set by `policy`, noting in `notes` if the defaults are used instead.
+pub fn protocols(
+    query: &api::response::Query,
+    policy: &ProtocolPolicy,
+    notes: &mut Vec<String>,
+) -> collections::BTreeSet<String> {
+    let mut original: Vec<_> = query.protocols.iter().map(|p| p.0.as_str()).collect();
+    if original.is_empty() && policy.fallback {
+        log::warn!("no protocols returned by the wiki, using the defaults of MediaWiki instead");
+        notes.push(
+            "protocols: none returned by the wiki, the defaults of MediaWiki 1.39 used instead"
+                .to_owned(),
+        );
+        original = DEFAULT_PROTOCOLS.to_vec();
+    }
+    let mut seen = collections::BTreeSet::new();
+    let mut protocols = collections::BTreeSet::new();
+    for mut protocol in original {
+        if let ProtocolSuffix::Strip = policy.suffix {
+            protocol = protocol
+                .strip_suffix("://")
+                .or_else(|| protocol.strip_suffix(':'))
+                .unwrap_or(protocol);
+        }
+        let lower = protocol.to_lowercase();
+        if let Some(allowlist) = &policy.allowlist {
+            if !allowlist.iter().any(|a| a.to_lowercase() == lower) {
+                log::debug!("protocol not in allowlist: {:?}", protocol);
+                continue;
+            }
+        }
+        if !seen.insert(lower.clone()) {
+            log::debug!("protocol is a case variant of another: {:?}", protocol);
+            continue;
+        }
+        protocols.insert(match policy.case {
+            ProtocolCase::Lower => lower,
+            ProtocolCase::Preserve => protocol.to_owned(),
+        });
+    }
+    protocols
 }
 
-fn link_trail(query: &api::response::Query) -> Result<collections::BTreeSet<char>, LinkTrailError> {
-    use hir::HirKind::*;
+/// The characters of the repeated group of the link trail pattern: the group named `trail` if any,
+/// e.g. `/^(?<trail>[a-z]+)(.*)$/sD`, and else the first group.
+pub fn link_trail(
+    query: &api::response::Query,
+) -> Result<collections::BTreeSet<char>, LinkTrailError> {
+    use pcre::HirNode::*;
 
     let original = &query.general.linktrail;
     let pattern: pcre::Pattern = original.parse()?;
     log::debug!("pattern = {:?}", pattern);
 
+    const GROUP_NAME: &str = "trail";
     const GROUP_INDEX: u32 = 1;
     let group = pattern
         .hir
-        .find_group_index(GROUP_INDEX)
+        .find_group_by_name(GROUP_NAME)
+        .or_else(|| pattern.hir.find_group_index(GROUP_INDEX))
         .ok_or_else(|| LinkTrailError::group_not_found(original, GROUP_INDEX))?;
-    let repeated = match group.hir.kind() {
+    log::trace!("group index = {}, name = {:?}", group.index, group.name);
+    let repeated = match group.hir.node() {
         Empty => Ok(None),
-        Repetition(repetition) => Ok(Some(&repetition.hir)),
-        Alternation(..) | Anchor(..) | Class(..) | Concat(..) | Group(..) | Literal(..)
-        | WordBoundary(..) => Err(LinkTrailError::group_invalid(original, GROUP_INDEX)),
+        Repetition(hir) => Ok(Some(hir)),
+        Alternation(..) | Capture(..) | Class(..) | Concat(..) | Literal(..) | Look => {
+            Err(LinkTrailError::group_invalid(original, group.index))
+        }
     }?;
-    log::debug!("repeated = {:?}", repeated.map(|r| pcre::HirDebugAlt(r)));
+    log::debug!("repeated = {:?}", repeated.map(pcre::HirDebugAlt));
 
     let mut characters = Default::default();
     if let Some(repeated) = repeated {
         link_trail_characters(repeated, &mut characters)
-            .map_err(|_| LinkTrailError::group_invalid(original, GROUP_INDEX))?;
+            .map_err(|_| LinkTrailError::group_invalid(original, group.index))?;
     }
     Ok(characters)
 }
@@ -192,64 +806,78 @@ fn link_trail_characters(
     hir: &hir::Hir,
     characters: &mut collections::BTreeSet<char>,
 ) -> Result<(), ()> {
-    use hir::HirKind::*;
-    use hir::{Class, Literal};
-    match hir.kind() {
+    use pcre::HirNode::*;
+    match hir.node() {
         Alternation(hirs) => {
             for hir in hirs {
                 link_trail_characters(hir, characters)?;
             }
             Ok(())
         }
-        Class(class) => {
-            match class {
-                Class::Bytes(bytes) => {
-                    for range in bytes.iter() {
-                        for b in range.start()..=range.end() {
-                            debug_assert!(b.is_ascii());
-                            characters.insert(b.into());
-                        }
-                    }
-                }
-                Class::Unicode(unicode) => {
-                    for range in unicode.iter() {
-                        for c in range.start()..=range.end() {
-                            characters.insert(c);
-                        }
-                    }
-                }
-            }
+        Capture(capture) => link_trail_characters(capture.hir, characters),
+        Class(ranges) => {
+            characters.extend(ranges.into_iter().flatten());
             Ok(())
         }
-        Group(group) => link_trail_characters(&group.hir, characters),
         Literal(literal) => {
-            let c = match literal {
-                Literal::Byte(..) => unreachable!(),
-                Literal::Unicode(c) => *c,
-            };
-            characters.insert(c);
-            Ok(())
+            let mut chars = literal.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => {
+                    characters.insert(c);
+                    Ok(())
+                }
+                _ => Err(()),
+            }
         }
-        Anchor(..) | Concat(..) | Empty | Repetition(..) | WordBoundary(..) => Err(()),
+        Concat(..) | Empty | Look | Repetition(..) => Err(()),
     }
 }
 
-fn magic_words(query: &api::response::Query) -> collections::BTreeSet<String> {
-    query
+/// The behavior switches, lowercase and without underscores, e.g. `notoc` for `__NOTOC__`.
+pub fn magic_words(
+    query: &api::response::Query,
+    normalization: Normalization,
+) -> collections::BTreeSet<String> {
+    for mw in query
         .magicwords
         .iter()
-        .flat_map(|mw| {
-            mw.aliases
-                .iter()
-                .map(AsRef::as_ref)
-                .chain(iter::once(mw.name.as_str()))
-        })
-        .filter_map(|s| s.strip_prefix("__").and_then(|s| s.strip_suffix("__")))
-        .map(str::to_lowercase)
-        .collect()
+        .filter(|mw| mw.case_sensitive == Some(true))
+    {
+        log::trace!(
+            "case-sensitive magic word folded to lowercase: {:?}",
+            mw.name
+        );
+    }
+
+    let mut sources = collections::BTreeMap::<_, collections::BTreeSet<_>>::new();
+    for mw in &query.magicwords {
+        let words = mw
+            .aliases
+            .iter()
+            .map(AsRef::as_ref)
+            .chain(iter::once(mw.name.as_str()))
+            .filter_map(|s| s.strip_prefix("__").and_then(|s| s.strip_suffix("__")))
+            .map(|s| fold(s, CaseFolding::Lowercase, normalization));
+        for word in words {
+            sources.entry(word).or_default().insert(mw.name.as_str());
+        }
+    }
+
+    for (word, names) in sources.iter().filter(|(_, names)| names.len() > 1) {
+        log::warn!(
+            "magic word {:?} is an alias of several magic words: {:?}",
+            word,
+            names
+        );
+    }
+    sources.into_keys().collect()
 }
 
-fn magic_words_redirect(query: &api::response::Query) -> collections::BTreeSet<String> {
+/// The aliases of `#REDIRECT`, lowercase and without `#`.
+pub fn magic_words_redirect(
+    query: &api::response::Query,
+    normalization: Normalization,
+) -> collections::BTreeSet<String> {
     const NAME: &str = "redirect";
     const PREFIX: &str = "#";
     query
@@ -259,6 +887,6 @@ fn magic_words_redirect(query: &api::response::Query) -> collections::BTreeSet<S
         .flat_map(|mw| mw.aliases.iter())
         .map(|s| s.strip_prefix(PREFIX).unwrap_or(s))
         .chain(iter::once(NAME))
-        .map(str::to_lowercase)
+        .map(|s| fold(s, CaseFolding::Lowercase, normalization))
         .collect()
 }
@@ -1,63 +1,131 @@
-use err_derive::Error;
+//! PHP PCRE patterns, as found in the MediaWiki configuration, e.g. `/^([a-z]+)(.*)$/sD`: the
+//! delimiters and modifiers are parsed, and the regex is translated to a `regex_syntax` HIR with
+//! the modifiers that have an equivalent applied.
+
 use regex_syntax::{ast, hir};
-use std::fmt;
+use std::{borrow, fmt, ops};
+use thiserror::Error;
 
 mod private {
     pub trait Sealed {}
 }
 
+/// A parsed PHP PCRE pattern.
 pub struct Pattern {
+    /// The regex, with the modifiers that have an equivalent applied.
     pub hir: hir::Hir,
     modifiers: Modifiers,
 }
 
-#[derive(Debug, Default)]
-struct Modifiers {
+/// The modifiers after the closing delimiter, each a single letter.
+#[derive(Clone, Debug, Default)]
+pub struct Modifiers {
     // Passed to parser.
-    extended: bool,
+    /// `x`: whitespace and `#` comments in the regex are ignored.
+    pub extended: bool,
 
     // Passed to translator.
-    caseless: bool,
-    dotall: bool,
-    multiline: bool,
-    ungreedy: bool,
+    /// `i`: letters match case-insensitively.
+    pub caseless: bool,
+    /// `s`: `.` also matches newlines.
+    pub dotall: bool,
+    /// `m`: `^` and `$` also match at newlines.
+    pub multiline: bool,
+    /// `U`: quantifiers are lazy unless followed by `?`.
+    pub ungreedy: bool,
 
     // Ignored: not relevant.
-    anchored: bool,
-    dollar_endonly: bool,
-    extra: bool,
-    utf8: bool,
+    /// `A`: the regex is anchored at the start.
+    pub anchored: bool,
+    /// `D`: `$` does not match before a final newline.
+    pub dollar_endonly: bool,
+    /// `X`: unknown escapes are errors.
+    pub extra: bool,
+    /// `u`: the pattern and subject are UTF-8.
+    pub utf8: bool,
 
     // Ignored: no effect.
-    speedup: bool,
+    /// `S`: the pattern is studied before matching.
+    pub speedup: bool,
 
     // Error: not supported.
-    info_jchanged: bool,
+    /// `J`: group names may be duplicated.
+    pub info_jchanged: bool,
 }
 
+/// Debugs a HIR as the regex it matches, more readable than its structure.
 pub struct HirDebugAlt<'h>(pub &'h hir::Hir);
 
+/// A pattern that cannot be parsed.
 #[derive(Debug, Error)]
-#[error(display = "{}: {:?}", kind, pattern)]
+#[error("{kind}: {pattern:?}")]
 pub struct PatternParseError {
-    pub pattern: String,
-    pub kind: PatternParseErrorKind,
+    pattern: String,
+    kind: PatternParseErrorKind,
 }
 
-#[derive(Debug)]
+/// Why a pattern cannot be parsed.
+#[derive(Debug, Error)]
 pub enum PatternParseErrorKind {
+    /// A modifier with no equivalent.
+    #[error("unsupported PHP PCRE modifier: {0:?}")]
     ModifierUnsupported(char),
+    /// An unknown modifier.
+    #[error("{0}")]
     Modifiers(ModifiersParseError),
+    /// Missing or unmatched delimiters.
+    #[error("invalid PHP PCRE pattern")]
     Pattern,
-    Regex(regex_syntax::Error),
+    /// A regex `regex_syntax` cannot parse or translate.
+    #[error("invalid PHP PCRE regex: {0}")]
+    Regex(Box<regex_syntax::Error>),
 }
 
+/// An unknown modifier.
 #[derive(Debug, Error)]
-#[error(display = "unrecognized PHP PCRE modifier: {:?}", _0)]
+#[error("unrecognized PHP PCRE modifier: {0:?}")]
 pub struct ModifiersParseError(char);
 
+/// Version-independent view of a `regex_syntax` HIR node, so that callers don't depend on the
+/// shape of `hir::HirKind` directly.
+pub enum HirNode<'h> {
+    /// Any of the alternatives, e.g. `a|b`.
+    Alternation(&'h [hir::Hir]),
+    /// A group.
+    Capture(HirCapture<'h>),
+    /// A set of characters, as ranges, e.g. `[a-z]`.
+    Class(Vec<ops::RangeInclusive<char>>),
+    /// The concatenation, e.g. `ab*`.
+    Concat(&'h [hir::Hir]),
+    /// Matches the empty string.
+    Empty,
+    /// A literal string.
+    Literal(borrow::Cow<'h, str>),
+    /// An assertion, e.g. `^` or `\b`.
+    Look,
+    /// The repetition of the node, e.g. `a*`, whatever its bounds.
+    Repetition(&'h hir::Hir),
+}
+
+/// A group of a HIR.
+pub struct HirCapture<'h> {
+    /// The index, counting from 1.
+    pub index: u32,
+    /// The name, if a named group.
+    pub name: Option<&'h str>,
+    /// The regex of the group.
+    pub hir: &'h hir::Hir,
+}
+
+/// A view of `regex_syntax` HIRs independent of the version of `regex_syntax`.
 pub trait HirExt: private::Sealed {
-    fn find_group_index(&self, index: u32) -> Option<&hir::Group>;
+    /// The kind of the node.
+    fn node(&self) -> HirNode<'_>;
+    /// The group of index `index`, searched depth first.
+    fn find_group_index(&self, index: u32) -> Option<HirCapture<'_>>;
+    /// Named groups, e.g. `(?<trail>...)` or `(?P<trail>...)`, are also numbered, so they can be
+    /// found either way.
+    fn find_group_by_name(&self, name: &str) -> Option<HirCapture<'_>>;
 }
 
 impl fmt::Debug for Pattern {
@@ -100,7 +168,7 @@ impl std::str::FromStr for Pattern {
                 (end, index + 1)
             }
             None => {
-                return Err(PatternParseError::pattern(s));
+                return Err(PatternParseError::pattern_invalid(s));
             }
         };
 
@@ -111,7 +179,9 @@ impl std::str::FromStr for Pattern {
         // character boundaries are properly aligned.  (Also checked in the debug assertion.)
         debug_assert!(std::str::from_utf8(modifiers).is_ok());
         let modifiers = unsafe { std::str::from_utf8_unchecked(modifiers) };
-        let regex = rsplit.next().ok_or_else(|| PatternParseError::pattern(s))?;
+        let regex = rsplit
+            .next()
+            .ok_or_else(|| PatternParseError::pattern_invalid(s))?;
 
         // UNSAFE: See above.
         debug_assert!(std::str::from_utf8(regex).is_ok());
@@ -147,6 +217,13 @@ impl std::str::FromStr for Pattern {
     }
 }
 
+impl Pattern {
+    /// The modifiers, including those already applied to `hir`.
+    pub fn modifiers(&self) -> &Modifiers {
+        &self.modifiers
+    }
+}
+
 impl std::str::FromStr for Modifiers {
     type Err = ModifiersParseError;
 
@@ -182,26 +259,66 @@ impl<'h> fmt::Debug for HirDebugAlt<'h> {
 }
 
 impl HirExt for hir::Hir {
-    fn find_group_index(&self, index: u32) -> Option<&hir::Group> {
-        use hir::GroupKind::*;
+    fn node(&self) -> HirNode<'_> {
         use hir::HirKind::*;
         match self.kind() {
-            Concat(hirs) | Alternation(hirs) => {
-                hirs.iter().filter_map(|h| h.find_group_index(index)).next()
+            Alternation(hirs) => HirNode::Alternation(hirs),
+            Capture(capture) => HirNode::Capture(HirCapture {
+                index: capture.index,
+                name: capture.name.as_deref(),
+                hir: &capture.sub,
+            }),
+            Class(hir::Class::Bytes(bytes)) => HirNode::Class(
+                bytes
+                    .iter()
+                    .map(|range| range.start().into()..=range.end().into())
+                    .collect(),
+            ),
+            Class(hir::Class::Unicode(unicode)) => HirNode::Class(
+                unicode
+                    .iter()
+                    .map(|range| range.start()..=range.end())
+                    .collect(),
+            ),
+            Concat(hirs) => HirNode::Concat(hirs),
+            Empty => HirNode::Empty,
+            Literal(literal) => HirNode::Literal(String::from_utf8_lossy(&literal.0)),
+            Look(..) => HirNode::Look,
+            Repetition(repetition) => HirNode::Repetition(&repetition.sub),
+        }
+    }
+
+    fn find_group_index(&self, index: u32) -> Option<HirCapture<'_>> {
+        match self.node() {
+            HirNode::Concat(hirs) | HirNode::Alternation(hirs) => {
+                hirs.iter().find_map(|h| h.find_group_index(index))
             }
-            Group(group) => {
-                let found = match group.kind {
-                    CaptureIndex(i) | CaptureName { index: i, name: _ } => i == index,
-                    NonCapturing => false,
-                };
-                if found {
-                    Some(group)
+            HirNode::Capture(capture) => {
+                if capture.index == index {
+                    Some(capture)
+                } else {
+                    capture.hir.find_group_index(index)
+                }
+            }
+            HirNode::Repetition(hir) => hir.find_group_index(index),
+            HirNode::Class(..) | HirNode::Empty | HirNode::Literal(..) | HirNode::Look => None,
+        }
+    }
+
+    fn find_group_by_name(&self, name: &str) -> Option<HirCapture<'_>> {
+        match self.node() {
+            HirNode::Concat(hirs) | HirNode::Alternation(hirs) => {
+                hirs.iter().find_map(|h| h.find_group_by_name(name))
+            }
+            HirNode::Capture(capture) => {
+                if capture.name == Some(name) {
+                    Some(capture)
                 } else {
-                    group.hir.find_group_index(index)
+                    capture.hir.find_group_by_name(name)
                 }
             }
-            Repetition(repetition) => repetition.hir.find_group_index(index),
-            Anchor(..) | Class(..) | Empty | Literal(..) | WordBoundary(..) => None,
+            HirNode::Repetition(hir) => hir.find_group_by_name(name),
+            HirNode::Class(..) | HirNode::Empty | HirNode::Literal(..) | HirNode::Look => None,
         }
     }
 }
@@ -209,6 +326,16 @@ impl HirExt for hir::Hir {
 impl private::Sealed for hir::Hir {}
 
 impl PatternParseError {
+    /// The pattern as given.
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Why the pattern cannot be parsed.
+    pub fn kind(&self) -> &PatternParseErrorKind {
+        &self.kind
+    }
+
     fn modifier_unsupported(pattern: &str, c: char) -> Self {
         Self {
             pattern: pattern.to_owned(),
@@ -223,7 +350,7 @@ impl PatternParseError {
         }
     }
 
-    fn pattern(pattern: &str) -> Self {
+    fn pattern_invalid(pattern: &str) -> Self {
         Self {
             pattern: pattern.to_owned(),
             kind: PatternParseErrorKind::Pattern,
@@ -233,19 +360,26 @@ impl PatternParseError {
     fn regex(pattern: &str, e: regex_syntax::Error) -> Self {
         Self {
             pattern: pattern.to_owned(),
-            kind: PatternParseErrorKind::Regex(e),
+            kind: PatternParseErrorKind::Regex(Box::new(e)),
         }
     }
 }
 
-impl fmt::Display for PatternParseErrorKind {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+impl ModifiersParseError {
+    /// The unknown modifier.
+    pub fn modifier(&self) -> char {
+        self.0
+    }
+}
+
+impl PatternParseErrorKind {
+    /// The modifier at fault, if any.
+    pub fn modifier(&self) -> Option<char> {
         use PatternParseErrorKind::*;
         match self {
-            ModifierUnsupported(c) => write!(f, "unsupported PHP PCRE modifier: {:?}", c),
-            Modifiers(e) => write!(f, "{}", e),
-            Pattern => write!(f, "invalid PHP PCRE pattern"),
-            Regex(e) => write!(f, "invalid PHP PCRE regex: {}", e),
+            ModifierUnsupported(c) => Some(*c),
+            Modifiers(e) => Some(e.modifier()),
+            Pattern | Regex(..) => None,
         }
     }
 }
@@ -1,33 +1,1679 @@
+//! Generating the configuration of `parse_wiki_text` as Rust code, and the other output formats of
+//! an extracted `ConfigurationSource`.
+
 use crate::extract;
-use std::io;
+use itertools::Itertools;
+use proc_macro2::{TokenStream, TokenTree};
+use std::{collections, io, iter, path};
+use thiserror::Error;
+
+/// How to generate Rust code.
+#[derive(Debug, Default)]
+pub struct Options {
+    /// Emit the arrays as constants, checked at compile time to be strictly sorted.
+    pub const_assertions: bool,
+    /// Emit a complete constant item of this name instead of the bare expression.
+    pub const_name: Option<String>,
+    /// What to emit around the configuration.
+    pub emit: Emit,
+    /// Escape the non-ASCII characters of literals and comments as `\u{...}`.
+    pub escape_unicode: bool,
+    /// How to emit the link trail.
+    pub link_trail: LinkTrailEncoding,
+    /// Wrap arrays wider than this at their elements, so that diffs show changed elements.
+    pub max_width: Option<usize>,
+    /// Also emit a function resolving namespace names and aliases to namespace ids.
+    pub namespace_ids: bool,
+    /// The names of the emitted items.
+    pub naming: Naming,
+    /// Fail if the code would require `std` or `alloc`.
+    pub no_std: bool,
+    /// Load the link trail, magic words and protocols from the data files of `split_data`,
+    /// included from this directory.
+    pub split: Option<path::PathBuf>,
+    /// The parser crate.
+    pub target: Target,
+    /// The visibility of the emitted items.
+    pub visibility: Visibility,
+}
+
+/// The parser crate whose `ConfigurationSource` is generated.
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Target {
+    /// `parse_wiki_text`.
+    #[default]
+    ParseWikiText,
+    /// `parse_wiki_text_2`, its maintained fork.
+    #[serde(rename = "parse-wiki-text-2")]
+    ParseWikiText2,
+}
+
+/// What to emit around the configuration: nothing, or a function returning the `Configuration`
+/// built from it on first use.
+#[derive(Clone, Copy, Debug, Default, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Emit {
+    /// The bare `ConfigurationSource` expression.
+    #[default]
+    Expression,
+    /// A constant, and a function returning the `Configuration` built from it.
+    Constructor,
+    /// Only the values added to and removed from `DEFAULTS`, with a function merging them into
+    /// given defaults.
+    Delta,
+}
+
+/// How to emit the link trail, whose characters may number tens of thousands.
+#[derive(Clone, Copy, Debug, Default, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LinkTrailEncoding {
+    /// A string literal.
+    #[default]
+    String,
+    /// Ranges of characters, from which the string is built at compile time.
+    Ranges,
+}
+
+/// The default configuration of `parse_wiki_text` (`Configuration::default`), kept by its fork.
+pub const DEFAULTS: &[(&str, &[&str])] = &[
+    ("category_namespaces", &["category"]),
+    (
+        "extension_tags",
+        &[
+            "categorytree",
+            "ce",
+            "charinsert",
+            "chem",
+            "gallery",
+            "graph",
+            "hiero",
+            "imagemap",
+            "indicator",
+            "inputbox",
+            "mapframe",
+            "maplink",
+            "math",
+            "nowiki",
+            "poem",
+            "pre",
+            "ref",
+            "references",
+            "score",
+            "section",
+            "source",
+            "syntaxhighlight",
+            "templatedata",
+            "timeline",
+        ],
+    ),
+    ("file_namespaces", &["file", "image"]),
+    (
+        "magic_words",
+        &[
+            "disambig",
+            "expected_unconnected_page",
+            "expectunusedcategory",
+            "forcetoc",
+            "hiddencat",
+            "index",
+            "newsectionlink",
+            "nocc",
+            "nocontentconvert",
+            "noeditsection",
+            "nogallery",
+            "noglobal",
+            "noindex",
+            "nonewsectionlink",
+            "notc",
+            "notitleconvert",
+            "notoc",
+            "staticredirect",
+            "toc",
+        ],
+    ),
+    (
+        "protocols",
+        &[
+            "//",
+            "bitcoin:",
+            "ftp://",
+            "ftps://",
+            "geo:",
+            "git://",
+            "gopher://",
+            "http://",
+            "https://",
+            "irc://",
+            "ircs://",
+            "magnet:",
+            "mailto:",
+            "mms://",
+            "news:",
+            "nntp://",
+            "redis://",
+            "sftp://",
+            "sip:",
+            "sips:",
+            "sms:",
+            "ssh://",
+            "svn://",
+            "tel:",
+            "telnet://",
+            "urn:",
+            "worldwind://",
+            "xmpp:",
+        ],
+    ),
+    ("redirect_magic_words", &["redirect"]),
+];
+
+/// The default link trail of `parse_wiki_text`, like `DEFAULTS`.
+pub const DEFAULT_LINK_TRAIL: &str = "abcdefghijklmnopqrstuvwxyz";
+
+/// The visibility of emitted items.
+#[derive(Clone, Copy, Debug, Default, serde::Deserialize)]
+pub enum Visibility {
+    /// `pub`.
+    #[default]
+    #[serde(rename = "pub")]
+    Pub,
+    /// `pub(crate)`.
+    #[serde(rename = "pub(crate)")]
+    Crate,
+    /// Private to the module.
+    #[serde(rename = "private")]
+    Private,
+}
+
+/// Names of the emitted items: the words of `prefix`, the base name and `suffix`, joined in the
+/// case style of the item kind unless `case` is given, e.g. `EN_WIKIPEDIA_MAGIC_WORDS`.
+#[derive(Debug, Default)]
+pub struct Naming {
+    /// Prepended to the base name, e.g. `en_wikipedia`.
+    pub prefix: String,
+    /// Appended to the base name.
+    pub suffix: String,
+    /// The case style of all items, instead of that of their kind.
+    pub case: Option<CaseStyle>,
+}
+
+/// The case style of a name.
+#[derive(Clone, Copy, Debug, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CaseStyle {
+    /// `camelCase`.
+    Camel,
+    /// `PascalCase`.
+    Pascal,
+    /// `SCREAMING_SNAKE_CASE`.
+    ScreamingSnake,
+    /// `snake_case`.
+    Snake,
+}
+
+/// An unknown value of an option.
+#[derive(Debug, Error)]
+#[error("invalid value: {0:?}")]
+pub struct OptionParseError(String);
+
+/// A failure to generate an output.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// The output cannot be written.
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    /// The JSON output cannot be serialized.
+    #[error("{0}")]
+    Json(#[from] serde_json::Error),
+    /// The code would require this item of `std` or `alloc`, with `Options::no_std`.
+    #[error("generated code requires `{0}`, which is not available in `core`")]
+    RequiresStd(String),
+    /// The option needs the items of a named constant or of `Emit::Constructor`.
+    #[error(
+        "`{0}` cannot be emitted with a bare expression, only with a named constant or functions"
+    )]
+    RequiresItems(&'static str),
+    /// The options cannot be combined.
+    #[error("{0} cannot be combined with {1}")]
+    Incompatible(&'static str, &'static str),
+    /// The modules of two wikis would have the same name, e.g. of `a-b.org` and `a.b.org`.
+    #[error("the modules of {0:?} and {1:?} would both be named `{2}`")]
+    DuplicateModule(String, String, String),
+    /// An error of another `CodeGenerator`.
+    #[error("{0}")]
+    Custom(Box<dyn std::error::Error + Send + Sync>),
+}
+
+/// An emitter of the extracted configuration, e.g. code for another parser crate.  The formats of
+/// the command line are implemented by `Rust`, `Json`, `Markdown`, `ParserTests` and `TitleRules`;
+/// other emitters can report their own errors as `Error::Custom`.
+pub trait CodeGenerator {
+    /// Write the output of `configuration_source` to `out`.
+    fn generate(
+        &self,
+        out: &mut dyn io::Write,
+        configuration_source: &extract::ConfigurationSource,
+    ) -> Result<(), Error>;
+}
+
+/// Rust code of a `ConfigurationSource`.
+pub struct Rust(pub Options);
+
+/// The `ConfigurationSource`, as JSON.
+pub struct Json;
+
+/// A Markdown report of the configuration.
+pub struct Markdown;
+
+/// The configuration of MediaWiki parser tests run against the wiki.
+pub struct ParserTests;
+
+/// What is needed to normalize titles the way the wiki does, as JSON.
+pub struct TitleRules;
+
+impl CodeGenerator for Rust {
+    fn generate(
+        &self,
+        out: &mut dyn io::Write,
+        configuration_source: &extract::ConfigurationSource,
+    ) -> Result<(), Error> {
+        self::configuration_source(out, configuration_source, &self.0)
+    }
+}
+
+impl CodeGenerator for Json {
+    fn generate(
+        &self,
+        out: &mut dyn io::Write,
+        configuration_source: &extract::ConfigurationSource,
+    ) -> Result<(), Error> {
+        json(out, configuration_source)
+    }
+}
 
+impl CodeGenerator for Markdown {
+    fn generate(
+        &self,
+        out: &mut dyn io::Write,
+        configuration_source: &extract::ConfigurationSource,
+    ) -> Result<(), Error> {
+        markdown(out, configuration_source)
+    }
+}
+
+impl CodeGenerator for ParserTests {
+    fn generate(
+        &self,
+        out: &mut dyn io::Write,
+        configuration_source: &extract::ConfigurationSource,
+    ) -> Result<(), Error> {
+        parser_tests(out, configuration_source)
+    }
+}
+
+impl CodeGenerator for TitleRules {
+    fn generate(
+        &self,
+        out: &mut dyn io::Write,
+        configuration_source: &extract::ConfigurationSource,
+    ) -> Result<(), Error> {
+        title_rules(out, configuration_source)
+    }
+}
+
+/// Write the configuration as Rust code.
 pub fn configuration_source(
     mut out: impl io::Write,
     configuration_source: &extract::ConfigurationSource,
-) -> Result<(), io::Error> {
+    options: &Options,
+) -> Result<(), Error> {
     let extract::ConfigurationSource {
+        case: _,
         category_namespaces,
         extension_tags,
+        extension_tag_sources: _,
         file_namespaces,
+        language: _,
+        legal_title_chars: _,
         link_trail,
         magic_words,
+        namespaces,
+        notes,
         protocols,
         redirect_magic_words,
+        site_name: _,
     } = configuration_source;
-    let link_trail: String = link_trail.iter().collect();
+    if let Emit::Delta = options.emit {
+        if options.split.is_some() {
+            return Err(Error::Incompatible("split output", "a delta"));
+        }
+        if options.const_name.is_some() {
+            return Err(Error::Incompatible("a named constant", "a delta"));
+        }
+        if options.const_assertions {
+            return Err(Error::Incompatible("const assertions", "a delta"));
+        }
+    }
+    let link_trail_string: String = link_trail.iter().collect();
+    let items = options.const_name.is_some() || matches!(options.emit, Emit::Constructor);
+    let (link_trail, link_trail_items) = match (&options.split, options.link_trail) {
+        (Some(directory), _) => (
+            split_value(directory, "link_trail", None),
+            TokenStream::new(),
+        ),
+        (None, LinkTrailEncoding::String) => {
+            (quote::quote!(#link_trail_string), TokenStream::new())
+        }
+        (None, LinkTrailEncoding::Ranges) => link_trail_ranges(link_trail, items, options),
+    };
+    let arrays = [
+        ("category_namespaces", category_namespaces),
+        ("extension_tags", extension_tags),
+        ("file_namespaces", file_namespaces),
+        ("magic_words", magic_words),
+        ("protocols", protocols),
+        ("redirect_magic_words", redirect_magic_words),
+    ];
+    let values = arrays.map(|(field, values)| match &options.split {
+        Some(directory) if SPLIT_FIELDS.contains(&field) => {
+            split_value(directory, field, Some((values.len(), &options.naming)))
+        }
+        _ => quote::quote!(&[ #( #values ),* ]),
+    });
 
+    let tokens = if let Emit::Delta = options.emit {
+        delta(&arrays, &link_trail_string, options)
+    } else if options.const_assertions {
+        const_assertions(
+            &arrays,
+            &values,
+            &link_trail,
+            &options.naming,
+            options.target,
+        )
+    } else {
+        let krate = options.target;
+        let [category_namespaces, extension_tags, file_namespaces, magic_words, protocols, redirect_magic_words] =
+            &values;
+        quote::quote! {
+            #krate::ConfigurationSource {
+                category_namespaces: #category_namespaces,
+                extension_tags: #extension_tags,
+                file_namespaces: #file_namespaces,
+                link_trail: #link_trail ,
+                magic_words: #magic_words,
+                protocols: #protocols,
+                redirect_magic_words: #redirect_magic_words,
+            }
+        }
+    };
+    let tokens = if options.split.is_some() {
+        let lines = options.naming.function("lines");
+        let allow = options.naming.allow();
+        quote::quote! {
+            {
+                #allow
+                const fn #lines<const N: usize>(data: &'static str) -> [&'static str; N] {
+                    let mut values = [""; N];
+                    let mut rest = data.as_bytes();
+                    let mut i = 0;
+                    while i < N {
+                        let mut n = 0;
+                        while rest[n] != b'\n' {
+                            n += 1;
+                        }
+                        let (line, tail) = rest.split_at(n);
+                        values[i] = match ::core::str::from_utf8(line) {
+                            Ok(line) => line,
+                            Err(..) => panic!("data file not encoded as UTF-8"),
+                        };
+                        rest = tail.split_at(1).1;
+                        i += 1;
+                    }
+                    assert!(rest.is_empty(), "data file has more lines than generated");
+                    values
+                }
+                #tokens
+            }
+        }
+    } else {
+        tokens
+    };
+    let krate = options.target;
+    let visibility = options.visibility;
+    let tokens = match (&options.const_name, options.emit) {
+        (Some(name), Emit::Expression) => {
+            let name = quote::format_ident!("{}", name);
+            quote::quote! {
+                #visibility const #name: #krate::ConfigurationSource<'static> = #tokens;
+                #link_trail_items
+            }
+        }
+        (None, Emit::Expression) => tokens,
+        (name, Emit::Constructor) => {
+            let (source_visibility, source) = match name {
+                Some(name) => (visibility, quote::format_ident!("{}", name)),
+                None => (Visibility::Private, options.naming.constant("source")),
+            };
+            let configuration = options.naming.constant("configuration");
+            let function = options.naming.function("configuration");
+            let allow = options.naming.allow();
+            quote::quote! {
+                #allow
+                #source_visibility const #source: #krate::ConfigurationSource<'static> =
+                    #tokens;
+                #link_trail_items
+                #allow
+                #visibility fn #function() -> &'static #krate::Configuration {
+                    static #configuration: ::std::sync::OnceLock<#krate::Configuration> =
+                        ::std::sync::OnceLock::new();
+                    #configuration.get_or_init(|| #krate::Configuration::new(&#source))
+                }
+            }
+        }
+        (_, Emit::Delta) => tokens,
+    };
+    let tokens = if options.namespace_ids {
+        if options.const_name.is_none() && matches!(options.emit, Emit::Expression) {
+            return Err(Error::RequiresItems("namespace_id"));
+        }
+        let namespace_ids = namespace_ids(namespaces, options);
+        quote::quote!(#tokens #namespace_ids)
+    } else {
+        tokens
+    };
+    let tokens = if options.escape_unicode {
+        escape_unicode(tokens)
+    } else {
+        tokens
+    };
+    if options.no_std {
+        check_no_std(&tokens)?;
+    }
+    for note in notes {
+        if options.escape_unicode {
+            writeln!(out, "// {}", escape_non_ascii(note))?;
+        } else {
+            writeln!(out, "// {}", note)?;
+        }
+    }
+    // The named constant comes first in the tokens, so the comments document it.
+    if options.const_name.is_some() {
+        for line in documentation(configuration_source) {
+            let line = if options.escape_unicode {
+                escape_non_ascii(&line)
+            } else {
+                line
+            };
+            if line.is_empty() {
+                writeln!(out, "///")?;
+            } else {
+                writeln!(out, "/// {}", line)?;
+            }
+        }
+    }
+    write_tokens(out, tokens, options.max_width)?;
+
+    Ok(())
+}
+
+// Lines of the doc comment of a named constant: the wiki, and the size of each field with the
+// values not among the parser defaults, so that the documentation of the consuming crate shows
+// what is particular to the wiki.
+fn documentation(configuration_source: &extract::ConfigurationSource) -> Vec<String> {
+    // At most this many values are listed per field.
+    const NOTABLE: usize = 8;
+
+    let extract::ConfigurationSource {
+        category_namespaces,
+        extension_tags,
+        file_namespaces,
+        language,
+        link_trail,
+        magic_words,
+        protocols,
+        redirect_magic_words,
+        site_name,
+        ..
+    } = configuration_source;
+    let wiki = match (site_name, language) {
+        (Some(site_name), Some(language)) => format!("{} (`{}`)", site_name, language),
+        (Some(site_name), None) => site_name.clone(),
+        (None, Some(language)) => format!("the `{}` wiki", language),
+        (None, None) => "the wiki".to_owned(),
+    };
+    let mut lines = vec![
+        format!("The `ConfigurationSource` of {}.", wiki),
+        String::new(),
+        format!(
+            "Generated by `{}` {}. The number of values of each field, with those",
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION")
+        ),
+        "not among the parser defaults:".to_owned(),
+        String::new(),
+    ];
+    for (field, title, values) in [
+        (
+            "category_namespaces",
+            "Category namespaces",
+            category_namespaces,
+        ),
+        ("extension_tags", "Extension tags", extension_tags),
+        ("file_namespaces", "File namespaces", file_namespaces),
+        ("magic_words", "Magic words", magic_words),
+        ("protocols", "Protocols", protocols),
+        (
+            "redirect_magic_words",
+            "Redirect magic words",
+            redirect_magic_words,
+        ),
+    ] {
+        let defaults = defaults(field);
+        let notable: Vec<_> = values
+            .iter()
+            .filter(|value| !defaults.contains(value.as_str()))
+            .collect();
+        let mut line = format!("- {}: {}", title, values.len());
+        if !notable.is_empty() {
+            line += &format!(" (`{}`", notable.iter().take(NOTABLE).format("`, `"));
+            if notable.len() > NOTABLE {
+                line += &format!(", and {} more", notable.len() - NOTABLE);
+            }
+            line += ")";
+        }
+        lines.push(line);
+    }
+    let notable: String = link_trail
+        .iter()
+        .filter(|c| !DEFAULT_LINK_TRAIL.contains(**c))
+        .collect();
+    // Characters are listed together, so more of them fit.
+    let mut line = format!("- Link trail characters: {}", link_trail.len());
+    if !notable.is_empty() {
+        let count = notable.chars().count();
+        line += &format!(
+            " (`{}`",
+            notable.chars().take(NOTABLE * 4).collect::<String>()
+        );
+        if count > NOTABLE * 4 {
+            line += &format!(", and {} more", count - NOTABLE * 4);
+        }
+        line += ")";
+    }
+    lines.push(line);
+    lines
+}
+
+// A table of the names, canonical names and aliases of all namespaces, sorted for binary search,
+// and a function looking names up in it.  Names are matched as by MediaWiki: case-insensitively,
+// with underscores for spaces.
+fn namespace_ids(namespaces: &[extract::Namespace], options: &Options) -> TokenStream {
+    fn normalize(name: &str) -> String {
+        name.chars()
+            .flat_map(char::to_lowercase)
+            .map(|c| if c == '_' { ' ' } else { c })
+            .collect()
+    }
+
+    let mut ids = collections::BTreeMap::new();
+    for namespace in namespaces {
+        let names = iter::once(&namespace.name)
+            .chain(&namespace.canonical)
+            .chain(&namespace.aliases);
+        for name in names {
+            ids.entry(normalize(name)).or_insert(namespace.id);
+        }
+    }
+    let (names, ids): (Vec<_>, Vec<_>) = ids
+        .into_iter()
+        .map(|(name, id)| (name, proc_macro2::Literal::i64_unsuffixed(id)))
+        .unzip();
+
+    let visibility = options.visibility;
+    let table = options.naming.constant("namespace_ids");
+    let function = options.naming.function("namespace_id");
+    let allow = options.naming.allow();
+    quote::quote! {
+        #allow
+        #visibility const #table: &[(&str, i64)] = &[ #( (#names, #ids) ),* ];
+        #allow
+        #visibility fn #function(name: &str) -> Option<i64> {
+            let name = || {
+                name.chars()
+                    .flat_map(char::to_lowercase)
+                    .map(|c| if c == '_' { ' ' } else { c })
+            };
+            #table
+                .binary_search_by(|(entry, _)| entry.chars().cmp(name()))
+                .ok()
+                .map(|i| #table[i].1)
+        }
+    }
+}
+
+/// The code generated for each of several wikis, in a module named after its domain, e.g.
+/// `en_wikipedia_org`, so that they can share a single output.
+pub fn modules(
+    mut out: impl io::Write,
+    wikis: &[(String, Vec<u8>)],
+    visibility: Visibility,
+) -> Result<(), Error> {
+    let names = module_names(wikis.iter().map(|(domain, _)| domain.as_str()))?;
+    for (i, ((_, code), name)) in wikis.iter().zip(names).enumerate() {
+        if i > 0 {
+            writeln!(out)?;
+        }
+        writeln!(out, "{} {{", quote::quote!(#visibility mod #name))?;
+        out.write_all(code)?;
+        writeln!(out)?;
+        writeln!(out, "}}")?;
+    }
+    Ok(())
+}
+
+/// A function returning the named constant of the module of `modules` for each of `domains`, e.g.
+/// `configuration_for("en.wikipedia.org")`, for parsing the wikitext of any of them at runtime.
+pub fn registry(
+    mut out: impl io::Write,
+    domains: &[String],
+    options: &Options,
+) -> Result<(), Error> {
+    let name = match (&options.const_name, options.emit) {
+        (_, Emit::Delta) => return Err(Error::Incompatible("a registry", "a delta")),
+        (None, _) => return Err(Error::RequiresItems("configuration_for")),
+        (Some(name), _) => quote::format_ident!("{}", name),
+    };
+    if matches!(options.visibility, Visibility::Private) {
+        return Err(Error::Incompatible("a registry", "private items"));
+    }
+    let krate = options.target;
+    let visibility = options.visibility;
+    let function = options.naming.function("configuration_for");
+    let modules = module_names(domains.iter().map(String::as_str))?;
+    let allow = options.naming.allow();
     let tokens = quote::quote! {
-        ::parse_wiki_text::ConfigurationSource {
-            category_namespaces: &[ #( #category_namespaces ),* ],
-            extension_tags: &[ #( #extension_tags ),* ],
-            file_namespaces: &[ #( #file_namespaces ),* ],
-            link_trail: #link_trail ,
-            magic_words: &[ #( #magic_words ),* ],
-            protocols: &[ #( #protocols ),* ],
-            redirect_magic_words: &[ #( #redirect_magic_words ),* ],
+        #allow
+        #visibility fn #function(domain: &str) ->
+            ::core::option::Option<&'static #krate::ConfigurationSource<'static>> {
+            match domain {
+                #(#domains => ::core::option::Option::Some(&#modules::#name),)*
+                _ => ::core::option::Option::None,
+            }
         }
     };
-    write!(out, "{}", tokens)?;
+    writeln!(out)?;
+    write_tokens(&mut out, tokens, options.max_width)?;
+    writeln!(out)?;
+    Ok(())
+}
+
+fn module_names<'a>(
+    domains: impl Iterator<Item = &'a str>,
+) -> Result<Vec<proc_macro2::Ident>, Error> {
+    let mut seen = collections::HashMap::new();
+    domains
+        .map(|domain| {
+            let name = module_name(domain);
+            match seen.insert(name.to_string(), domain) {
+                Some(other) => Err(Error::DuplicateModule(
+                    other.to_owned(),
+                    domain.to_owned(),
+                    name.to_string(),
+                )),
+                None => Ok(name),
+            }
+        })
+        .collect()
+}
+
+// E.g. `en_wikipedia_org`.
+fn module_name(domain: &str) -> proc_macro2::Ident {
+    let mut name = CaseStyle::Snake.join(&words(domain));
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        name.insert_str(0, "wiki_");
+    }
+    quote::format_ident!("{}", name)
+}
+
+fn write_tokens(
+    mut out: impl io::Write,
+    tokens: TokenStream,
+    max_width: Option<usize>,
+) -> Result<(), io::Error> {
+    match max_width {
+        Some(max_width) => {
+            let mut wrapper = Wrapper {
+                out: String::new(),
+                column: 0,
+                depth: 0,
+                max_width,
+            };
+            wrapper.statements(tokens);
+            write!(out, "{}", wrapper.out.trim_end())
+        }
+        None => write!(out, "{}", tokens),
+    }
+}
+
+// Writes tokens spaced as by `TokenStream`'s `Display`, on a single line except for groups wider
+// than `max_width`: arrays of literals, whose elements are filled into lines, and braces, with a
+// line per field or statement, indented by the depth of the enclosing groups.
+struct Wrapper {
+    out: String,
+    column: usize,
+    depth: usize,
+    max_width: usize,
+}
+
+impl Wrapper {
+    fn tokens(&mut self, tokens: TokenStream) {
+        for token in tokens {
+            self.token(token);
+        }
+    }
+
+    fn token(&mut self, token: TokenTree) {
+        use proc_macro2::Delimiter;
+        match token {
+            TokenTree::Group(group) => {
+                let width = group.to_string().len();
+                let elements = array_elements(&group);
+                let wide = self.column + width > self.max_width;
+                match elements {
+                    Some(elements) if wide => self.array(elements),
+                    _ if wide && group.delimiter() == Delimiter::Brace => self.block(group),
+                    _ => {
+                        let (open, close) = match group.delimiter() {
+                            Delimiter::Brace => ("{ ", " }"),
+                            Delimiter::Bracket => ("[", "]"),
+                            Delimiter::Parenthesis => ("(", ")"),
+                            Delimiter::None => ("", ""),
+                        };
+                        self.push(open);
+                        self.depth += 1;
+                        self.tokens(group.stream());
+                        self.depth -= 1;
+                        if self.out.ends_with(' ') {
+                            self.out.pop();
+                            self.column -= 1;
+                        }
+                        self.push(close);
+                        self.push(" ");
+                    }
+                }
+            }
+            TokenTree::Punct(punct) => {
+                self.push(&punct.as_char().to_string());
+                if punct.spacing() == proc_macro2::Spacing::Alone {
+                    self.push(" ");
+                }
+            }
+            TokenTree::Ident(..) | TokenTree::Literal(..) => {
+                self.push(&token.to_string());
+                self.push(" ");
+            }
+        }
+    }
+
+    fn block(&mut self, group: proc_macro2::Group) {
+        self.push("{");
+        self.depth += 1;
+        self.newline();
+        self.statements(group.stream());
+        self.depth -= 1;
+        self.newline();
+        self.push("} ");
+    }
+
+    fn statements(&mut self, tokens: TokenStream) {
+        let tokens: Vec<_> = tokens.into_iter().collect();
+        for (i, token) in tokens.iter().enumerate() {
+            // A line ends after a field or statement, or a block that is not followed by either.
+            let separator = |token: &TokenTree| match token {
+                TokenTree::Punct(punct) => matches!(punct.as_char(), ',' | ';'),
+                _ => false,
+            };
+            let end = match token {
+                TokenTree::Group(group) => {
+                    group.delimiter() == proc_macro2::Delimiter::Brace
+                        && !tokens.get(i + 1).is_some_and(separator)
+                }
+                _ => separator(token),
+            };
+            self.token(token.clone());
+            if end {
+                self.newline();
+            }
+        }
+    }
+
+    fn array(&mut self, elements: Vec<String>) {
+        self.push("[");
+        self.depth += 1;
+        self.newline();
+        let start = self.column;
+        for element in elements {
+            if self.column > start && self.column + element.len() + 1 > self.max_width {
+                self.newline();
+            }
+            self.push(&element);
+            self.push(", ");
+        }
+        self.depth -= 1;
+        self.newline();
+        self.push("] ");
+    }
+
+    // Trailing spaces of the current line are removed, and so is the line if it is empty.
+    fn newline(&mut self) {
+        let trimmed = self.out.trim_end_matches(' ').len();
+        self.out.truncate(trimmed);
+        if !self.out.ends_with('\n') {
+            self.out.push('\n');
+        }
+        self.out.push_str(&"    ".repeat(self.depth));
+        self.column = 4 * self.depth;
+    }
+
+    fn push(&mut self, s: &str) {
+        self.out.push_str(s);
+        self.column += s.chars().count();
+    }
+}
+
+// The elements of a non-empty bracketed list of literals or tuples of literals, e.g. `["a", "b"]`
+// or `[('a', 'z')]`.
+fn array_elements(group: &proc_macro2::Group) -> Option<Vec<String>> {
+    if group.delimiter() != proc_macro2::Delimiter::Bracket {
+        return None;
+    }
+    // Negative numbers are a `-` followed by the literal.
+    fn literal(tokens: &[TokenTree]) -> Option<String> {
+        match tokens {
+            [TokenTree::Literal(literal)] => Some(literal.to_string()),
+            [TokenTree::Punct(minus), TokenTree::Literal(literal)] if minus.as_char() == '-' => {
+                Some(format!("-{}", literal))
+            }
+            _ => None,
+        }
+    }
+
+    let elements = list(group.stream(), |tokens| match tokens {
+        [TokenTree::Group(group)] if group.delimiter() == proc_macro2::Delimiter::Parenthesis => {
+            let literals = list(group.stream(), literal)?;
+            Some(format!("({})", literals.join(", ")))
+        }
+        _ => literal(tokens),
+    })?;
+    if elements.is_empty() {
+        None
+    } else {
+        Some(elements)
+    }
+}
+
+// The elements of a comma-separated list, if `element` accepts the tokens of each of them.
+fn list(
+    tokens: TokenStream,
+    element: impl Fn(&[TokenTree]) -> Option<String>,
+) -> Option<Vec<String>> {
+    let tokens: Vec<_> = tokens.into_iter().collect();
+    if tokens.is_empty() {
+        return Some(Vec::new());
+    }
+    tokens
+        .split(|token| matches!(token, TokenTree::Punct(punct) if punct.as_char() == ','))
+        .map(element)
+        .collect()
+}
+
+// Emit each array as a constant, checked at compile time to be strictly sorted, and non-empty
+// unless it was generated empty, so that hand edits of the generated code are caught by the
+// compiler of the consumer.
+fn const_assertions(
+    arrays: &[(&str, &collections::BTreeSet<String>)],
+    values: &[TokenStream],
+    link_trail: &TokenStream,
+    naming: &Naming,
+    krate: Target,
+) -> TokenStream {
+    let fields: Vec<_> = arrays
+        .iter()
+        .map(|(field, _)| quote::format_ident!("{}", field))
+        .collect();
+    let names: Vec<_> = arrays
+        .iter()
+        .map(|(field, _)| naming.constant(field))
+        .collect();
+    let is_sorted = naming.function("is_sorted");
+    let less = naming.function("less");
+    let allow = naming.allow();
+    let non_empty = arrays
+        .iter()
+        .zip(&names)
+        .filter(|((_, set), _)| !set.is_empty())
+        .map(|(_, name)| name);
+
+    quote::quote! {
+        {
+            #allow
+            const fn #is_sorted(items: &[&str]) -> bool {
+                const fn #less(a: &[u8], b: &[u8]) -> bool {
+                    let mut i = 0;
+                    while i < a.len() && i < b.len() {
+                        if a[i] != b[i] {
+                            return a[i] < b[i];
+                        }
+                        i += 1;
+                    }
+                    a.len() < b.len()
+                }
+                let mut i = 1;
+                while i < items.len() {
+                    if !#less(items[i - 1].as_bytes(), items[i].as_bytes()) {
+                        return false;
+                    }
+                    i += 1;
+                }
+                true
+            }
+            #( #allow const #names: &[&str] = #values; )*
+            const _: () = {
+                #( assert!(#is_sorted(#names)); )*
+                #( assert!(!#non_empty.is_empty()); )*
+            };
+            #krate::ConfigurationSource {
+                #( #fields: #names, )*
+                link_trail: #link_trail,
+            }
+        }
+    }
+}
+
+/// The fields of `split_data`.
+pub const SPLIT_FIELDS: &[&str] = &["link_trail", "magic_words", "protocols"];
+
+/// The data files of `Options::split`, by name: the link trail as its string, and the magic words
+/// and protocols with a line each, so that changes of the data are diffed by line.
+pub fn split_data(configuration_source: &extract::ConfigurationSource) -> Vec<(String, String)> {
+    let lines = |values: &collections::BTreeSet<String>| -> String {
+        values.iter().map(|value| format!("{}\n", value)).collect()
+    };
+    vec![
+        (
+            split_file("link_trail"),
+            configuration_source.link_trail.iter().collect(),
+        ),
+        (
+            split_file("magic_words"),
+            lines(&configuration_source.magic_words),
+        ),
+        (
+            split_file("protocols"),
+            lines(&configuration_source.protocols),
+        ),
+    ]
+}
+
+fn split_file(field: &str) -> String {
+    format!("{}.txt", field)
+}
+
+// The string of the data file of `field`, or, given the number of lines, its lines as an array,
+// split by the function `lines` at compile time (requires Rust 1.71).
+fn split_value(
+    directory: &path::Path,
+    field: &str,
+    lines: Option<(usize, &Naming)>,
+) -> TokenStream {
+    let path = directory.join(split_file(field));
+    let path = path.to_string_lossy();
+    match lines {
+        Some((len, naming)) => {
+            let lines = naming.function("lines");
+            quote::quote! {
+                {
+                    const LINES: [&str; #len] = #lines(include_str!(#path));
+                    &LINES
+                }
+            }
+        }
+        None => quote::quote!(include_str!(#path)),
+    }
+}
+
+// The link trail as ranges of characters, from which its string is built at compile time, so that
+// the link trails of e.g. CJK wikis, of tens of thousands of characters, stay small in the source
+// (requires Rust 1.63).  With items, the ranges are emitted as a constant too, with a function
+// matching characters against them.
+fn link_trail_ranges(
+    link_trail: &collections::BTreeSet<char>,
+    items: bool,
+    options: &Options,
+) -> (TokenStream, TokenStream) {
+    let mut ranges: Vec<(char, char)> = Vec::new();
+    for &c in link_trail {
+        match ranges.last_mut() {
+            Some((_, end)) if *end as u32 + 1 == c as u32 => *end = c,
+            _ => ranges.push((c, c)),
+        }
+    }
+    let (starts, ends): (Vec<_>, Vec<_>) = ranges.into_iter().unzip();
+    let len: usize = link_trail.iter().map(|c| c.len_utf8()).sum();
+
+    let (ranges, items) = if items {
+        let visibility = options.visibility;
+        let name = options.naming.constant("link_trail_ranges");
+        let matcher = options.naming.function("is_link_trail_character");
+        let allow = options.naming.allow();
+        let items = quote::quote! {
+            #allow
+            #visibility const #name: &[(char, char)] = &[ #( (#starts, #ends) ),* ];
+            #allow
+            #visibility fn #matcher(c: char) -> bool {
+                #name
+                    .binary_search_by(|&(start, end)| {
+                        if end < c {
+                            ::core::cmp::Ordering::Less
+                        } else if start > c {
+                            ::core::cmp::Ordering::Greater
+                        } else {
+                            ::core::cmp::Ordering::Equal
+                        }
+                    })
+                    .is_ok()
+            }
+        };
+        (quote::quote!(#name), items)
+    } else {
+        (
+            quote::quote!(&[ #( (#starts, #ends) ),* ]),
+            TokenStream::new(),
+        )
+    };
+    let expr = quote::quote! {
+        {
+            const RANGES: &[(char, char)] = #ranges;
+            const BYTES: &[u8] = &{
+                let mut bytes = [0; #len];
+                let mut n = 0;
+                let mut i = 0;
+                while i < RANGES.len() {
+                    let mut c = RANGES[i].0 as u32;
+                    while c <= RANGES[i].1 as u32 {
+                        if c < 0x80 {
+                            bytes[n] = c as u8;
+                            n += 1;
+                        } else if c < 0x800 {
+                            bytes[n] = 0xc0 | (c >> 6) as u8;
+                            bytes[n + 1] = 0x80 | (c & 0x3f) as u8;
+                            n += 2;
+                        } else if c < 0x10000 {
+                            bytes[n] = 0xe0 | (c >> 12) as u8;
+                            bytes[n + 1] = 0x80 | ((c >> 6) & 0x3f) as u8;
+                            bytes[n + 2] = 0x80 | (c & 0x3f) as u8;
+                            n += 3;
+                        } else {
+                            bytes[n] = 0xf0 | (c >> 18) as u8;
+                            bytes[n + 1] = 0x80 | ((c >> 12) & 0x3f) as u8;
+                            bytes[n + 2] = 0x80 | ((c >> 6) & 0x3f) as u8;
+                            bytes[n + 3] = 0x80 | (c & 0x3f) as u8;
+                            n += 4;
+                        }
+                        c += 1;
+                    }
+                    i += 1;
+                }
+                bytes
+            };
+            match ::core::str::from_utf8(BYTES) {
+                Ok(link_trail) => link_trail,
+                Err(..) => panic!("link trail ranges not encoded as UTF-8"),
+            }
+        }
+    };
+    (expr, items)
+}
+
+fn defaults(field: &str) -> collections::BTreeSet<&'static str> {
+    DEFAULTS
+        .iter()
+        .find(|(f, _)| *f == field)
+        .map_or(&[][..], |(_, values)| values)
+        .iter()
+        .copied()
+        .collect()
+}
+
+// The added and removed values as two `ConfigurationSource` constants, the link trail as strings
+// of characters, and a function building the `Configuration` from them and the defaults, which
+// are not exposed by the parser crates, so must be passed in.
+fn delta(
+    arrays: &[(&str, &collections::BTreeSet<String>)],
+    link_trail: &str,
+    options: &Options,
+) -> TokenStream {
+    let fields: Vec<_> = arrays
+        .iter()
+        .map(|(field, _)| quote::format_ident!("{}", field))
+        .collect();
+    let (added, removed): (Vec<Vec<_>>, Vec<Vec<_>>) = arrays
+        .iter()
+        .map(|(field, values)| {
+            let defaults = defaults(field);
+            let values: collections::BTreeSet<_> = values.iter().map(String::as_str).collect();
+            (
+                values.difference(&defaults).copied().collect(),
+                defaults.difference(&values).copied().collect(),
+            )
+        })
+        .unzip();
+    let added_link_trail: String = link_trail
+        .chars()
+        .filter(|c| !DEFAULT_LINK_TRAIL.contains(*c))
+        .collect();
+    let removed_link_trail: String = DEFAULT_LINK_TRAIL
+        .chars()
+        .filter(|c| !link_trail.contains(*c))
+        .collect();
+
+    let krate = options.target;
+    let visibility = options.visibility;
+    let added_name = options.naming.constant("added");
+    let removed_name = options.naming.constant("removed");
+    let function = options.naming.function("configuration");
+    let merge = options.naming.function("merge");
+    let allow = options.naming.allow();
+    quote::quote! {
+        #allow
+        #visibility const #added_name: #krate::ConfigurationSource<'static> =
+            #krate::ConfigurationSource {
+                #( #fields: &[ #( #added ),* ], )*
+                link_trail: #added_link_trail,
+            };
+        #allow
+        #visibility const #removed_name: #krate::ConfigurationSource<'static> =
+            #krate::ConfigurationSource {
+                #( #fields: &[ #( #removed ),* ], )*
+                link_trail: #removed_link_trail,
+            };
+        #allow
+        #visibility fn #function(defaults: &#krate::ConfigurationSource<'_>) -> #krate::Configuration {
+            fn #merge<'a>(
+                defaults: &[&'a str],
+                added: &[&'a str],
+                removed: &[&str],
+            ) -> ::std::vec::Vec<&'a str> {
+                defaults
+                    .iter()
+                    .filter(|value| !removed.contains(value))
+                    .chain(added)
+                    .copied()
+                    .collect()
+            }
+            #( let #fields = #merge(defaults.#fields, #added_name.#fields, #removed_name.#fields); )*
+            let link_trail: ::std::string::String = defaults
+                .link_trail
+                .chars()
+                .filter(|c| !#removed_name.link_trail.contains(*c))
+                .chain(#added_name.link_trail.chars())
+                .collect();
+            #krate::Configuration::new(&#krate::ConfigurationSource {
+                #( #fields: &#fields, )*
+                link_trail: &link_trail,
+            })
+        }
+    }
+}
+
+impl Naming {
+    /// The name of a constant, in `SCREAMING_SNAKE_CASE` unless `case` is given.
+    pub fn constant(&self, base: &str) -> proc_macro2::Ident {
+        self.ident(base, CaseStyle::ScreamingSnake)
+    }
+
+    /// The name of a function, in `snake_case` unless `case` is given.
+    pub fn function(&self, base: &str) -> proc_macro2::Ident {
+        self.ident(base, CaseStyle::Snake)
+    }
+
+    /// The attribute of the items, allowing the lints of their names if `case` is given.
+    pub fn allow(&self) -> TokenStream {
+        match self.case {
+            Some(_) => quote::quote!(#[allow(non_snake_case, non_upper_case_globals)]),
+            None => TokenStream::new(),
+        }
+    }
+
+    fn ident(&self, base: &str, case: CaseStyle) -> proc_macro2::Ident {
+        let words: Vec<_> = [self.prefix.as_str(), base, self.suffix.as_str()]
+            .iter()
+            .flat_map(|s| words(s))
+            .collect();
+        quote::format_ident!("{}", self.case.unwrap_or(case).join(&words))
+    }
+}
+
+impl CaseStyle {
+    fn join(self, words: &[String]) -> String {
+        fn capitalize(word: &str) -> String {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|c| c.to_ascii_uppercase())
+                .into_iter()
+                .chain(chars)
+                .collect()
+        }
+        match self {
+            CaseStyle::Camel => words
+                .iter()
+                .enumerate()
+                .map(|(i, word)| {
+                    if i == 0 {
+                        word.clone()
+                    } else {
+                        capitalize(word)
+                    }
+                })
+                .collect(),
+            CaseStyle::Pascal => words.iter().map(|word| capitalize(word)).collect(),
+            CaseStyle::ScreamingSnake => words.join("_").to_ascii_uppercase(),
+            CaseStyle::Snake => words.join("_"),
+        }
+    }
+}
+
+impl quote::ToTokens for Visibility {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        tokens.extend(match self {
+            Self::Pub => quote::quote!(pub),
+            Self::Crate => quote::quote!(pub(crate)),
+            Self::Private => TokenStream::new(),
+        });
+    }
+}
+
+impl Target {
+    /// All targets.
+    pub const ALL: &'static [Self] = &[Self::ParseWikiText, Self::ParseWikiText2];
+
+    /// The name of the crate, as used in paths.
+    pub fn crate_name(self) -> &'static str {
+        match self {
+            Self::ParseWikiText => "parse_wiki_text",
+            Self::ParseWikiText2 => "parse_wiki_text_2",
+        }
+    }
+}
+
+impl quote::ToTokens for Target {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let name = quote::format_ident!("{}", self.crate_name());
+        tokens.extend(quote::quote!(::#name));
+    }
+}
+
+impl std::str::FromStr for Target {
+    type Err = OptionParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "parse-wiki-text" => Ok(Self::ParseWikiText),
+            "parse-wiki-text-2" => Ok(Self::ParseWikiText2),
+            _ => Err(OptionParseError(s.to_owned())),
+        }
+    }
+}
+
+impl std::str::FromStr for LinkTrailEncoding {
+    type Err = OptionParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "string" => Ok(Self::String),
+            "ranges" => Ok(Self::Ranges),
+            _ => Err(OptionParseError(s.to_owned())),
+        }
+    }
+}
+
+impl std::str::FromStr for Emit {
+    type Err = OptionParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "expression" => Ok(Self::Expression),
+            "constructor" => Ok(Self::Constructor),
+            "delta" => Ok(Self::Delta),
+            _ => Err(OptionParseError(s.to_owned())),
+        }
+    }
+}
+
+impl std::str::FromStr for Visibility {
+    type Err = OptionParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pub" => Ok(Self::Pub),
+            "pub(crate)" => Ok(Self::Crate),
+            "private" => Ok(Self::Private),
+            _ => Err(OptionParseError(s.to_owned())),
+        }
+    }
+}
+
+impl std::str::FromStr for CaseStyle {
+    type Err = OptionParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "camel" => Ok(Self::Camel),
+            "pascal" => Ok(Self::Pascal),
+            "screaming-snake" => Ok(Self::ScreamingSnake),
+            "snake" => Ok(Self::Snake),
+            _ => Err(OptionParseError(s.to_owned())),
+        }
+    }
+}
+
+/// A module selecting the generated Rust outputs by the language code of their wiki, each included
+/// from its path, e.g. for all the Wiktionaries.
+pub fn by_language(
+    mut out: impl io::Write,
+    outputs: &collections::BTreeMap<String, path::PathBuf>,
+    krate: Target,
+) -> Result<(), Error> {
+    let codes = outputs.keys();
+    let paths = outputs.values().map(|path| path.to_string_lossy());
+    let tokens = quote::quote! {
+        pub mod by_language {
+            pub fn get(
+                code: &str,
+            ) -> Option<&'static #krate::ConfigurationSource<'static>> {
+                match code {
+                    #(
+                        #codes => {
+                            static CONFIGURATION_SOURCE:
+                                #krate::ConfigurationSource<'static> = include!(#paths);
+                            Some(&CONFIGURATION_SOURCE)
+                        }
+                    )*
+                    _ => None,
+                }
+            }
+        }
+    };
+    writeln!(out, "{}", tokens)?;
+    Ok(())
+}
+
+// Split a name into lowercase words, at non-alphanumeric characters and at lowercase to uppercase
+// transitions, e.g. `en-wikipedia` and `EnWikipedia` both into `en`, `wikipedia`.
+fn words(s: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut previous_lowercase = false;
+    for c in s.chars() {
+        if !c.is_ascii_alphanumeric() {
+            words.push(std::mem::take(&mut word));
+            previous_lowercase = false;
+            continue;
+        }
+        if c.is_ascii_uppercase() && previous_lowercase {
+            words.push(std::mem::take(&mut word));
+        }
+        previous_lowercase = c.is_ascii_lowercase() || c.is_ascii_digit();
+        word.push(c.to_ascii_lowercase());
+    }
+    words.push(word);
+    words.retain(|word| !word.is_empty());
+    words
+}
+
+// Rewrite string and character literals with non-ASCII characters, escaping those as `\u{...}`.
+// Escapes being ASCII, the non-ASCII characters of a literal are those of its value.
+fn escape_unicode(tokens: TokenStream) -> TokenStream {
+    tokens
+        .into_iter()
+        .map(|token| match token {
+            TokenTree::Group(group) => {
+                let mut escaped =
+                    proc_macro2::Group::new(group.delimiter(), escape_unicode(group.stream()));
+                escaped.set_span(group.span());
+                TokenTree::Group(escaped)
+            }
+            TokenTree::Literal(literal) => {
+                let repr = literal.to_string();
+                if repr.is_ascii() || !repr.starts_with(['"', '\'']) {
+                    return TokenTree::Literal(literal);
+                }
+                let escaped: String = repr
+                    .chars()
+                    .map(|c| {
+                        if c.is_ascii() {
+                            c.to_string()
+                        } else {
+                            c.escape_unicode().to_string()
+                        }
+                    })
+                    .collect();
+                TokenTree::Literal(escaped.parse().unwrap())
+            }
+            TokenTree::Ident(..) | TokenTree::Punct(..) => token,
+        })
+        .collect()
+}
+
+// ASCII characters are escaped as by `str::escape_debug`, others always as `\u{...}`.
+fn escape_non_ascii(s: &str) -> String {
+    s.chars()
+        .map(|c| {
+            if c.is_ascii() {
+                c.escape_debug().to_string()
+            } else {
+                c.escape_unicode().to_string()
+            }
+        })
+        .collect()
+}
+
+// Reject paths and items of `std` and `alloc`, so that the generated code can be included by
+// `no_std` crates without an allocator.
+fn check_no_std(tokens: &TokenStream) -> Result<(), Error> {
+    const NOT_CORE: &[&str] = &["alloc", "std", "Box", "String", "Vec", "format", "vec"];
+    for token in tokens.clone() {
+        match token {
+            TokenTree::Group(group) => check_no_std(&group.stream())?,
+            TokenTree::Ident(ident) => {
+                if let Some(name) = NOT_CORE.iter().find(|name| ident == name) {
+                    return Err(Error::RequiresStd((*name).to_owned()));
+                }
+            }
+            TokenTree::Literal(..) | TokenTree::Punct(..) => {}
+        }
+    }
+    Ok(())
+}
+
+/// Write the configuration as JSON.
+pub fn json(
+    mut out: impl io::Write,
+    configuration_source: &extract::ConfigurationSource,
+) -> Result<(), Error> {
+    serde_json::to_writer_pretty(&mut out, configuration_source)?;
+    writeln!(out)?;
+    Ok(())
+}
+
+/// Write a Markdown report of the configuration.
+pub fn markdown(
+    mut out: impl io::Write,
+    configuration_source: &extract::ConfigurationSource,
+) -> Result<(), Error> {
+    let extract::ConfigurationSource {
+        case: _,
+        category_namespaces,
+        extension_tags,
+        extension_tag_sources: _,
+        file_namespaces,
+        language: _,
+        legal_title_chars: _,
+        link_trail,
+        magic_words,
+        namespaces,
+        notes,
+        protocols,
+        redirect_magic_words,
+        site_name: _,
+    } = configuration_source;
+
+    writeln!(out, "# Wiki configuration")?;
+    if !notes.is_empty() {
+        writeln!(out)?;
+        for note in notes {
+            writeln!(out, "> {}", note)?;
+        }
+    }
+    writeln!(out)?;
+    writeln!(out, "| Field | Count |")?;
+    writeln!(out, "| --- | ---: |")?;
+    for (title, count) in [
+        ("Category namespaces", category_namespaces.len()),
+        ("Extension tags", extension_tags.len()),
+        ("File namespaces", file_namespaces.len()),
+        ("Link trail", link_trail.len()),
+        ("Magic words", magic_words.len()),
+        ("Namespaces", namespaces.len()),
+        ("Protocols", protocols.len()),
+        ("Redirect magic words", redirect_magic_words.len()),
+    ] {
+        writeln!(out, "| {} | {} |", title, count)?;
+    }
+
+    for (title, set) in [
+        ("Category namespaces", category_namespaces),
+        ("Extension tags", extension_tags),
+        ("File namespaces", file_namespaces),
+        ("Magic words", magic_words),
+        ("Protocols", protocols),
+        ("Redirect magic words", redirect_magic_words),
+    ] {
+        writeln!(out)?;
+        writeln!(out, "## {}", title)?;
+        writeln!(out)?;
+        writeln!(
+            out,
+            "{}",
+            set.iter().map(|s| format!("`{}`", s)).format(", ")
+        )?;
+    }
+
+    writeln!(out)?;
+    writeln!(out, "## Link trail")?;
+    writeln!(out)?;
+    writeln!(out, "`{}`", link_trail.iter().collect::<String>())?;
+
+    writeln!(out)?;
+    writeln!(out, "## Namespaces")?;
+    writeln!(out)?;
+    writeln!(out, "| Id | Name | Canonical | Aliases | Talk |")?;
+    writeln!(out, "| ---: | --- | --- | --- | ---: |")?;
+    for ns in namespaces {
+        writeln!(
+            out,
+            "| {} | {} | {} | {} | {} |",
+            ns.id,
+            ns.name,
+            ns.canonical.as_deref().unwrap_or(""),
+            ns.aliases.iter().format(", "),
+            ns.talk.map(|id| id.to_string()).unwrap_or_default()
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Only what is needed to normalize titles, as compact JSON: the case mode of the first letter of
+/// titles, the characters allowed in titles as a regular expression character class, and the
+/// namespaces.
+pub fn title_rules(
+    mut out: impl io::Write,
+    configuration_source: &extract::ConfigurationSource,
+) -> Result<(), Error> {
+    let extract::ConfigurationSource {
+        case,
+        legal_title_chars,
+        namespaces,
+        ..
+    } = configuration_source;
+    if case.is_none() || legal_title_chars.is_none() {
+        log::warn!("case mode or legal title characters not returned by the wiki, left null");
+    }
+    let namespaces: Vec<_> = namespaces
+        .iter()
+        .map(|ns| {
+            serde_json::json!({
+                "id": ns.id,
+                "name": ns.name,
+                "canonical": ns.canonical,
+                "aliases": ns.aliases,
+                "case": ns.case,
+            })
+        })
+        .collect();
+    let rules = serde_json::json!({
+        "case": case,
+        "legal_title_chars": legal_title_chars,
+        "namespaces": namespaces,
+    });
+    serde_json::to_writer(&mut out, &rules)?;
+    writeln!(out)?;
+    Ok(())
+}
+
+/// The extension tags become `hooks`, so that tests requiring other tags are skipped.  The rest of
+/// the configuration is set by the `language` option of each test, except for the protocols, which
+/// are given as a `!! config` line to copy into tests.
+pub fn parser_tests(
+    mut out: impl io::Write,
+    configuration_source: &extract::ConfigurationSource,
+) -> Result<(), Error> {
+    let extract::ConfigurationSource {
+        extension_tags,
+        notes,
+        protocols,
+        ..
+    } = configuration_source;
+
+    writeln!(out, "!! Version 2")?;
+    writeln!(out, "# Generated by fetch_mediawiki_configuration.")?;
+    for note in notes {
+        writeln!(out, "# Note: {}", note)?;
+    }
+    writeln!(out, "#")?;
+    writeln!(out, "# Protocols, for the `!! config` section of tests:")?;
+    writeln!(
+        out,
+        "# wgUrlProtocols={}",
+        serde_json::to_string(protocols)?
+    )?;
+    writeln!(out)?;
+    writeln!(out, "!! options")?;
+    writeln!(out, "version=2")?;
+    writeln!(out, "!! end")?;
+    if !extension_tags.is_empty() {
+        writeln!(out)?;
+        writeln!(out, "!! hooks")?;
+        for tag in extension_tags {
+            writeln!(out, "{}", tag)?;
+        }
+        writeln!(out, "!! endhooks")?;
+    }
 
     Ok(())
 }
@@ -0,0 +1,57 @@
+// Failures injected on purpose by the hidden `--inject-failure` and `--inject-latency` flags.
+
+use crate::api;
+use std::{thread, time};
+use thiserror::Error;
+
+// Stages at which `--inject-failure` makes a run fail, for testing the retries and alerting of
+// pipelines without a flaky network.
+#[derive(Clone, Copy, Debug, PartialEq, clap::ValueEnum)]
+pub enum Stage {
+    #[value(help = "Fail each request like a network error, which is retried")]
+    Fetch,
+    #[value(help = "Truncate each response, so that it cannot be decoded")]
+    Decode,
+    #[value(help = "Fail the extraction")]
+    Extract,
+}
+
+#[derive(Debug, Error)]
+#[error("injected {} failure", match .0 {
+    Stage::Fetch => "fetch",
+    Stage::Decode => "decode",
+    Stage::Extract => "extract",
+})]
+pub struct Failure(pub Stage);
+
+// Injects the failures of `Stage::Fetch` and `Stage::Decode`, and the latency, into the client.
+#[derive(Debug)]
+pub struct Hooks {
+    pub failure: Option<Stage>,
+    pub latency: Option<time::Duration>,
+}
+
+impl api::FetchHooks for Hooks {
+    fn before_fetch(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if let Some(latency) = self.latency {
+            log::warn!("injected latency of {:?}", latency);
+            thread::sleep(latency);
+        }
+        match self.failure {
+            Some(stage @ Stage::Fetch) => Err(Failure(stage).into()),
+            _ => Ok(()),
+        }
+    }
+
+    // Truncated like a response cut off by the network.
+    fn before_decode(&self, raw: &mut String) {
+        if self.failure == Some(Stage::Decode) {
+            log::warn!("injected decode failure, response truncated");
+            let mut len = raw.len() / 2;
+            while !raw.is_char_boundary(len) {
+                len -= 1;
+            }
+            raw.truncate(len);
+        }
+    }
+}
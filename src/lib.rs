@@ -0,0 +1,86 @@
+//! Fetch the configuration of a MediaWiki wiki from its API, and generate `parse_wiki_text`
+//! configuration code from it.  `fetch_configuration_source` fetches and extracts the
+//! configuration with the default options, and `generate_configuration_source` writes it as Rust
+//! code; `api`, `extract` and `generate` offer the steps with all their options.
+
+#![warn(missing_docs)]
+
+use thiserror::Error;
+
+pub mod api;
+pub mod extract;
+pub mod generate;
+
+pub use extract::{pcre, ConfigurationSource};
+pub use generate::{configuration_source as generate_configuration_source, CodeGenerator};
+
+/// The errors of the library functions.  Match on `category` rather than on the variants to tell
+/// network failures from malformed responses and extraction failures; new variants may be added.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// The HTTP client cannot be created, e.g. for lack of TLS support.
+    #[error("cannot create HTTP client: {0}")]
+    Client(#[source] reqwest::Error),
+    /// The siteinfo cannot be fetched, or the response is invalid.
+    #[error("API endpoint: {0}")]
+    Api(#[from] api::Error),
+    /// The configuration cannot be extracted from the response.
+    #[error("cannot extract configuration data: {0}")]
+    Extract(#[from] extract::Errors),
+}
+
+/// What failed, independently of the error type it is reported through.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ErrorCategory {
+    /// The domain, site path or a request parameter is invalid, or the client cannot be created.
+    Input,
+    /// The wiki cannot be reached, answers with an error status, or the request budget is used up.
+    Network,
+    /// The login was rejected or did not take effect.
+    Authentication,
+    /// The response is not valid JSON, lacks the query, or reports API errors.
+    Response,
+    /// The configuration cannot be extracted from a valid response.
+    Extraction,
+}
+
+impl Error {
+    /// What failed.
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            Error::Client(..) => ErrorCategory::Input,
+            Error::Api(e) => e.kind().category(),
+            Error::Extract(..) => ErrorCategory::Extraction,
+        }
+    }
+}
+
+/// Called with the name and the duration of each step of fetching and extracting, e.g. `request`
+/// or `link_trail`, to profile them, see `api::Options::timing` and `extract::Options::timing`.
+pub type Timing = fn(&'static str, std::time::Duration);
+
+/// Fetch and extract the configuration of the wiki at `domain` with the default options, as the
+/// command line does, e.g. from a build script.
+pub fn fetch_configuration_source(domain: &str) -> Result<ConfigurationSource, Error> {
+    let client = api::Client::new(api::Options::default(), Box::new(api::auth::NoAuth))
+        .map_err(Error::Client)?;
+    let fetched = client.fetch_query(domain)?;
+    Ok(extract::configuration_source(
+        &fetched.query,
+        &extract::Options::default(),
+    )?)
+}
+
+/// As `fetch_configuration_source`, with the async client of the `async` feature.
+#[cfg(feature = "async")]
+pub async fn fetch_configuration_source_async(domain: &str) -> Result<ConfigurationSource, Error> {
+    let client =
+        api::asynchronous::AsyncClient::new(api::Options::default()).map_err(Error::Client)?;
+    let fetched = client.fetch_async(domain).await?;
+    Ok(extract::configuration_source(
+        &fetched.query,
+        &extract::Options::default(),
+    )?)
+}
@@ -0,0 +1,130 @@
+use serde::{Deserialize, Serialize};
+use sha2::Digest;
+use std::{collections, fs, io, path, time};
+use thiserror::Error;
+
+// The siteinfo responses the outputs were last generated from, by wiki, so that `--locked` can
+// tell when a wiki changed since, like `Cargo.lock` for dependencies.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Lockfile {
+    #[serde(default, rename = "wiki")]
+    pub wikis: collections::BTreeMap<String, Locked>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Locked {
+    // The MediaWiki version, e.g. `MediaWiki 1.42.0-wmf.5`.
+    pub generator: Option<String>,
+    // In seconds since the Unix epoch.
+    pub fetched_at: u64,
+    pub siteinfo_sha256: String,
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("lockfile {path:?}: {source}")]
+    Io {
+        path: path::PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("lockfile {path:?}: {source}")]
+    Parse {
+        path: path::PathBuf,
+        #[source]
+        source: toml::de::Error,
+    },
+    #[error("lockfile {path:?}: {source}")]
+    Serialize {
+        path: path::PathBuf,
+        #[source]
+        source: toml::ser::Error,
+    },
+    #[error("{0:?} is not in the lockfile")]
+    Missing(String),
+    #[error(
+        "siteinfo of {domain:?} changed since it was locked ({}, fetched at {}), now {}",
+        locked.generator.as_deref().unwrap_or("unknown version"),
+        locked.fetched_at,
+        live.generator.as_deref().unwrap_or("unknown version")
+    )]
+    Changed {
+        domain: String,
+        locked: Box<Locked>,
+        live: Box<Locked>,
+    },
+}
+
+impl Lockfile {
+    pub fn load(path: &path::Path) -> Result<Self, Error> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                log::debug!("lockfile not found, start empty: {:?}", path);
+                return Ok(Self::default());
+            }
+            Err(source) => {
+                return Err(Error::Io {
+                    path: path.to_owned(),
+                    source,
+                })
+            }
+        };
+        toml::from_str(&contents).map_err(|source| Error::Parse {
+            path: path.to_owned(),
+            source,
+        })
+    }
+
+    pub fn save(&self, path: &path::Path) -> Result<(), Error> {
+        let contents = toml::to_string_pretty(self).map_err(|source| Error::Serialize {
+            path: path.to_owned(),
+            source,
+        })?;
+        fs::write(path, contents).map_err(|source| Error::Io {
+            path: path.to_owned(),
+            source,
+        })
+    }
+
+    // Whether the siteinfo of `domain` is the one locked.
+    pub fn verify(&self, domain: &str, live: &Locked) -> Result<(), Error> {
+        match self.wikis.get(domain) {
+            None => Err(Error::Missing(domain.to_owned())),
+            Some(locked) if locked.siteinfo_sha256 == live.siteinfo_sha256 => Ok(()),
+            Some(locked) => Err(Error::Changed {
+                domain: domain.to_owned(),
+                locked: Box::new(locked.clone()),
+                live: Box::new(live.clone()),
+            }),
+        }
+    }
+
+    // Keep the entry of `domain` as it was if its siteinfo did not change, so that the lockfile
+    // only changes with the wikis.  Returns whether it changed.
+    pub fn update(&mut self, domain: &str, live: Locked) -> bool {
+        match self.wikis.get(domain) {
+            Some(locked) if locked.siteinfo_sha256 == live.siteinfo_sha256 => false,
+            _ => {
+                log::info!("lock siteinfo of {:?}: {}", domain, live.siteinfo_sha256);
+                self.wikis.insert(domain.to_owned(), live);
+                true
+            }
+        }
+    }
+}
+
+impl Locked {
+    pub fn new(raw: &str, generator: Option<String>, fetched_at: time::SystemTime) -> Self {
+        Self {
+            generator,
+            fetched_at: fetched_at
+                .duration_since(time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            siteinfo_sha256: hex::encode(sha2::Sha256::digest(raw)),
+        }
+    }
+}
@@ -1,76 +1,88 @@
-use err_derive::Error;
-use std::{env, io, process};
+use clap::{CommandFactory, Parser};
+use fetch_mediawiki_configuration::{api, extract, generate};
+use std::{collections, ffi, fs, io, io::Write, path, process, time};
+use thiserror::Error;
 
-mod api;
-mod extract;
-mod generate;
-
-#[derive(Debug)]
-struct Args {
-    domain: String,
-    log_level: log::LevelFilter,
-}
+mod bundle;
+mod check;
+mod cli;
+mod crawl;
+mod diff;
+mod inject;
+mod lock;
+mod protocol;
+mod replay;
+mod rpc;
+mod sign;
+mod state;
+mod summary;
+mod sync;
 
 #[derive(Debug, Error)]
 enum Error {
-    #[error(display = "{}", _0)]
-    Clap(#[error(no_from, source)] clap::Error),
-    #[error(display = "{}", _0)]
-    ClapDisplayed(#[error(no_from, source)] clap::Error),
-    #[error(display = "I/O error: {}", _0)]
-    Io(#[error(source)] io::Error),
-    #[error(display = "cannot extract configuration data: {}", _0)]
-    Extract(#[error(source)] extract::Error),
-    #[error(display = "API endpoint: {}", _0)]
-    Api(#[error(source)] api::Error),
-}
-
-impl Args {
-    fn parse() -> Result<Self, clap::Error> {
-        use log::LevelFilter::*;
-
-        let log_levels: Vec<_> = [Off, Error, Warn, Info, Debug, Trace]
-            .iter()
-            .map(|l| l.as_str())
-            .collect();
-
-        let matches = clap::App::new(clap::crate_name!())
-            .about(clap::crate_description!())
-            .long_about(
-                "\
-                Fetch the site configuration of a MediaWiki based wiki, and output rust code for \
-                creating a configuration for `parse_wiki_text` specific to that wiki.  Write \
-                generated code to stdout, as a constant expression of type \
-                `parse_wiki_text::ConfigurationSource`.  Write log messages to stderr.\
-                ",
-            )
-            .version(clap::crate_version!())
-            .arg(
-                clap::Arg::with_name("domain")
-                    .help("The domain name of the wiki (e.g. `en.wikipedia.org`)")
-                    .required(true),
-            )
-            .arg(
-                clap::Arg::with_name("log-level")
-                    .long("log-level")
-                    .help("Maximum log level")
-                    .case_insensitive(true)
-                    .default_value(Info.as_str())
-                    .possible_values(&log_levels),
-            )
-            .get_matches_safe()?;
-
-        let domain = clap::value_t!(matches.value_of("domain"), _)?;
-        let log_level = clap::value_t!(matches.value_of("log-level"), _)?;
-        Ok(Self { domain, log_level })
-    }
+    #[error("{0}")]
+    Clap(#[source] clap::Error),
+    #[error("{0}")]
+    ClapDisplayed(#[source] clap::Error),
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("cannot write {0:?}: {1}")]
+    Write(path::PathBuf, #[source] io::Error),
+    #[error("cannot extract configuration data: {0}")]
+    Extract(#[from] extract::Errors),
+    #[error("API endpoint: {0}")]
+    Api(#[from] api::Error),
+    #[error("cannot create HTTP client: {0}")]
+    Client(#[source] reqwest::Error),
+    #[error("cannot read domains from {0:?}: {1}")]
+    Domains(path::PathBuf, #[source] io::Error),
+    #[error("cannot check {0:?}: {1}")]
+    Check(path::PathBuf, #[source] check::Error),
+    #[error("{0:?}: {1} fields out of date")]
+    Stale(path::PathBuf, usize),
+    #[error("generated code is too large ({0} > {1} bytes)")]
+    OutputTooLarge(u64, u64),
+    #[error("cannot crawl: {0}")]
+    Crawl(#[from] crawl::Error),
+    #[error("manifest {0}")]
+    Sync(#[from] sync::Error),
+    #[error("signature: {0}")]
+    Sign(#[from] sign::Error),
+    #[error("snapshot {0:?}: {1}")]
+    Snapshot(path::PathBuf, #[source] diff::SnapshotError),
+    #[error("cannot generate code: {0}")]
+    Generate(#[from] generate::Error),
+    #[error("cannot extract configuration data: {0}")]
+    Injected(#[from] inject::Failure),
+    #[error("{0}")]
+    State(#[from] state::Error),
+    #[error("{0}")]
+    Lock(#[from] lock::Error),
+    #[error("{0} of {1} wikis failed")]
+    Failed(usize, usize),
+    #[error("no user cache directory")]
+    NoCacheDirectory,
+    #[error("cache {0:?}: {1}")]
+    Cache(path::PathBuf, #[source] io::Error),
+    #[error("{0:?} is not cached")]
+    NotCached(String),
+    #[error("cannot read cassette {0:?}: {1}")]
+    Cassette(path::PathBuf, #[source] io::Error),
+    #[error("cannot read CA certificates {0:?}: {1}")]
+    CaCert(path::PathBuf, #[source] io::Error),
+    #[error("cannot read client certificate or key {0:?}: {1}")]
+    ClientCert(path::PathBuf, #[source] io::Error),
+    #[error("cannot read cookies {0:?}: {1}")]
+    Cookies(path::PathBuf, #[source] io::Error),
 }
 
 impl From<clap::Error> for Error {
     fn from(e: clap::Error) -> Self {
-        use clap::ErrorKind::*;
-        match e.kind {
-            HelpDisplayed | VersionDisplayed => Self::ClapDisplayed(e),
+        use clap::error::ErrorKind::*;
+        match e.kind() {
+            DisplayHelp | DisplayHelpOnMissingArgumentOrSubcommand | DisplayVersion => {
+                Self::ClapDisplayed(e)
+            }
             _ => Self::Clap(e),
         }
     }
@@ -80,7 +92,11 @@ fn main() {
     process::exit(match run() {
         Ok(()) => 0,
         Err(Error::ClapDisplayed(e)) => {
-            print!("{}", e);
+            if cli::stdout_only_output_requested() {
+                eprint!("{}", e);
+            } else {
+                print!("{}", e);
+            }
             0
         }
         Err(Error::Clap(e)) => {
@@ -89,29 +105,1192 @@ fn main() {
         }
         Err(e) => {
             log::error!("{}", e);
+            log_error_details(&e);
             1
         }
     });
 }
 
 fn run() -> Result<(), Error> {
-    let args = Args::parse()?;
+    let start = time::Instant::now();
+    let args = cli::Args::try_parse()?;
     log_initialize(args.log_level);
 
-    log::info!("connect to API at wiki domain: {:?} ...", args.domain);
-    let query = api::fetch_query(&args.domain)?;
+    if let Some(cli::Command::Cache { action }) = &args.command {
+        return cache(action, &args);
+    }
+    if args.man_page {
+        cli::man_page(io::stdout())?;
+        return Ok(());
+    }
+    if let Some(artifact) = &args.verify_signature {
+        let key = args.verify_key.as_deref().unwrap();
+        return sign::verify(key, artifact).map_err(Into::into);
+    }
+    if let [old, new] = args.changelog.as_slice() {
+        return changelog(old, new, &args);
+    }
+    for path in &args.compact_snapshots {
+        diff::compact_snapshot(path).map_err(|e| Error::Snapshot(path.clone(), e))?;
+    }
+    if !args.compact_snapshots.is_empty() {
+        return Ok(());
+    }
+    if let Some(dir) = &args.replay_serve {
+        return replay::serve(dir, &args.replay_address).map_err(Into::into);
+    }
+
+    if (args.from_cache_only || args.frozen) && !args.reads_locally() && args.cache().is_none() {
+        return Err(Error::NoCacheDirectory);
+    }
+    let cassette = match &args.replay_cassette {
+        Some(path) => {
+            let cassette = api::cassette::Cassette::load(path)
+                .map_err(|e| Error::Cassette(path.to_owned(), e))?;
+            Some(std::sync::Arc::new(cassette))
+        }
+        None => None,
+    };
+    let mut root_certificates = Vec::new();
+    for path in &args.ca_cert {
+        let certificates =
+            api::read_certificates(path).map_err(|e| Error::CaCert(path.clone(), e))?;
+        root_certificates.extend(certificates);
+    }
+    let identity = match (&args.client_cert, &args.client_key) {
+        (Some(certificate), Some(key)) => Some(
+            api::read_identity(certificate, key).map_err(|(path, e)| Error::ClientCert(path, e))?,
+        ),
+        _ => None,
+    };
+    if let Some(codings) = &args.compression {
+        if codings.len() > 1 && codings.contains(&api::Compression::None) {
+            return Err(cli::Args::command()
+                .error(
+                    clap::error::ErrorKind::ArgumentConflict,
+                    "`--compression none` cannot be combined with other codings",
+                )
+                .into());
+        }
+    }
+    let cookies = match &args.cookies {
+        Some(path) => {
+            let jar = api::read_cookies(path).map_err(|e| Error::Cookies(path.clone(), e))?;
+            Some(std::sync::Arc::new(jar))
+        }
+        None => None,
+    };
+    if args.insecure {
+        log::warn!("TLS certificates are not verified, responses may have been tampered with");
+    }
+    let api_options = api::Options {
+        api_path: args.api_path.clone(),
+        as_of: args.as_of.clone(),
+        assert: args.assert,
+        cache: args.cache(),
+        cassette,
+        extensions: args.stdin_protocol
+            || args.rpc
+            || args.sync.is_some()
+            || args
+                .outputs()
+                .iter()
+                .any(|(format, ..)| matches!(format, cli::Format::Json)),
+        fixture: args.fixture.clone(),
+        from_file: args.from_file.clone(),
+        from_dump: args.from_dump.clone(),
+        from_local_settings: args.from_local_settings.clone(),
+        post: args.post,
+        preflight: args.preflight,
+        hooks: if args.inject_failure.is_some() || args.inject_latency.is_some() {
+            Some(std::sync::Arc::new(inject::Hooks {
+                failure: args.inject_failure,
+                latency: args.inject_latency,
+            }))
+        } else {
+            None
+        },
+        probe: args.probe,
+        replay: args.replay.clone(),
+        retries: args.retries,
+        retry_all_errors: args.retry_all_errors,
+        timeout: Some(args.timeout),
+        connect_timeout: Some(args.connect_timeout),
+        proxy: args.proxy.clone(),
+        root_certificates,
+        insecure: args.insecure,
+        identity,
+        cookies,
+        headers: args.header.clone(),
+        compression: args.compression.clone(),
+        max_redirects: if args.no_redirects {
+            Some(0)
+        } else {
+            args.max_redirects
+        },
+        max_requests: args.max_requests,
+        deadline: args.deadline.map(|deadline| start + deadline),
+        raw_params: args.raw_param.clone(),
+        record: args.record.clone(),
+        timing: Some(summary::timing),
+    };
+    let client = api::Client::new(api_options, args.auth_provider()).map_err(Error::Client)?;
+
+    if args.telemetry.is_some() {
+        summary::collect_spans();
+    }
+    let result = fetch(&client, &args);
+    if let Some(max) = args.max_requests {
+        log::info!("HTTP requests: {} of {}", client.requests(), max);
+    }
+    let show_summary = match args.summary {
+        cli::SummaryMode::Auto => summary::is_terminal(),
+        cli::SummaryMode::Always => true,
+        cli::SummaryMode::Never => false,
+    };
+    if show_summary {
+        summary::print(client.requests(), start.elapsed())?;
+    }
+    if let Some(path) = &args.telemetry {
+        log::info!("write telemetry to {:?} ...", path);
+        let out = io::BufWriter::new(fs::File::create(path)?);
+        if path.extension().is_some_and(|e| e == "folded") {
+            summary::write_folded(out)?;
+        } else {
+            summary::write_telemetry(out, client.requests(), start.elapsed())?;
+        }
+    }
+    result
+}
+
+fn cache(action: &cli::CacheAction, args: &cli::Args) -> Result<(), Error> {
+    let cache = api::cache::Cache {
+        directory: api::cache::Cache::default_directory().ok_or(Error::NoCacheDirectory)?,
+        ttl: args.cache_ttl,
+        refresh: false,
+        offline: false,
+    };
+    let error = |path: &path::Path| {
+        let path = path.to_owned();
+        move |e| Error::Cache(path, e)
+    };
+    let freshness = |entry: &api::cache::Entry| {
+        if entry.age() > cache.ttl {
+            "expired"
+        } else {
+            "fresh"
+        }
+    };
+    let mut out = report_stream(args);
+    match action {
+        cli::CacheAction::List => {
+            let paths = cache.paths().map_err(error(&cache.directory))?;
+            let entries: Vec<_> = paths
+                .into_iter()
+                .map(|path| {
+                    let entry = api::cache::Entry::load(&path);
+                    (path, entry)
+                })
+                .collect();
+            let width = entries
+                .iter()
+                .filter_map(|(_, entry)| Some(entry.as_ref().ok()?.domain.len()))
+                .max()
+                .unwrap_or(0);
+            for (path, entry) in entries {
+                match entry {
+                    Ok(entry) => writeln!(
+                        out,
+                        "{:<width$}  {:>4} ago  {:<7}  {} bytes",
+                        entry.domain,
+                        age(entry.age()),
+                        freshness(&entry),
+                        entry.raw.len(),
+                        width = width
+                    )?,
+                    Err(e) => log::warn!("cannot read cached siteinfo {:?}: {}", path, e),
+                }
+            }
+        }
+        cli::CacheAction::Inspect { domain } => {
+            let key = api::site::key(domain);
+            let path = cache.path(&key);
+            let entry = match api::cache::Entry::load(&path) {
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                    return Err(Error::NotCached(key));
+                }
+                entry => entry.map_err(error(&path))?,
+            };
+            let general: serde_json::Value = serde_json::from_str(&entry.raw)
+                .ok()
+                .and_then(|response: serde_json::Value| response.pointer("/query/general").cloned())
+                .unwrap_or_default();
+            let fetched_at = time::UNIX_EPOCH + time::Duration::from_secs(entry.fetched_at);
+            for (name, value) in [
+                ("domain", entry.domain.clone()),
+                ("file", path.display().to_string()),
+                ("request", entry.request.to_string()),
+                ("url", entry.url.to_string()),
+                (
+                    "sitename",
+                    general["sitename"].as_str().unwrap_or("").to_owned(),
+                ),
+                (
+                    "generator",
+                    general["generator"].as_str().unwrap_or("").to_owned(),
+                ),
+                (
+                    "fetched",
+                    format!(
+                        "{} ({} ago, {})",
+                        cli::utc_date(fetched_at),
+                        age(entry.age()),
+                        freshness(&entry)
+                    ),
+                ),
+                ("size", format!("{} bytes", entry.raw.len())),
+            ] {
+                writeln!(out, "{:<10} {}", name, value)?;
+            }
+        }
+        cli::CacheAction::Clear { domains } if domains.is_empty() => {
+            let count = cache.clear().map_err(error(&cache.directory))?;
+            log::info!(
+                "removed {} cached responses from {:?}",
+                count,
+                cache.directory
+            );
+        }
+        cli::CacheAction::Clear { domains } => {
+            for domain in domains {
+                let key = api::site::key(domain);
+                if cache.remove(&key).map_err(error(&cache.path(&key)))? {
+                    log::info!("removed cached response of {:?}", key);
+                } else {
+                    log::warn!("{:?} is not cached", key);
+                }
+            }
+        }
+        cli::CacheAction::Export { path, domains } => {
+            let keys: Vec<_> = domains.iter().map(|d| api::site::key(d)).collect();
+            if let Some(key) = keys.iter().find(|key| !cache.path(key).exists()) {
+                return Err(Error::NotCached(key.clone()));
+            }
+            let count = bundle::export(&cache, path, &keys).map_err(error(path))?;
+            log::info!("exported {} cached responses to {:?}", count, path);
+        }
+        cli::CacheAction::Import { path } => {
+            let (added, kept) = bundle::import(&cache, path).map_err(error(path))?;
+            log::info!(
+                "imported {} cached responses from {:?}, kept {} more recent",
+                added,
+                path,
+                kept
+            );
+        }
+    }
+    Ok(())
+}
+
+// In the largest unit of at least one, e.g. `3h`.
+fn age(age: time::Duration) -> String {
+    let seconds = age.as_secs();
+    match [(86400, "d"), (3600, "h"), (60, "m")]
+        .iter()
+        .find(|(unit, _)| seconds >= *unit)
+    {
+        Some((unit, suffix)) => format!("{}{}", seconds / unit, suffix),
+        None => format!("{}s", seconds),
+    }
+}
+
+// The domain of the wiki of a saved response: its server name, or else the name of the file, e.g.
+// `en.wikipedia.org` of `en.wikipedia.org.json`.  The file is read again, and any error reported,
+// when fetching.
+fn saved_domain(path: &path::Path) -> String {
+    let servername = fs::read_to_string(path)
+        .ok()
+        .and_then(|raw| serde_json::from_str::<serde_json::Value>(&raw).ok())
+        .and_then(|response| {
+            let servername = response.pointer("/query/general/servername")?;
+            Some(servername.as_str()?.to_owned())
+        });
+    servername.unwrap_or_else(|| {
+        let stem = path.file_stem().unwrap_or_default();
+        stem.to_string_lossy().into_owned()
+    })
+}
+
+fn fetch(client: &api::Client, args: &cli::Args) -> Result<(), Error> {
+    if args.stdin_protocol {
+        return protocol::run(client, args, io::stdin().lock(), io::stdout().lock())
+            .map_err(Into::into);
+    }
+    if !args.crawl.is_empty() {
+        return crawl::run(client, &args.crawl, io::stdout().lock()).map_err(Into::into);
+    }
+    if args.rpc {
+        return rpc::Server::new(client, args)
+            .run(io::stdin().lock(), io::stdout().lock())
+            .map_err(Into::into);
+    }
+    if let Some(path) = &args.sync {
+        let manifest = sync::Manifest::load(path)?;
+        let jobs = manifest.jobs(path, args);
+        let mut staging = sync::Staging::default();
+        let mut by_language = collections::BTreeMap::new();
+        let mut by_language_target = None;
+        let mut updated = 0;
+        let mut pending = Pending::default();
+        for (i, job) in jobs.iter().enumerate() {
+            log::info!("wiki {}/{}: {:?}", i + 1, jobs.len(), job.domain);
+            let targets = job.args.targets();
+            let outputs: Vec<_> = match job.format {
+                cli::Format::Rust => targets
+                    .iter()
+                    .map(|&target| {
+                        let output = cli::target_path(&job.output, &targets, target);
+                        (job.format, target, Some(output))
+                    })
+                    .collect(),
+                _ => vec![(job.format, targets[0], Some(job.output.clone()))],
+            };
+            let generated =
+                fetch_domain(client, &job.args, &job.domain, &outputs, Some(&mut staging))?;
+            updated += usize::from(generated.updated);
+            pending.extend(generated.pending);
+            let configuration_source = generated.configuration_source;
+            if args.partial_commit {
+                staging.commit()?;
+                record(args, &mut pending)?;
+            }
+            if let (cli::Format::Rust, Some(language)) = (job.format, configuration_source.language)
+            {
+                if job.args.const_name.is_some()
+                    || !matches!(job.args.emit, generate::Emit::Expression)
+                {
+                    log::warn!(
+                        "{:?} is an item, not an expression, left out of by_language",
+                        job.output
+                    );
+                    continue;
+                }
+                let target = *by_language_target.get_or_insert(targets[0]);
+                if !targets.contains(&target) {
+                    log::warn!(
+                        "{:?} is generated for {:?}, not {:?}, left out of by_language",
+                        job.output,
+                        targets,
+                        target
+                    );
+                    continue;
+                }
+                let output = cli::target_path(&job.output, &targets, target);
+                if let Some(previous) = by_language.insert(language.clone(), output) {
+                    log::warn!(
+                        "language {:?} of {:?} already generated to {:?}, replaced",
+                        language,
+                        job.domain,
+                        previous
+                    );
+                }
+            }
+        }
+        log::info!("synchronized {} wikis", jobs.len());
+        if args.incremental {
+            log::info!(
+                "{} wikis updated, {} unchanged",
+                updated,
+                jobs.len() - updated
+            );
+        }
+        if let Some(by_language_path) = manifest.by_language(path) {
+            let staged = staging.stage(&by_language_path)?;
+            write_by_language(
+                &by_language_path,
+                &staged,
+                by_language,
+                by_language_target.unwrap_or_default(),
+            )?;
+        }
+        staging.commit()?;
+        record(args, &mut pending)?;
+        return Ok(());
+    }
+    let mut domains = args.domain.clone();
+    if let Some(path) = &args.domains_from {
+        domains.extend(read_domains(path).map_err(|e| Error::Domains(path.clone(), e))?);
+        if domains.is_empty() {
+            return Err(cli::Args::command()
+                .error(
+                    clap::error::ErrorKind::InvalidValue,
+                    format!("no domains in {:?}", path),
+                )
+                .into());
+        }
+    }
+    if domains.is_empty() {
+        domains.extend(args.fixture.clone());
+    }
+    if let (true, Some(path)) = (domains.is_empty(), &args.from_file) {
+        domains.push(saved_domain(path));
+    }
+    if let (true, Some(path)) = (domains.is_empty(), &args.from_dump) {
+        // The file is read again, and any error reported, when fetching.
+        let domain = api::dump::SiteInfo::read(path)
+            .ok()
+            .and_then(|site_info| site_info.domain());
+        domains.push(domain.unwrap_or_else(|| {
+            let stem = path.file_stem().unwrap_or_default();
+            stem.to_string_lossy().into_owned()
+        }));
+    }
+    if let (true, Some(path)) = (domains.is_empty(), &args.from_local_settings) {
+        // As for dumps.
+        let domain = api::local_settings::LocalSettings::read(path)
+            .ok()
+            .and_then(|settings| settings.domain());
+        domains.push(domain.unwrap_or_else(|| {
+            let stem = path.file_stem().unwrap_or_default();
+            stem.to_string_lossy().into_owned()
+        }));
+    }
+    let domains: Vec<_> = domains.iter().map(String::as_str).collect();
+    if let Some(path) = &args.check {
+        let domain = match domains.as_slice() {
+            [domain] => domain,
+            _ => {
+                return Err(cli::Args::command()
+                    .error(
+                        clap::error::ErrorKind::ArgumentConflict,
+                        "`--check` checks the file of a single domain",
+                    )
+                    .into())
+            }
+        };
+        let (configuration_source, _) = extract_domain(client, args, domain)?;
+        log::info!("check {:?} ...", path);
+        let findings =
+            check::check(path, &configuration_source).map_err(|e| Error::Check(path.clone(), e))?;
+        check::write(report_stream(args), args.check_format, path, &findings)?;
+        if !findings.is_empty() {
+            return Err(Error::Stale(path.clone(), findings.len()));
+        }
+        return Ok(());
+    }
+    let outputs = args.outputs();
+    if args.targets().len() > 1
+        && outputs
+            .iter()
+            .any(|(format, _, path)| matches!(format, cli::Format::Rust) && path.is_none())
+    {
+        return Err(cli::Args::command()
+            .error(
+                clap::error::ErrorKind::ArgumentConflict,
+                "several targets require Rust output files, one is written per target",
+            )
+            .into());
+    }
+    match domains.as_slice() {
+        [domain] if !args.registry => {
+            let mut generated = fetch_domain(client, args, domain, &outputs, None)?;
+            record(args, &mut generated.pending)
+        }
+        domains => fetch_domains(client, args, domains, &outputs),
+    }
+}
+
+// Blank lines and comments are skipped.
+fn read_domains(path: &path::Path) -> Result<Vec<String>, io::Error> {
+    let raw = if path == path::Path::new("-") {
+        io::read_to_string(io::stdin().lock())?
+    } else {
+        fs::read_to_string(path)?
+    };
+    Ok(raw
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_owned)
+        .collect())
+}
+
+// Each output file is written once per domain, with the domain in its name, and Rust output to
+// stdout as a module per domain, all fetched with the same client.
+fn fetch_domains(
+    client: &api::Client,
+    args: &cli::Args,
+    domains: &[&str],
+    outputs: &[(cli::Format, generate::Target, Option<path::PathBuf>)],
+) -> Result<(), Error> {
+    if outputs
+        .iter()
+        .any(|(format, _, path)| !matches!(format, cli::Format::Rust) && path.is_none())
+    {
+        return Err(cli::Args::command()
+            .error(
+                clap::error::ErrorKind::ArgumentConflict,
+                "several domains require output files, one is written per domain, except for \
+                    Rust output to stdout",
+            )
+            .into());
+    }
+    let stdout = outputs.iter().any(|(_, _, path)| path.is_none());
+    if args.dump_raw_stdout() {
+        return Err(cli::Args::command()
+            .error(
+                clap::error::ErrorKind::ArgumentConflict,
+                "the siteinfo responses of several domains require a file, one is written per \
+                    domain",
+            )
+            .into());
+    }
+    if args.registry && !stdout {
+        return Err(cli::Args::command()
+            .error(
+                clap::error::ErrorKind::ArgumentConflict,
+                "a registry requires Rust output to stdout, of a module per domain",
+            )
+            .into());
+    }
+    let results = parallel(args.jobs, !args.keep_going, domains, |i, domain| {
+        log::info!("wiki {}/{}: {:?}", i + 1, domains.len(), domain);
+        let args = args.for_domain(domain);
+        let outputs: Vec<_> = outputs
+            .iter()
+            .filter_map(|(format, target, path)| {
+                let path = path.as_deref()?;
+                // Unless named by the wiki in `--name-template`.
+                let named = args.out_dir.is_some()
+                    && (args.name_template.contains("{domain}")
+                        || args.name_template.contains("{lang}"));
+                let path = if named {
+                    path.to_owned()
+                } else {
+                    cli::domain_path(path, domain)
+                };
+                Some((*format, *target, Some(path)))
+            })
+            .collect();
+        fetch_domain(client, &args, domain, &outputs, None)
+    });
+    let mut modules = Vec::new();
+    let mut updated = 0;
+    let mut failures = Vec::new();
+    for (domain, result) in domains.iter().zip(results) {
+        // Only wikis after a failed one are not fetched, and the failure is returned first.
+        let mut generated = match result.expect("wiki not fetched before a failure") {
+            Ok(generated) => generated,
+            Err(e) if args.keep_going => {
+                log::error!("{}: {}", domain, e);
+                log_error_details(&e);
+                failures.push((*domain, e));
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        let args = args.for_domain(domain);
+        updated += usize::from(generated.updated);
+        record(&args, &mut generated.pending)?;
+        let configuration_source = generated.configuration_source;
+        if stdout {
+            let args = module_args(&args);
+            let mut out = Vec::new();
+            summary::phase("generate", || {
+                cli::generate(cli::Format::Rust, &mut out, &configuration_source, &args)
+            })?;
+            modules.push((api::site::key(domain), out));
+        }
+    }
+    if stdout {
+        let mut out = Vec::new();
+        generate::modules(&mut out, &modules, args.visibility)?;
+        if args.registry {
+            let domains: Vec<_> = modules.into_iter().map(|(domain, _)| domain).collect();
+            let options = module_args(args).generate_options();
+            generate::registry(&mut out, &domains, &options)?;
+        }
+        log::info!("write Rust output to stdout ...");
+        io::stdout().lock().write_all(&out)?;
+    }
+    if args.incremental {
+        log::info!(
+            "{} wikis updated, {} unchanged",
+            updated,
+            domains.len() - updated - failures.len()
+        );
+    }
+    if args.keep_going {
+        outcomes(io::stderr().lock(), domains, &failures)?;
+        if !failures.is_empty() {
+            return Err(Error::Failed(failures.len(), domains.len()));
+        }
+    }
+    Ok(())
+}
+
+// A line per wiki: whether it succeeded, or else the category of its error and the error.
+fn outcomes(
+    mut out: impl io::Write,
+    domains: &[&str],
+    failures: &[(&str, Error)],
+) -> Result<(), io::Error> {
+    let width = domains.iter().map(|domain| domain.len()).max().unwrap_or(0);
+    for domain in domains {
+        match failures.iter().find(|(failed, _)| failed == domain) {
+            Some((_, e)) => writeln!(
+                out,
+                "{:<width$}  failed  {:<10}  {}",
+                domain,
+                category(e),
+                e,
+                width = width
+            )?,
+            None => writeln!(out, "{:<width$}  ok", domain, width = width)?,
+        }
+    }
+    Ok(())
+}
+
+// As by `fetch_mediawiki_configuration::ErrorCategory`, or else what failed after extraction.
+fn category(e: &Error) -> String {
+    use fetch_mediawiki_configuration::ErrorCategory;
+    let category = match e {
+        Error::Api(e) => e.kind().category(),
+        Error::Client(..) => ErrorCategory::Input,
+        Error::Extract(..) | Error::Injected(..) => ErrorCategory::Extraction,
+        Error::Lock(..) => return "lock".to_owned(),
+        Error::Generate(..) => return "generation".to_owned(),
+        _ => return "output".to_owned(),
+    };
+    format!("{:?}", category).to_lowercase()
+}
+
+// `f` of each of `items` and its index, on `jobs` threads, in the order of `items`.  Once it fails,
+// with `stop`, the items not yet started are left out, as `None`.
+fn parallel<T: Send>(
+    jobs: usize,
+    stop: bool,
+    items: &[&str],
+    f: impl Fn(usize, &str) -> Result<T, Error> + Sync,
+) -> Vec<Option<Result<T, Error>>> {
+    use std::sync::{atomic, Mutex};
+    let next = atomic::AtomicUsize::new(0);
+    let failed = atomic::AtomicBool::new(false);
+    let results: Vec<_> = items.iter().map(|_| Mutex::new(None)).collect();
+    std::thread::scope(|scope| {
+        for _ in 0..jobs.min(items.len()) {
+            scope.spawn(|| {
+                while !failed.load(atomic::Ordering::SeqCst) {
+                    let i = next.fetch_add(1, atomic::Ordering::SeqCst);
+                    let Some(item) = items.get(i) else { break };
+                    let result = f(i, item);
+                    if stop && result.is_err() {
+                        failed.store(true, atomic::Ordering::SeqCst);
+                    }
+                    *results[i].lock().unwrap() = Some(result);
+                }
+            });
+        }
+    });
+    results
+        .into_iter()
+        .map(|result| result.into_inner().unwrap())
+        .collect()
+}
+
+// The arguments of the code of a wiki in a module of several.
+fn module_args(args: &cli::Args) -> cli::Args {
+    let mut args = args.for_target(args.targets()[0]);
+    // A bare expression cannot be in a module, nor be found by a registry, and neither can the
+    // private source of a constructor.
+    let unnamed = match args.emit {
+        generate::Emit::Expression => true,
+        generate::Emit::Constructor => args.registry,
+        generate::Emit::Delta => false,
+    };
+    if args.const_name.is_none() && unnamed {
+        let name = args
+            .generate_options()
+            .naming
+            .constant("configuration_source");
+        args.const_name = Some(name.to_string());
+    }
+    args
+}
+
+fn extract_domain(
+    client: &api::Client,
+    args: &cli::Args,
+    domain: &str,
+) -> Result<(extract::ConfigurationSource, api::Fetched), Error> {
+    log::info!("connect to API at wiki domain: {:?} ...", domain);
+    let fetched = summary::phase("fetch", || client.fetch_query(domain))?;
+    if let (true, Some(path)) = (args.locked || args.frozen, &args.lockfile) {
+        let live = lock::Locked::new(
+            &fetched.raw,
+            fetched.query.general.generator.clone(),
+            time::SystemTime::now(),
+        );
+        lock::Lockfile::load(path)?.verify(&api::site::key(domain), &live)?;
+    }
+    if args.pin_endpoint {
+        let path = state_path(args)?;
+        let mut state = state::State::load(&path)?;
+        if state.pin_endpoint(&api::site::key(domain), &fetched.url) {
+            state.save(&path)?;
+        }
+    }
+
+    let fetched_at = time::SystemTime::now();
+
     log::info!("extract configuration data from response ...");
-    let configuration_source = extract::configuration_source(&query)?;
+    if args.inject_failure == Some(inject::Stage::Extract) {
+        return Err(inject::Failure(inject::Stage::Extract).into());
+    }
+    let configuration_source = summary::phase("extract", || {
+        let options = extract::Options {
+            timing: Some(summary::timing),
+            ..args.extract_options()
+        };
+        extract::configuration_source(&fetched.query, &options)
+    })?;
+    if let Some(path) = &args.metadata_json {
+        write_metadata(
+            path,
+            domain,
+            &fetched,
+            fetched_at,
+            &configuration_source,
+            args.create_dirs,
+        )?;
+    }
+    if let Some(path) = &args.dump_raw {
+        dump_raw(path, &fetched, args)?;
+    }
+    summary::wiki(format!(
+        "{}: {} namespaces, {} magic words, {} extension tags, {} protocols, {} link trail \
+            characters",
+        domain,
+        configuration_source.namespaces.len(),
+        configuration_source.magic_words.len(),
+        configuration_source.extension_tags.len(),
+        configuration_source.protocols.len(),
+        configuration_source.link_trail.len()
+    ));
+    Ok((configuration_source, fetched))
+}
+
+fn state_path(args: &cli::Args) -> Result<path::PathBuf, Error> {
+    match &args.state_file {
+        Some(path) => Ok(path.clone()),
+        None => Ok(state::State::default_path()?),
+    }
+}
+
+struct Generated {
+    configuration_source: extract::ConfigurationSource,
+    // Whether any output was written, rather than left as it was by `--incremental`.
+    updated: bool,
+    pending: Pending,
+}
+
+// Of the wikis generated, to record once their outputs are in place.
+#[derive(Default)]
+struct Pending {
+    // For `--incremental`.
+    fingerprints: Vec<(String, String)>,
+    // For `--lockfile`.
+    locks: Vec<(String, lock::Locked)>,
+}
+
+// Outputs are written to the paths staged by `staging` if any, to be committed by the caller.
+fn fetch_domain(
+    client: &api::Client,
+    args: &cli::Args,
+    domain: &str,
+    outputs: &[(cli::Format, generate::Target, Option<path::PathBuf>)],
+    mut staging: Option<&mut sync::Staging>,
+) -> Result<Generated, Error> {
+    let (configuration_source, fetched) = extract_domain(client, args, domain)?;
+    let mut pending = Pending::default();
+    if args.lockfile.is_some() && !(args.locked || args.frozen) {
+        let locked = lock::Locked::new(
+            &fetched.raw,
+            fetched.query.general.generator.clone(),
+            time::SystemTime::now(),
+        );
+        pending.locks.push((api::site::key(domain), locked));
+    }
+    let rendered: Vec<_>;
+    let outputs = if args.out_dir.is_some() {
+        let date = cli::utc_date(time::SystemTime::now());
+        let language = configuration_source.language.as_deref();
+        rendered = outputs
+            .iter()
+            .map(|(format, target, path)| {
+                let path = path
+                    .as_deref()
+                    .map(|path| cli::name_path(path, domain, language, &date));
+                (*format, *target, path)
+            })
+            .collect();
+        &rendered[..]
+    } else {
+        outputs
+    };
+    if args.incremental {
+        // By the output paths too, as the same wiki may be generated to other outputs.
+        let paths: Vec<_> = outputs
+            .iter()
+            .filter_map(|(_, _, path)| path.as_deref())
+            .collect();
+        let key = format!("{} {:?}", api::site::key(domain), paths);
+        let fingerprint = fingerprint(&fetched.raw, args, outputs);
+        let state = state::State::load(&state_path(args)?)?;
+        let written = outputs
+            .iter()
+            .all(|(_, _, path)| path.as_deref().is_some_and(path::Path::is_file));
+        if written && state.fingerprints.get(&key) == Some(&fingerprint) {
+            log::info!("{:?} unchanged, outputs left as they were", domain);
+            return Ok(Generated {
+                configuration_source,
+                updated: false,
+                pending,
+            });
+        }
+        pending.fingerprints.push((key, fingerprint));
+    }
+    let mut updated = false;
+    let signing_key = match &args.sign_key {
+        Some(path) => Some(sign::signing_key(path)?),
+        None => None,
+    };
+
+    if let Some(directory) = &args.split_output {
+        for (name, contents) in generate::split_data(&configuration_source) {
+            let path = directory.join(name);
+            log::info!("write data to {:?} ...", path);
+            let written = match &mut staging {
+                Some(staging) => staging.stage(&path)?,
+                None => path,
+            };
+            summary::phase("write", || {
+                write_atomic(&written, contents.as_bytes(), args.create_dirs)
+            })?;
+        }
+    }
+
+    // Outputs are generated in full before being written, so that nothing is written if
+    // generation fails.
+    for (format, target, path) in outputs {
+        let (format, path) = (*format, path.as_deref());
+        let mut args = args.for_target(*target);
+        if let (Some(directory), Some(path)) = (&args.split_output, path) {
+            args.split_output = Some(relative_to(path, directory)?);
+        }
+        let args = &args;
+        let mut out = Vec::new();
+        summary::phase("generate", || {
+            cli::generate(format, &mut out, &configuration_source, args)
+        })?;
+        if let (cli::Format::Rust, Some(max)) = (format, args.max_output_size) {
+            check_output_size(out.len() as u64, max, &configuration_source, args)?;
+        }
+        match path {
+            Some(path) if args.incremental && fs::read(path).is_ok_and(|old| old == out) => {
+                log::info!("{:?} output {:?} unchanged", format, path);
+            }
+            Some(path) => {
+                log::info!("write {:?} output to {:?} ...", format, path);
+                let written = match &mut staging {
+                    Some(staging) => staging.stage(path)?,
+                    None => path.to_owned(),
+                };
+                let create_dirs = args.create_dirs || args.out_dir.is_some();
+                summary::phase("write", || write_atomic(&written, &out, create_dirs))?;
+                updated = true;
+                if let Some(key) = &signing_key {
+                    sign::sign(key, &written)?;
+                }
+            }
+            None => {
+                log::info!("write {:?} output to stdout ...", format);
+                summary::phase("write", || io::stdout().lock().write_all(&out))?;
+                updated = true;
+            }
+        }
+        summary::output(format!(
+            "{} ({:?}, {} bytes)",
+            path.map_or("stdout".into(), |path| path.display().to_string()),
+            format,
+            out.len()
+        ));
+    }
 
-    log::info!("write generated code to stdout ...");
-    let out = io::stdout();
-    generate::configuration_source(out, &configuration_source)?;
+    Ok(Generated {
+        configuration_source,
+        updated,
+        pending,
+    })
+}
+
+// Of the siteinfo response, and of everything else the outputs depend on.
+fn fingerprint(
+    raw: &str,
+    args: &cli::Args,
+    outputs: &[(cli::Format, generate::Target, Option<path::PathBuf>)],
+) -> String {
+    use sha2::Digest;
+    let mut hasher = sha2::Sha256::new();
+    hasher.update(raw);
+    hasher.update(format!(
+        "{} {:?} {:?} {:?}",
+        env!("CARGO_PKG_VERSION"),
+        args.extract_options(),
+        args.generate_options(),
+        outputs
+    ));
+    hex::encode(hasher.finalize())
+}
+
+fn record(args: &cli::Args, pending: &mut Pending) -> Result<(), Error> {
+    if !pending.fingerprints.is_empty() {
+        let path = state_path(args)?;
+        let mut state = state::State::load(&path)?;
+        state.fingerprints.extend(pending.fingerprints.drain(..));
+        state.save(&path)?;
+    }
+    if let (false, Some(path)) = (pending.locks.is_empty(), &args.lockfile) {
+        let mut lockfile = lock::Lockfile::load(path)?;
+        let mut changed = false;
+        for (domain, locked) in pending.locks.drain(..) {
+            changed |= lockfile.update(&domain, locked);
+        }
+        if changed {
+            log::info!("write lockfile {:?} ...", path);
+            lockfile.save(path)?;
+        }
+    }
+    Ok(())
+}
+
+impl Pending {
+    fn extend(&mut self, other: Pending) {
+        self.fingerprints.extend(other.fingerprints);
+        self.locks.extend(other.locks);
+    }
+}
+
+// Written to a temporary file in the same directory, so that the rename is atomic.
+fn write_atomic(path: &path::Path, contents: &[u8], create_dirs: bool) -> Result<(), Error> {
+    let directory = path.parent().unwrap_or_else(|| path::Path::new(""));
+    if create_dirs && !directory.as_os_str().is_empty() {
+        fs::create_dir_all(directory).map_err(|e| Error::Write(path.to_owned(), e))?;
+    }
+    let mut name = ffi::OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(format!(".tmp-{}", process::id()));
+    let temporary = path.with_file_name(name);
+    if let Err(e) = fs::write(&temporary, contents).and_then(|()| fs::rename(&temporary, path)) {
+        let _ = fs::remove_file(&temporary);
+        return Err(Error::Write(path.to_owned(), e));
+    }
+    Ok(())
+}
+
+fn write_metadata(
+    path: &path::Path,
+    domain: &str,
+    fetched: &api::Fetched,
+    fetched_at: time::SystemTime,
+    configuration_source: &extract::ConfigurationSource,
+    create_dirs: bool,
+) -> Result<(), Error> {
+    log::info!("write metadata to {:?} ...", path);
+    let response: serde_json::Value = serde_json::from_str(&fetched.raw)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let general = response
+        .pointer("/query/general")
+        .cloned()
+        .unwrap_or_default();
+    let counts: serde_json::Map<_, _> = diff::fields(configuration_source)
+        .into_iter()
+        .map(|(field, values)| (field.to_owned(), values.len().into()))
+        .collect();
+    let metadata = serde_json::json!({
+        "domain": domain,
+        "url": fetched.url.as_str(),
+        "fetched_at": fetched_at
+            .duration_since(time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+        "generator": general.get("generator"),
+        "general": general,
+        "counts": counts,
+    });
+    let mut out = serde_json::to_vec_pretty(&metadata)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    out.push(b'\n');
+    write_atomic(path, &out, create_dirs)?;
+    Ok(())
+}
+
+fn dump_raw(path: &path::Path, fetched: &api::Fetched, args: &cli::Args) -> Result<(), Error> {
+    let response: serde_json::Value = serde_json::from_str(&fetched.raw)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let mut out = serde_json::to_vec_pretty(&response)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    out.push(b'\n');
+    if args.dump_raw_stdout() {
+        log::info!("write siteinfo response to stdout ...");
+        io::stdout().lock().write_all(&out)?;
+    } else {
+        log::info!("write siteinfo response to {:?} ...", path);
+        write_atomic(path, &out, args.create_dirs)?;
+    }
+    Ok(())
+}
+
+// `target` as included from the file at `path`: relative to it if in its directory or below, or
+// else absolute.
+fn relative_to(path: &path::Path, target: &path::Path) -> Result<path::PathBuf, io::Error> {
+    let directory = path.parent().unwrap_or_else(|| path::Path::new(""));
+    match target.strip_prefix(directory) {
+        Ok(relative) => Ok(relative.to_owned()),
+        Err(..) => path::absolute(target),
+    }
+}
 
+// The outputs are included relative to the module at `path` if they are in its directory or below.
+fn write_by_language(
+    path: &path::Path,
+    staged: &path::Path,
+    outputs: collections::BTreeMap<String, path::PathBuf>,
+    target: generate::Target,
+) -> Result<(), Error> {
+    let outputs = outputs
+        .into_iter()
+        .map(|(language, output)| Ok((language, relative_to(path, &output)?)))
+        .collect::<Result<_, io::Error>>()?;
+    log::info!("write outputs by language to {:?} ...", path);
+    let mut out = Vec::new();
+    generate::by_language(&mut out, &outputs, target)?;
+    fs::write(staged, out)?;
     Ok(())
 }
 
+fn check_output_size(
+    size: u64,
+    max: u64,
+    configuration_source: &extract::ConfigurationSource,
+    args: &cli::Args,
+) -> Result<(), Error> {
+    if size <= max {
+        return Ok(());
+    }
+    let link_trail: usize = configuration_source
+        .link_trail
+        .iter()
+        .map(|c| c.len_utf8())
+        .sum();
+    log::warn!(
+        "generated code is {} bytes, over the maximum of {} bytes; the link trail accounts for {} \
+            bytes of it, and would be much smaller as character ranges",
+        size,
+        max,
+        link_trail
+    );
+    match args.output_size_policy {
+        cli::SizePolicy::Warn => Ok(()),
+        cli::SizePolicy::Fail => Err(Error::OutputTooLarge(size, max)),
+    }
+}
+
+fn changelog(old: &path::Path, new: &path::Path, args: &cli::Args) -> Result<(), Error> {
+    let extract_options = args.extract_options();
+    let load = |path: &path::Path| {
+        diff::load_snapshot(path, &extract_options).map_err(|e| Error::Snapshot(path.to_owned(), e))
+    };
+    let changes = diff::diff(&load(old)?, &load(new)?);
+    log::info!("changed fields: {}", changes.len());
+
+    let out = io::stdout().lock();
+    match args.format {
+        cli::Format::Json => diff::write_json(out, &changes)?,
+        cli::Format::Markdown
+        | cli::Format::ParserTests
+        | cli::Format::Rust
+        | cli::Format::TitleRules => diff::write_text(out, &changes)?,
+    }
+    Ok(())
+}
+
+fn log_error_details(e: &Error) {
+    use extract::Error::*;
+    match e {
+        Error::Api(e) => {
+            log::debug!("domain = {:?}, status = {:?}", e.domain(), e.status());
+        }
+        Error::Extract(errors) => {
+            for e in errors.iter() {
+                match e {
+                    LinkTrail(e) => {
+                        log::debug!("pattern = {:?}, group = {:?}", e.pattern(), e.group_index());
+                        if let extract::LinkTrailError::Pcre(e) = e {
+                            log::debug!(
+                                "kind = {:?}, modifier = {:?}",
+                                e.kind(),
+                                e.kind().modifier()
+                            );
+                        }
+                    }
+                    MalformedExtensionTag(e) => log::debug!("tag = {:?}", e.tag()),
+                    NamespaceNotFound(e) => log::debug!("canonical = {:?}", e.canonical()),
+                    _ => {}
+                }
+            }
+        }
+        Error::Clap(..)
+        | Error::ClapDisplayed(..)
+        | Error::Client(..)
+        | Error::Check(..)
+        | Error::Crawl(..)
+        | Error::Domains(..)
+        | Error::Sign(..)
+        | Error::Sync(..)
+        | Error::Snapshot(..)
+        | Error::Stale(..)
+        | Error::Generate(..)
+        | Error::Injected(..)
+        | Error::Io(..)
+        | Error::Write(..)
+        | Error::OutputTooLarge(..)
+        | Error::State(..)
+        | Error::Lock(..)
+        | Error::Failed(..)
+        | Error::NoCacheDirectory
+        | Error::Cache(..)
+        | Error::NotCached(..)
+        | Error::Cassette(..)
+        | Error::CaCert(..)
+        | Error::ClientCert(..)
+        | Error::Cookies(..) => {}
+    }
+}
+
+// Where reports other than the generated output are written: stdout, or stderr with
+// `--stdout-only-output`.
+fn report_stream(args: &cli::Args) -> Box<dyn io::Write> {
+    if args.stdout_only_output {
+        Box::new(io::stderr().lock())
+    } else {
+        Box::new(io::stdout().lock())
+    }
+}
+
 fn log_initialize(level: log::LevelFilter) {
-    simplelog::TermLogger::init(
+    let logger = simplelog::TermLogger::new(
         level,
         simplelog::ConfigBuilder::default()
             .set_level_padding(simplelog::LevelPadding::Left)
@@ -120,6 +1299,7 @@ fn log_initialize(level: log::LevelFilter) {
             .build(),
         simplelog::TerminalMode::Stderr,
         simplelog::ColorChoice::Auto,
-    )
-    .unwrap();
+    );
+    log::set_boxed_logger(Box::new(summary::Logger::new(logger))).unwrap();
+    log::set_max_level(level);
 }
@@ -0,0 +1,89 @@
+use crate::{api, cli, extract};
+use serde::{Deserialize, Serialize};
+use std::io;
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct Request {
+    domain: String,
+    #[serde(default)]
+    format: Option<cli::Format>,
+}
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct Response {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    domain: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format: Option<cli::Format>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    output: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+pub fn run(
+    client: &api::Client,
+    args: &cli::Args,
+    input: impl io::BufRead,
+    mut output: impl io::Write,
+) -> Result<(), io::Error> {
+    log::info!("serve requests from stdin ...");
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => respond(client, args, request),
+            Err(e) => Response {
+                error: Some(format!("invalid request: {}", e)),
+                ..Default::default()
+            },
+        };
+        serde_json::to_writer(&mut output, &response)?;
+        writeln!(output)?;
+        output.flush()?;
+        if client.budget_exhausted() {
+            log::error!("request budget exhausted, stop serving requests");
+            break;
+        }
+    }
+    log::info!("end of requests");
+    Ok(())
+}
+
+fn respond(client: &api::Client, args: &cli::Args, request: Request) -> Response {
+    let format = request.format.unwrap_or(args.format);
+    log::info!("request: {:?} as {:?}", request.domain, format);
+    let result = generate(client, args, &request.domain, format);
+    if let Err(e) = &result {
+        log::warn!("request for {:?} failed: {}", request.domain, e);
+    }
+    let (output, error) = match result {
+        Ok(output) => (Some(output), None),
+        Err(e) => (None, Some(e)),
+    };
+    Response {
+        domain: Some(request.domain),
+        format: Some(format),
+        output,
+        error,
+    }
+}
+
+fn generate(
+    client: &api::Client,
+    args: &cli::Args,
+    domain: &str,
+    format: cli::Format,
+) -> Result<String, String> {
+    let fetched = client.fetch_query(domain).map_err(|e| e.to_string())?;
+    let configuration_source =
+        extract::configuration_source(&fetched.query, &args.extract_options())
+            .map_err(|e| e.to_string())?;
+    let mut out = Vec::new();
+    cli::generate(format, &mut out, &configuration_source, args).map_err(|e| e.to_string())?;
+    String::from_utf8(out).map_err(|e| e.to_string())
+}
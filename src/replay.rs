@@ -0,0 +1,86 @@
+use crate::diff;
+use std::{
+    io::{self, BufRead, Read, Write},
+    net, path,
+};
+
+// Serve saved siteinfo API responses over HTTP, for fetching with `--replay` without touching the
+// network: the response of a wiki is read from `<dir>/<wiki>.json`, optionally gzip-compressed,
+// whatever the query of the request.
+pub fn serve(dir: &path::Path, address: &str) -> Result<(), io::Error> {
+    let listener = net::TcpListener::bind(address)?;
+    log::info!(
+        "replay responses from {:?} at http://{} ...",
+        dir,
+        listener.local_addr()?
+    );
+    for stream in listener.incoming() {
+        if let Err(e) = handle(dir, stream?) {
+            log::warn!("replay request failed: {}", e);
+        }
+    }
+    Ok(())
+}
+
+fn handle(dir: &path::Path, stream: net::TcpStream) -> Result<(), io::Error> {
+    let mut reader = io::BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // The headers are not needed, but must be read before responding, as must the body of a POST
+    // request.
+    let mut header = String::new();
+    let mut length = 0;
+    while reader.read_line(&mut header)? > 2 {
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse().unwrap_or_default();
+            }
+        }
+        header.clear();
+    }
+    io::copy(&mut reader.by_ref().take(length), &mut io::sink())?;
+
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = (
+        parts.next().unwrap_or_default(),
+        parts.next().unwrap_or("/"),
+    );
+    let wiki = target
+        .split('?')
+        .next()
+        .unwrap_or_default()
+        .trim_matches('/');
+    let file = dir.join(format!("{}.json", wiki));
+    let mut out = io::BufWriter::new(stream);
+    let body = if wiki.split('/').any(|part| part.is_empty() || part == "..") {
+        None
+    } else if file.is_file() {
+        Some(diff::read_snapshot(&file)?)
+    } else {
+        None
+    };
+    log::info!(
+        "{} {:?}: {}",
+        method,
+        wiki,
+        body.as_ref().map_or("not found", |_| "replayed")
+    );
+    match body {
+        Some(body) => {
+            write!(
+                out,
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json; charset=utf-8\r\n\
+                    Content-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            )?;
+            if method != "HEAD" {
+                out.write_all(body.as_bytes())?;
+            }
+        }
+        None => write!(
+            out,
+            "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+        )?,
+    }
+    out.flush()
+}
@@ -0,0 +1,185 @@
+use crate::{api, cli, extract};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::{collections, io, time};
+
+// JSON-RPC 2.0 error codes.
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const SERVER_ERROR: i64 = -32000;
+
+pub struct Server<'a> {
+    client: &'a api::Client,
+    args: &'a cli::Args,
+    cache: collections::BTreeMap<String, (time::Instant, api::Fetched)>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Request {
+    jsonrpc: String,
+    #[serde(default)]
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+struct DomainParams {
+    domain: String,
+    #[serde(default)]
+    format: Option<cli::Format>,
+    #[serde(default)]
+    refresh: bool,
+}
+
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl<'a> Server<'a> {
+    pub fn new(client: &'a api::Client, args: &'a cli::Args) -> Self {
+        Self {
+            client,
+            args,
+            cache: Default::default(),
+        }
+    }
+
+    pub fn run(&mut self, input: impl io::BufRead, mut output: impl io::Write) -> io::Result<()> {
+        log::info!("serve JSON-RPC requests from stdin ...");
+        for line in input.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Some(response) = self.handle(&line) {
+                serde_json::to_writer(&mut output, &response)?;
+                writeln!(output)?;
+                output.flush()?;
+            }
+            if self.client.budget_exhausted() {
+                log::error!("request budget exhausted, stop serving requests");
+                break;
+            }
+        }
+        log::info!("end of requests");
+        Ok(())
+    }
+
+    fn handle(&mut self, line: &str) -> Option<Value> {
+        let request: Value = match serde_json::from_str(line) {
+            Ok(request) => request,
+            Err(e) => return Some(error_response(Value::Null, PARSE_ERROR, e.to_string())),
+        };
+        let request: Request = match serde_json::from_value(request) {
+            Ok(request) => request,
+            Err(e) => return Some(error_response(Value::Null, INVALID_REQUEST, e.to_string())),
+        };
+        if request.jsonrpc != "2.0" {
+            let id = request.id.unwrap_or(Value::Null);
+            return Some(error_response(id, INVALID_REQUEST, "unsupported version"));
+        }
+        log::debug!("request: {} {}", request.method, request.params);
+
+        let result = self.call(&request.method, request.params);
+        // Notifications (requests without an id) get no response.
+        let id = request.id?;
+        Some(match result {
+            Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+            Err(e) => error_response(id, e.code, e.message),
+        })
+    }
+
+    fn call(&mut self, method: &str, params: Value) -> Result<Value, RpcError> {
+        match method {
+            "fetchSiteinfo" => {
+                let params = domain_params(params)?;
+                let fetched = self.fetch(&params)?;
+                serde_json::from_str(&fetched.raw).map_err(RpcError::server)
+            }
+            "extract" => {
+                let params = domain_params(params)?;
+                let configuration_source = self.extract(&params)?;
+                serde_json::to_value(configuration_source).map_err(RpcError::server)
+            }
+            "generate" => {
+                let params = domain_params(params)?;
+                let format = params.format.unwrap_or(self.args.format);
+                let configuration_source = self.extract(&params)?;
+                let mut out = Vec::new();
+                cli::generate(format, &mut out, &configuration_source, self.args)
+                    .map_err(RpcError::server)?;
+                let output = String::from_utf8(out).map_err(RpcError::server)?;
+                Ok(json!({ "format": format, "output": output }))
+            }
+            "cacheStatus" => {
+                let entries: Vec<_> = self
+                    .cache
+                    .iter()
+                    .map(|(domain, (fetched_at, fetched))| {
+                        json!({
+                            "domain": domain,
+                            "ageSeconds": fetched_at.elapsed().as_secs(),
+                            "url": fetched.url.as_str(),
+                        })
+                    })
+                    .collect();
+                Ok(json!({ "entries": entries }))
+            }
+            _ => Err(RpcError {
+                code: METHOD_NOT_FOUND,
+                message: format!("method not found: {:?}", method),
+            }),
+        }
+    }
+
+    fn fetch(&mut self, params: &DomainParams) -> Result<&api::Fetched, RpcError> {
+        let key = api::site::key(&params.domain);
+        if params.refresh || !self.cache.contains_key(&key) {
+            log::info!("fetch {:?} ...", params.domain);
+            let fetched = self
+                .client
+                .fetch_query(&params.domain)
+                .map_err(RpcError::server)?;
+            self.cache
+                .insert(key.clone(), (time::Instant::now(), fetched));
+        }
+        Ok(&self.cache[&key].1)
+    }
+
+    fn extract(&mut self, params: &DomainParams) -> Result<extract::ConfigurationSource, RpcError> {
+        let extract_options = self.args.extract_options();
+        let fetched = self.fetch(params)?;
+        extract::configuration_source(&fetched.query, &extract_options).map_err(RpcError::server)
+    }
+}
+
+impl RpcError {
+    fn server(e: impl ToString) -> Self {
+        Self {
+            code: SERVER_ERROR,
+            message: e.to_string(),
+        }
+    }
+}
+
+fn domain_params(params: Value) -> Result<DomainParams, RpcError> {
+    serde_json::from_value(params).map_err(|e| RpcError {
+        code: INVALID_PARAMS,
+        message: e.to_string(),
+    })
+}
+
+fn error_response(id: Value, code: i64, message: impl Into<String>) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message.into() },
+    })
+}
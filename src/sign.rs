@@ -0,0 +1,106 @@
+use ed25519_dalek::{Signer, Verifier};
+use std::{convert::TryInto, ffi, fs, io, path};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("{path:?}: {source}")]
+    Io {
+        path: path::PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("{path:?}: invalid hex: {source}")]
+    Hex {
+        path: path::PathBuf,
+        #[source]
+        source: hex::FromHexError,
+    },
+    #[error("{path:?}: expected {expected} bytes, found {found}")]
+    Length {
+        path: path::PathBuf,
+        expected: usize,
+        found: usize,
+    },
+    #[error("{path:?}: invalid key: {source}")]
+    Key {
+        path: path::PathBuf,
+        #[source]
+        source: ed25519_dalek::SignatureError,
+    },
+    #[error("{path:?}: signature does not match: {source}")]
+    Mismatch {
+        path: path::PathBuf,
+        #[source]
+        source: ed25519_dalek::SignatureError,
+    },
+}
+
+pub fn signing_key(path: &path::Path) -> Result<ed25519_dalek::SigningKey, Error> {
+    let key = ed25519_dalek::SigningKey::from_bytes(&read_hex(path)?);
+    log::info!(
+        "public key: {}",
+        hex::encode(key.verifying_key().as_bytes())
+    );
+    Ok(key)
+}
+
+pub fn sign(key: &ed25519_dalek::SigningKey, artifact: &path::Path) -> Result<(), Error> {
+    let data = fs::read(artifact).map_err(|e| Error::io(artifact, e))?;
+    let signature_path = signature_path(artifact);
+    log::info!("write signature to {:?} ...", signature_path);
+    let signature = hex::encode(key.sign(&data).to_bytes()) + "\n";
+    fs::write(&signature_path, signature).map_err(|e| Error::io(&signature_path, e))
+}
+
+pub fn verify(key_path: &path::Path, artifact: &path::Path) -> Result<(), Error> {
+    let key = ed25519_dalek::VerifyingKey::from_bytes(&read_hex(key_path)?).map_err(|source| {
+        Error::Key {
+            path: key_path.to_owned(),
+            source,
+        }
+    })?;
+    let signature_path = signature_path(artifact);
+    let signature = ed25519_dalek::Signature::from_bytes(&read_hex(&signature_path)?);
+    let data = fs::read(artifact).map_err(|e| Error::io(artifact, e))?;
+    key.verify(&data, &signature)
+        .map_err(|source| Error::Mismatch {
+            path: artifact.to_owned(),
+            source,
+        })?;
+    log::info!("signature {:?} is valid", signature_path);
+    Ok(())
+}
+
+// The detached signature of `artifact` is stored next to it, with `.sig` appended to its name.
+pub fn signature_path(artifact: &path::Path) -> path::PathBuf {
+    let mut name = artifact
+        .file_name()
+        .map(ffi::OsStr::to_owned)
+        .unwrap_or_default();
+    name.push(".sig");
+    artifact.with_file_name(name)
+}
+
+fn read_hex<const N: usize>(path: &path::Path) -> Result<[u8; N], Error> {
+    let raw = fs::read_to_string(path).map_err(|e| Error::io(path, e))?;
+    let bytes = hex::decode(raw.trim()).map_err(|source| Error::Hex {
+        path: path.to_owned(),
+        source,
+    })?;
+    let found = bytes.len();
+    bytes.try_into().map_err(|_| Error::Length {
+        path: path.to_owned(),
+        expected: N,
+        found,
+    })
+}
+
+impl Error {
+    fn io(path: &path::Path, source: io::Error) -> Self {
+        Self::Io {
+            path: path.to_owned(),
+            source,
+        }
+    }
+}
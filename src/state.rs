@@ -0,0 +1,102 @@
+use serde::{Deserialize, Serialize};
+use std::{collections, fs, io, path};
+use thiserror::Error;
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct State {
+    #[serde(default)]
+    pub endpoints: collections::BTreeMap<String, url::Url>,
+    // Of the siteinfo response and the options of the outputs last generated, by wiki and output
+    // paths, for `--incremental`.
+    #[serde(default)]
+    pub fingerprints: collections::BTreeMap<String, String>,
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("no state directory, specify a state file explicitly")]
+    NoDefaultPath,
+    #[error("state file {path:?}: {source}")]
+    Io {
+        path: path::PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("state file {path:?}: {source}")]
+    Json {
+        path: path::PathBuf,
+        #[source]
+        source: serde_json::Error,
+    },
+}
+
+impl State {
+    pub fn default_path() -> Result<path::PathBuf, Error> {
+        let dir = dirs::state_dir()
+            .or_else(dirs::data_local_dir)
+            .ok_or(Error::NoDefaultPath)?;
+        Ok(dir.join(env!("CARGO_PKG_NAME")).join("state.json"))
+    }
+
+    pub fn load(path: &path::Path) -> Result<Self, Error> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                log::debug!("state file not found, start empty: {:?}", path);
+                return Ok(Self::default());
+            }
+            Err(e) => return Err(Error::io(path, e)),
+        };
+        serde_json::from_str(&contents).map_err(|e| Error::json(path, e))
+    }
+
+    pub fn save(&self, path: &path::Path) -> Result<(), Error> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| Error::io(path, e))?;
+        }
+        let contents = serde_json::to_string_pretty(self).map_err(|e| Error::json(path, e))?;
+        fs::write(path, contents).map_err(|e| Error::io(path, e))
+    }
+
+    pub fn pin_endpoint(&mut self, domain: &str, url: &url::Url) -> bool {
+        let mut url = url.clone();
+        url.set_query(None);
+        match self.endpoints.get(domain) {
+            None => {
+                log::info!("pin endpoint of {:?}: {}", domain, url);
+                self.endpoints.insert(domain.to_owned(), url);
+                true
+            }
+            Some(pinned) if *pinned == url => {
+                log::debug!("endpoint matches pin: {}", url);
+                false
+            }
+            Some(pinned) => {
+                log::warn!(
+                    "endpoint of {:?} changed: pinned {}, now {} (remove the pin to accept)",
+                    domain,
+                    pinned,
+                    url
+                );
+                false
+            }
+        }
+    }
+}
+
+impl Error {
+    fn io(path: &path::Path, source: io::Error) -> Self {
+        Self::Io {
+            path: path.to_owned(),
+            source,
+        }
+    }
+
+    fn json(path: &path::Path, source: serde_json::Error) -> Self {
+        Self::Json {
+            path: path.to_owned(),
+            source,
+        }
+    }
+}
@@ -0,0 +1,261 @@
+// The summary and telemetry of a run, with the steps timed by the library, see `timing`.
+
+use std::{
+    cell, collections,
+    io::{self, IsTerminal, Write},
+    sync, time,
+};
+use termcolor::WriteColor;
+
+// Collected over the run, for a summary at its end.
+struct Summary {
+    phases: Vec<(&'static str, time::Duration)>,
+    spans: Vec<Span>,
+    wikis: Vec<String>,
+    outputs: Vec<String>,
+    warnings: collections::BTreeMap<String, usize>,
+}
+
+// A timed call, named by the names of the enclosing spans and its own, joined by `;` as in the
+// folded stacks of flame graphs, e.g. `fetch;decode`.
+struct Span {
+    stack: String,
+    start: time::Duration,
+    duration: time::Duration,
+}
+
+// Counts warnings by module, before passing all records on to the terminal logger.
+pub struct Logger {
+    inner: Box<simplelog::TermLogger>,
+}
+
+static SUMMARY: sync::Mutex<Summary> = sync::Mutex::new(Summary {
+    phases: Vec::new(),
+    spans: Vec::new(),
+    wikis: Vec::new(),
+    outputs: Vec::new(),
+    warnings: collections::BTreeMap::new(),
+});
+
+// Whether spans are recorded, only for the telemetry of the binary, so that library users do not
+// collect spans without end.
+static COLLECT: sync::atomic::AtomicBool = sync::atomic::AtomicBool::new(false);
+
+// Start of the first span, from which span starts are measured.
+static EPOCH: sync::OnceLock<time::Instant> = sync::OnceLock::new();
+
+thread_local! {
+    static STACK: cell::RefCell<Vec<&'static str>> = const { cell::RefCell::new(Vec::new()) };
+}
+
+pub fn phase<T>(name: &'static str, f: impl FnOnce() -> T) -> T {
+    let start = time::Instant::now();
+    let result = span(name, f);
+    let elapsed = start.elapsed();
+    let mut summary = SUMMARY.lock().unwrap();
+    match summary.phases.iter_mut().find(|(phase, _)| *phase == name) {
+        Some((_, total)) => *total += elapsed,
+        None => summary.phases.push((name, elapsed)),
+    }
+    result
+}
+
+pub fn collect_spans() {
+    COLLECT.store(true, sync::atomic::Ordering::Relaxed);
+}
+
+pub fn span<T>(name: &'static str, f: impl FnOnce() -> T) -> T {
+    if !COLLECT.load(sync::atomic::Ordering::Relaxed) {
+        return f();
+    }
+    let epoch = *EPOCH.get_or_init(time::Instant::now);
+    let stack = STACK.with(|stack| {
+        let mut stack = stack.borrow_mut();
+        stack.push(name);
+        stack.join(";")
+    });
+    let start = time::Instant::now();
+    let result = f();
+    let duration = start.elapsed();
+    STACK.with(|stack| stack.borrow_mut().pop());
+    push_span(stack, start.saturating_duration_since(epoch), duration);
+    result
+}
+
+// A span of a step of the library, nested in the current span, see
+// `fetch_mediawiki_configuration::Timing`.
+pub fn timing(name: &'static str, duration: time::Duration) {
+    if !COLLECT.load(sync::atomic::Ordering::Relaxed) {
+        return;
+    }
+    let epoch = *EPOCH.get_or_init(time::Instant::now);
+    let stack = STACK.with(|stack| {
+        let mut stack = stack.borrow().clone();
+        stack.push(name);
+        stack.join(";")
+    });
+    let start = time::Instant::now()
+        .saturating_duration_since(epoch)
+        .saturating_sub(duration);
+    push_span(stack, start, duration);
+}
+
+fn push_span(stack: String, start: time::Duration, duration: time::Duration) {
+    log::debug!("span {}: {:.2?}", stack, duration);
+    SUMMARY.lock().unwrap().spans.push(Span {
+        stack,
+        start,
+        duration,
+    });
+}
+
+pub fn wiki(description: String) {
+    SUMMARY.lock().unwrap().wikis.push(description);
+}
+
+pub fn output(description: String) {
+    SUMMARY.lock().unwrap().outputs.push(description);
+}
+
+// Whether to print the summary in `auto` mode: only if stderr is a terminal.
+pub fn is_terminal() -> bool {
+    io::stderr().is_terminal()
+}
+
+pub fn print(requests: u32, elapsed: time::Duration) -> Result<(), io::Error> {
+    let summary = SUMMARY.lock().unwrap();
+    let choice = if is_terminal() {
+        termcolor::ColorChoice::Auto
+    } else {
+        termcolor::ColorChoice::Never
+    };
+    let stderr = termcolor::StandardStream::stderr(choice);
+    let mut out = stderr.lock();
+    let heading = |out: &mut termcolor::StandardStreamLock, heading: &str| {
+        out.set_color(termcolor::ColorSpec::new().set_bold(true))?;
+        write!(out, "{:<10}", heading)?;
+        out.reset()
+    };
+
+    out.set_color(termcolor::ColorSpec::new().set_bold(true))?;
+    writeln!(out, "summary")?;
+    out.reset()?;
+    for wiki in &summary.wikis {
+        heading(&mut out, "wiki")?;
+        writeln!(out, "{}", wiki)?;
+    }
+    for output in &summary.outputs {
+        heading(&mut out, "output")?;
+        writeln!(out, "{}", output)?;
+    }
+
+    heading(&mut out, "warnings")?;
+    if summary.warnings.is_empty() {
+        out.set_color(termcolor::ColorSpec::new().set_fg(Some(termcolor::Color::Green)))?;
+        writeln!(out, "none")?;
+    } else {
+        out.set_color(termcolor::ColorSpec::new().set_fg(Some(termcolor::Color::Yellow)))?;
+        let warnings: Vec<_> = summary
+            .warnings
+            .iter()
+            .map(|(category, count)| format!("{} {}", count, category))
+            .collect();
+        writeln!(out, "{}", warnings.join(", "))?;
+    }
+    out.reset()?;
+
+    heading(&mut out, "time")?;
+    let phases: Vec<_> = summary
+        .phases
+        .iter()
+        .map(|(phase, elapsed)| format!("{} {:.2?}", phase, elapsed))
+        .collect();
+    writeln!(out, "{} (total {:.2?})", phases.join(", "), elapsed)?;
+    heading(&mut out, "requests")?;
+    writeln!(out, "{}", requests)?;
+    Ok(())
+}
+
+pub fn write_telemetry(
+    mut out: impl io::Write,
+    requests: u32,
+    elapsed: time::Duration,
+) -> Result<(), io::Error> {
+    let summary = SUMMARY.lock().unwrap();
+    let micros = |duration: time::Duration| duration.as_micros() as u64;
+    let telemetry = serde_json::json!({
+        "elapsed_us": micros(elapsed),
+        "requests": requests,
+        "phases": summary
+            .phases
+            .iter()
+            .map(|(phase, elapsed)| (phase.to_string(), micros(*elapsed).into()))
+            .collect::<serde_json::Map<_, _>>(),
+        "spans": summary
+            .spans
+            .iter()
+            .map(|span| serde_json::json!({
+                "stack": span.stack,
+                "start_us": micros(span.start),
+                "duration_us": micros(span.duration),
+            }))
+            .collect::<Vec<_>>(),
+        "warnings": summary.warnings,
+    });
+    serde_json::to_writer_pretty(&mut out, &telemetry)?;
+    writeln!(out)?;
+    out.flush()
+}
+
+// Write the self time of each stack, i.e. its total time less that of the stacks directly nested in
+// it, in microseconds.
+pub fn write_folded(mut out: impl io::Write) -> Result<(), io::Error> {
+    let summary = SUMMARY.lock().unwrap();
+    let mut totals = collections::BTreeMap::<&str, time::Duration>::new();
+    for span in &summary.spans {
+        *totals.entry(&span.stack).or_default() += span.duration;
+    }
+    for (stack, total) in &totals {
+        let nested: time::Duration = totals
+            .range(*stack..)
+            .filter_map(|(other, total)| {
+                let rest = other.strip_prefix(stack)?.strip_prefix(';')?;
+                (!rest.contains(';')).then_some(*total)
+            })
+            .sum();
+        let micros = total.saturating_sub(nested).as_micros();
+        if micros > 0 {
+            writeln!(out, "{} {}", stack, micros)?;
+        }
+    }
+    out.flush()
+}
+
+impl Logger {
+    pub fn new(inner: Box<simplelog::TermLogger>) -> Self {
+        Self { inner }
+    }
+}
+
+impl log::Log for Logger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &log::Record) {
+        if record.level() == log::Level::Warn {
+            let category = record.target().rsplit("::").next().unwrap_or_default();
+            *SUMMARY
+                .lock()
+                .unwrap()
+                .warnings
+                .entry(category.to_owned())
+                .or_default() += 1;
+        }
+        self.inner.log(record);
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
@@ -0,0 +1,352 @@
+use crate::{cli, extract, generate, sign};
+use serde::Deserialize;
+use std::{ffi, fs, io, iter, path, process};
+use thiserror::Error;
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Manifest {
+    pub by_language: Option<path::PathBuf>,
+    #[serde(default)]
+    pub defaults: Overrides,
+    #[serde(default, rename = "wiki")]
+    pub wikis: Vec<Wiki>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Wiki {
+    pub domain: String,
+    // The path of the API under the domain, for wikis not at `/w/api.php`.
+    pub api_path: Option<String>,
+    pub output: path::PathBuf,
+    pub format: Option<cli::Format>,
+    #[serde(default)]
+    pub overrides: Overrides,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Overrides {
+    pub case_folding: Option<extract::CaseFolding>,
+    pub const_assertions: Option<bool>,
+    pub const_name: Option<String>,
+    pub duplicate_namespaces: Option<extract::DuplicateNamespaces>,
+    pub emit: Option<generate::Emit>,
+    pub escape_unicode: Option<bool>,
+    pub extra_category_namespaces: Option<Vec<String>>,
+    pub extra_file_namespaces: Option<Vec<String>>,
+    pub item_case: Option<generate::CaseStyle>,
+    pub item_prefix: Option<String>,
+    pub item_suffix: Option<String>,
+    pub link_trail_encoding: Option<generate::LinkTrailEncoding>,
+    pub max_width: Option<usize>,
+    pub namespace_ids: Option<bool>,
+    pub no_std: Option<bool>,
+    pub normalization: Option<extract::Normalization>,
+    pub protocol_allow: Option<Vec<String>>,
+    pub protocol_case: Option<extract::ProtocolCase>,
+    pub protocol_fallback: Option<bool>,
+    pub protocol_suffix: Option<extract::ProtocolSuffix>,
+    pub target: Option<generate::Target>,
+    pub visibility: Option<generate::Visibility>,
+}
+
+// A wiki of the manifest, with the options of the command line overridden by the manifest.
+#[derive(Debug)]
+pub struct Job {
+    pub domain: String,
+    pub format: cli::Format,
+    pub output: path::PathBuf,
+    pub args: cli::Args,
+}
+
+// Outputs written to hidden files next to them, so that they are renamed into place on commit
+// without crossing filesystems, and an interrupted or failed run leaves the outputs not yet
+// committed as they were.  A commit failing halfway is rolled back.
+#[derive(Default)]
+pub struct Staging {
+    staged: Vec<(path::PathBuf, path::PathBuf)>,
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("{path:?}: {source}")]
+    Io {
+        path: path::PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("{path:?}: {source}")]
+    Toml {
+        path: path::PathBuf,
+        #[source]
+        source: toml::de::Error,
+    },
+}
+
+impl Manifest {
+    pub fn load(path: &path::Path) -> Result<Self, Error> {
+        log::info!("load manifest {:?} ...", path);
+        let raw = fs::read_to_string(path).map_err(|source| Error::Io {
+            path: path.to_owned(),
+            source,
+        })?;
+        toml::from_str(&raw).map_err(|source| Error::Toml {
+            path: path.to_owned(),
+            source,
+        })
+    }
+
+    // Relative to the directory of the manifest at `path`, like the outputs.
+    pub fn by_language(&self, path: &path::Path) -> Option<path::PathBuf> {
+        let directory = path.parent().unwrap_or_else(|| path::Path::new(""));
+        self.by_language
+            .as_ref()
+            .map(|by_language| directory.join(by_language))
+    }
+
+    // Output paths are relative to the directory of the manifest at `path`.
+    pub fn jobs(&self, path: &path::Path, args: &cli::Args) -> Vec<Job> {
+        let directory = path.parent().unwrap_or_else(|| path::Path::new(""));
+        self.wikis
+            .iter()
+            .map(|wiki| {
+                let mut args = args.clone();
+                self.defaults.apply(&mut args);
+                wiki.overrides.apply(&mut args);
+                let output = directory.join(&wiki.output);
+                // The domain followed by the path of the API, as on the command line.
+                let domain = match &wiki.api_path {
+                    Some(api_path) => format!(
+                        "{}/{}",
+                        wiki.domain.trim_end_matches('/'),
+                        api_path.trim_start_matches('/')
+                    ),
+                    None => wiki.domain.clone(),
+                };
+                Job {
+                    domain,
+                    format: wiki
+                        .format
+                        .unwrap_or_else(|| cli::Format::from_path(&output, args.format)),
+                    output,
+                    args,
+                }
+            })
+            .collect()
+    }
+}
+
+impl Staging {
+    // The path to write `output` to instead, until committed, creating the directory of `output`.
+    pub fn stage(&mut self, output: &path::Path) -> Result<path::PathBuf, Error> {
+        let directory = output.parent().unwrap_or_else(|| path::Path::new(""));
+        if !directory.as_os_str().is_empty() {
+            fs::create_dir_all(directory).map_err(|source| Error::Io {
+                path: directory.to_owned(),
+                source,
+            })?;
+        }
+        let suffix = format!("staging-{}-{}", process::id(), self.staged.len());
+        let staged = hidden_path(output, &suffix);
+        self.staged.push((staged.clone(), output.to_owned()));
+        Ok(staged)
+    }
+
+    // Rename the outputs staged so far into place, with their signatures if any.  The outputs
+    // replaced are kept until all are renamed, and restored if any rename fails.
+    pub fn commit(&mut self) -> Result<(), Error> {
+        let renames: Vec<_> = self
+            .staged
+            .drain(..)
+            .flat_map(|(staged, output)| {
+                [
+                    (sign::signature_path(&staged), sign::signature_path(&output)),
+                    (staged, output),
+                ]
+            })
+            .filter(|(from, _)| from.exists())
+            .collect();
+        let mut committed = Vec::new();
+        let mut renames = renames.into_iter();
+        while let Some((from, to)) = renames.next() {
+            log::debug!("commit {:?} to {:?}", from, to);
+            match replace(&from, &to) {
+                Ok(backup) => committed.push((to, backup)),
+                Err(source) => {
+                    for (from, _) in iter::once((from, to.clone())).chain(renames) {
+                        let _ = fs::remove_file(from);
+                    }
+                    rollback(committed);
+                    return Err(Error::Io { path: to, source });
+                }
+            }
+        }
+        for backup in committed.into_iter().filter_map(|(_, backup)| backup) {
+            if let Err(e) = fs::remove_file(&backup) {
+                log::warn!("cannot remove replaced output {:?}: {}", backup, e);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Staging {
+    fn drop(&mut self) {
+        if self.staged.is_empty() {
+            return;
+        }
+        log::warn!(
+            "{} staged outputs not committed, left as they were",
+            self.staged.len()
+        );
+        for (staged, _) in &self.staged {
+            for path in [sign::signature_path(staged), staged.clone()] {
+                if let Err(e) = fs::remove_file(&path) {
+                    if e.kind() != io::ErrorKind::NotFound {
+                        log::warn!("cannot remove staged output {:?}: {}", path, e);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// A hidden file next to `path`, e.g. `.en.rs.staging-123-0` for `en.rs`.
+fn hidden_path(path: &path::Path, suffix: &str) -> path::PathBuf {
+    let mut name = ffi::OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(".");
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+// Rename `from` to `to`, keeping the file it replaces, if any, as a backup.
+fn replace(from: &path::Path, to: &path::Path) -> Result<Option<path::PathBuf>, io::Error> {
+    if to.is_dir() {
+        return Err(io::Error::other("is a directory"));
+    }
+    let backup = hidden_path(to, &format!("backup-{}", process::id()));
+    let backup = match fs::rename(to, &backup) {
+        Ok(()) => Some(backup),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e),
+    };
+    if let Err(e) = fs::rename(from, to) {
+        if let Some(backup) = &backup {
+            let _ = fs::rename(backup, to);
+        }
+        return Err(e);
+    }
+    Ok(backup)
+}
+
+// Restore the outputs replaced by a commit, in reverse.
+fn rollback(committed: Vec<(path::PathBuf, Option<path::PathBuf>)>) {
+    for (output, backup) in committed.into_iter().rev() {
+        let result = match &backup {
+            Some(backup) => fs::rename(backup, &output),
+            None => fs::remove_file(&output),
+        };
+        match result {
+            Ok(()) => log::warn!("commit of {:?} rolled back", output),
+            Err(e) => log::error!("cannot roll back commit of {:?}: {}", output, e),
+        }
+    }
+}
+
+impl Overrides {
+    fn apply(&self, args: &mut cli::Args) {
+        let Self {
+            case_folding,
+            const_assertions,
+            const_name,
+            duplicate_namespaces,
+            emit,
+            escape_unicode,
+            extra_category_namespaces,
+            extra_file_namespaces,
+            item_case,
+            item_prefix,
+            item_suffix,
+            link_trail_encoding,
+            max_width,
+            namespace_ids,
+            no_std,
+            normalization,
+            protocol_allow,
+            protocol_case,
+            protocol_fallback,
+            protocol_suffix,
+            target,
+            visibility,
+        } = self;
+        if let Some(case_folding) = case_folding {
+            args.case_folding = *case_folding;
+        }
+        if let Some(const_assertions) = const_assertions {
+            args.const_assertions = *const_assertions;
+        }
+        if const_name.is_some() {
+            args.const_name = const_name.clone();
+        }
+        if let Some(duplicate_namespaces) = duplicate_namespaces {
+            args.duplicate_namespaces = *duplicate_namespaces;
+        }
+        if let Some(emit) = emit {
+            args.emit = *emit;
+        }
+        if let Some(escape_unicode) = escape_unicode {
+            args.escape_unicode = *escape_unicode;
+        }
+        if let Some(extra_category_namespaces) = extra_category_namespaces {
+            args.extra_category_namespaces = extra_category_namespaces.clone();
+        }
+        if let Some(extra_file_namespaces) = extra_file_namespaces {
+            args.extra_file_namespaces = extra_file_namespaces.clone();
+        }
+        if item_case.is_some() {
+            args.item_case = *item_case;
+        }
+        if item_prefix.is_some() {
+            args.item_prefix = item_prefix.clone();
+        }
+        if item_suffix.is_some() {
+            args.item_suffix = item_suffix.clone();
+        }
+        if let Some(link_trail_encoding) = link_trail_encoding {
+            args.link_trail_encoding = *link_trail_encoding;
+        }
+        if max_width.is_some() {
+            args.max_width = *max_width;
+        }
+        if let Some(namespace_ids) = namespace_ids {
+            args.namespace_ids = *namespace_ids;
+        }
+        if let Some(no_std) = no_std {
+            args.no_std = *no_std;
+        }
+        if let Some(normalization) = normalization {
+            args.normalization = *normalization;
+        }
+        if let Some(protocol_allow) = protocol_allow {
+            args.protocol_allowlist = protocol_allow.clone();
+        }
+        if let Some(protocol_fallback) = protocol_fallback {
+            args.protocol_fallback = *protocol_fallback;
+        }
+        if let Some(protocol_case) = protocol_case {
+            args.protocol_case = *protocol_case;
+        }
+        if let Some(protocol_suffix) = protocol_suffix {
+            args.protocol_suffix = *protocol_suffix;
+        }
+        if let Some(target) = target {
+            args.target = vec![cli::TargetSelection::One(*target)];
+        }
+        if let Some(visibility) = visibility {
+            args.visibility = *visibility;
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use std::process;
+
+// The JSON output of the bundled fixture `name`, and the log.
+fn fetch(name: &str) -> (process::ExitStatus, String, String) {
+    let output = process::Command::new(env!("CARGO_BIN_EXE_fetch_mediawiki_configuration"))
+        .args(["--fixture", name, "--format", "json", "--log-level", "warn"])
+        .env_remove("FETCH_MEDIAWIKI_CONFIGURATION_DOMAIN")
+        .output()
+        .unwrap();
+    (
+        output.status,
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+fn assert_fails(name: &str, error: &str) {
+    let (status, stdout, stderr) = fetch(name);
+    assert!(!status.success(), "{} succeeded", name);
+    assert!(stdout.is_empty());
+    assert!(stderr.contains(error), "{}:\n{}", name, stderr);
+}
+
+fn assert_link_trail(name: &str, link_trail: &str) {
+    let (status, stdout, stderr) = fetch(name);
+    assert!(status.success(), "{} failed:\n{}", name, stderr);
+    let expected = format!("\"link_trail\": \"{}\"", link_trail);
+    assert!(stdout.contains(&expected), "{}:\n{}", name, stdout);
+}
+
+#[test]
+fn minimal() {
+    assert_link_trail("minimal", "abcdefghijklmnopqrstuvwxyz");
+}
+
+#[test]
+fn extensions() {
+    let (status, stdout, _) = fetch("extensions");
+    assert!(status.success());
+    assert!(stdout.contains("\"score\""));
+}
+
+#[test]
+fn linktrail_empty() {
+    assert_link_trail("linktrail-empty", "");
+}
+
+#[test]
+fn linktrail_named_group() {
+    assert_link_trail("linktrail-named-group", "abcdefghijklmnopqrstuvwxyz");
+}
+
+#[test]
+fn linktrail_unicode() {
+    assert_link_trail("linktrail-unicode", "abcdefghijklmnopqrstuvwxyzßäöü");
+}
+
+#[test]
+fn linktrail_no_group() {
+    assert_fails("linktrail-no-group", "group 1 not found");
+}
+
+#[test]
+fn linktrail_unsupported_modifier() {
+    assert_fails(
+        "linktrail-unsupported-modifier",
+        "unsupported PHP PCRE modifier: 'J'",
+    );
+}
+
+#[test]
+fn magic_word_shared_alias() {
+    let (status, _, stderr) = fetch("magic-word-shared-alias");
+    assert!(status.success());
+    assert!(
+        stderr.contains("magic word \"notoc\" is an alias of several magic words"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn api_error() {
+    assert_fails("api-error", "badvalue");
+}
+
+#[test]
+fn api_warning() {
+    assert_fails("api-warning", "unrecognizedvalues");
+}
+
+#[test]
+fn namespace_missing() {
+    assert_fails("namespace-missing", "namespace not found: \"Category\"");
+}
+
+#[test]
+fn no_query() {
+    assert_fails("no-query", "no query found");
+}
+
+#[test]
+fn unknown_field() {
+    assert_fails("unknown-field", "unknown field `statistics`");
+}
@@ -0,0 +1,56 @@
+use std::{env, process};
+
+// Identifiers of `std` and `alloc` that generated code must not contain with `--no-std`.
+const NOT_CORE: &[&str] = &["alloc", "std", "Box", "String", "Vec", "format", "vec"];
+
+fn generate(args: &[&str]) -> process::Output {
+    process::Command::new(env!("CARGO_BIN_EXE_fetch_mediawiki_configuration"))
+        .args(["--fixture", "minimal", "--log-level", "error"])
+        .args(args)
+        .env_remove("FETCH_MEDIAWIKI_CONFIGURATION_DOMAIN")
+        .output()
+        .unwrap()
+}
+
+fn assert_core_only(code: &str) {
+    let idents = code.split(|c: char| !c.is_alphanumeric() && c != '_');
+    for ident in idents {
+        assert!(
+            !NOT_CORE.contains(&ident),
+            "requires `{}`:\n{}",
+            ident,
+            code
+        );
+    }
+}
+
+#[test]
+fn no_std_outputs_are_core_only() {
+    for args in [
+        &[][..],
+        &["--const-name", "CONFIG"],
+        &["--const-name", "CONFIG", "--namespace-ids"],
+        &["--const-name", "CONFIG", "--const-assertions"],
+        &["--const-name", "CONFIG", "--link-trail-encoding", "ranges"],
+    ] {
+        let output = generate(&[&["--no-std"][..], args].concat());
+        assert!(output.status.success(), "{:?} failed", args);
+        assert_core_only(&String::from_utf8(output.stdout).unwrap());
+    }
+}
+
+#[test]
+fn no_std_rejects_constructor() {
+    let output = generate(&["--no-std", "--emit", "constructor"]);
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("requires `"));
+}
+
+#[test]
+#[should_panic(expected = "requires `")]
+fn constructor_is_not_core_only() {
+    let output = generate(&["--emit", "constructor"]);
+    assert!(output.status.success());
+    assert_core_only(&String::from_utf8(output.stdout).unwrap());
+}
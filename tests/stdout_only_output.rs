@@ -0,0 +1,63 @@
+use std::{env, fs, path, process};
+
+fn run(args: &[&str]) -> process::Output {
+    process::Command::new(env!("CARGO_BIN_EXE_fetch_mediawiki_configuration"))
+        .args(args)
+        .env_remove("FETCH_MEDIAWIKI_CONFIGURATION_DOMAIN")
+        .output()
+        .unwrap()
+}
+
+fn temp_path(name: &str) -> path::PathBuf {
+    env::temp_dir().join(format!(
+        "fetch_mediawiki_configuration-{}-{}",
+        process::id(),
+        name
+    ))
+}
+
+#[test]
+fn stdout_is_the_output_written_to_a_file() {
+    let path = temp_path("output.rs");
+    let path_str = path.to_str().unwrap();
+    let to_file = run(&["--fixture", "minimal", "--output", path_str]);
+    assert!(to_file.status.success());
+    let expected = fs::read(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    let to_stdout = run(&["--fixture", "minimal", "--stdout-only-output"]);
+    assert!(to_stdout.status.success());
+    assert_eq!(to_stdout.stdout, expected);
+    assert!(!to_stdout.stderr.is_empty());
+}
+
+#[test]
+fn check_findings_go_to_stderr() {
+    let path = temp_path("stale.rs");
+    let path_str = path.to_str().unwrap();
+    assert!(run(&["--fixture", "minimal", "--output", path_str])
+        .status
+        .success());
+    let generated = fs::read_to_string(&path).unwrap();
+    fs::write(&path, generated.replacen("\"notoc\" , ", "", 1)).unwrap();
+
+    let checked = run(&[
+        "--fixture",
+        "minimal",
+        "--check",
+        path_str,
+        "--stdout-only-output",
+    ]);
+    fs::remove_file(&path).unwrap();
+    assert!(!checked.status.success());
+    assert!(checked.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&checked.stderr).contains("magic_words"));
+}
+
+#[test]
+fn help_goes_to_stderr() {
+    let help = run(&["--help", "--stdout-only-output"]);
+    assert!(help.status.success());
+    assert!(help.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&help.stderr).contains("--stdout-only-output"));
+}